@@ -0,0 +1,71 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use rusty_canvas::drawing_app::{application, canvas, commands};
+
+/// Canvas with walls every other row, each with a gap at alternating ends,
+/// so the fillable area is one long path which doubles back on itself
+fn serpentine_canvas(width: i32, height: i32) -> canvas::Canvas {
+    let mut canvas = canvas::Canvas::blank_canvas(width, height, " ");
+    for row in (1..height).step_by(2) {
+        let gap = if row % 4 == 1 { width - 1 } else { 0 };
+        for column in 0..width {
+            if column != gap {
+                canvas.layers[0].pixels[row as usize][column as usize] = canvas::Cell::from("#");
+            }
+        }
+    }
+    canvas
+}
+
+/// Canvas scattered with walls from a fixed pseudo-random sequence
+fn scattered_canvas(width: i32, height: i32) -> canvas::Canvas {
+    let mut canvas = canvas::Canvas::blank_canvas(width, height, " ");
+    let mut seed: u32 = 12345;
+    for pixel in canvas.layers[0].pixels.iter_mut().flatten() {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        if (seed >> 16) % 5 < 2 {
+            *pixel = canvas::Cell::from("#");
+        }
+    }
+    canvas
+}
+
+fn flood_fill_command(connectivity: commands::Connectivity) -> commands::DrawCommand {
+    commands::DrawCommand {
+        name: commands::CommandName::FloodFill,
+        position: canvas::Point { x: 0, y: 0 },
+        character: String::from("o"),
+        flood_fill: commands::FloodFillOptions {
+            connectivity,
+            ..commands::FloodFillOptions::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Fills from the corner of big canvases, applied the way a draw request applies them
+/// Run with `cargo bench`
+fn large_flood_fills(c: &mut Criterion) {
+    let cases = vec![
+        ("blank 2000x2000, 4-way", canvas::Canvas::blank_canvas(2000, 2000, " "), commands::Connectivity::Four),
+        ("serpentine 2000x2000, 4-way", serpentine_canvas(2000, 2000), commands::Connectivity::Four),
+        ("scattered 2000x2000, 8-way", scattered_canvas(2000, 2000), commands::Connectivity::Eight),
+    ];
+
+    let mut group = c.benchmark_group("flood fill");
+    group.sample_size(10);
+    for (name, canvas, connectivity) in cases {
+        let command = flood_fill_command(connectivity);
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || vec![command.clone()],
+                |commands| application::apply_draw_commands(&canvas, commands, None).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, large_flood_fills);
+criterion_main!(benches);
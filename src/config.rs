@@ -1,70 +1,701 @@
+use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use super::drawing_app::canvas;
+use super::drawing_app::mismatch::{ConfiguredCanvas, OnMismatch};
 
 const CANVAS_WIDTH: i32 = 30;
 const CANVAS_HEIGHT: i32 = 30;
-const BLANK_CHARACTER: char = '⬛';
-const CANVAS: &'static str = "canvas_data.txt";
-const TEMP_CANVAS: &'static str = "temp_canvas_data.txt";
+const BLANK_CHARACTER: &str = "⬛";
+const DATA_DIR: &str = ".";
+const CANVAS: &str = "canvas_data.txt";
+const TEMP_CANVAS: &str = "temp_canvas_data.txt";
+const SNAPSHOT_CANVAS: &str = "snapshot_canvas_data.txt";
+const LOCK_FILE: &str = "rusty_canvas.lock";
+const ADDRESS: &str = "127.0.0.1";
+const PORT: u16 = 8080;
+const UNIX_SOCKET_PREFIX: &str = "unix:";
+const RATE_BURST: u32 = 10;
+
+/// Config file read when none is named, if it exists
+const CONFIG_FILE: &str = "rusty_canvas.toml";
+/// Prefix of the environment variables which override the config file
+const ENV_PREFIX: &str = "RUSTY_CANVAS_";
+
+pub const USAGE: &str = "Usage: rusty_canvas [OPTIONS]
+
+Options:
+    --width <CELLS>       Width of a new canvas, at most 4096 [default: 30]
+    --height <CELLS>      Height of a new canvas, at most 4096 [default: 30]
+    --blank <CHARACTER>   Character shown wherever nothing is drawn [default: ⬛]
+    --data-dir <PATH>     Directory the canvas is saved in [default: .]
+    --address <ADDRESS>   Address to listen on, or unix:<PATH> for a Unix socket [default: 127.0.0.1]
+    --port <PORT>         Port to listen on [default: 8080]
+    --fresh               Start with a blank canvas, throwing away the saved one (only as a flag)
+    --strict              Refuse draw requests which are partly off the canvas or draw nothing,
+                          unless they set strict to false [default: clip them to the canvas]
+    --rate-limit <REQUESTS>
+                          Draw requests each client can make per minute [default: no limit]
+    --rate-burst <REQUESTS>
+                          Draw requests a client can make at once, before the rate limit applies [default: 10]
+    --cell-quota <CELLS>  Cells each client can change per minute [default: no quota]
+    --on-mismatch <WHAT>  What to do if the saved canvas has a different size or blank character:
+                          refuse, resize or migrate [default: refuse]
+    --config <PATH>       TOML file to read settings from [default: rusty_canvas.toml, if it exists]
+    --help                Print this message
+
+Every option other than --fresh can also be set in the config file (using underscores, as in data_dir = \"canvas\")
+or with an environment variable (as in RUSTY_CANVAS_DATA_DIR=canvas).
+Flags win over environment variables, which win over the config file.
+
+Clients are told apart by their API key, or by their address when they don't send one.
+
+API keys can only be set in the config file, as [[api_keys]] tables with a name, key and role
+(viewer, drawer or admin). Without any keys, anyone who can reach the server can use every route.";
+
+/// Where the server listens for requests
+#[derive(Clone, Debug, PartialEq)]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf), // path of a Unix domain socket
+}
+
+/// What an API key is allowed to do, each role can do everything the roles before it can
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer, // look at the canvas, its layers, stamps and locks
+    Drawer, // draw, and change layers, stamps and the clip
+    Admin, // lock regions, and resize or crop the canvas
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Drawer => write!(f, "drawer"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// A key clients send in the X-API-Key header, and the name its changes are attributed to
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    pub name: String,
+    pub key: String,
+    pub role: Role,
+}
+
+/// How much each client can draw, counted by API key or else by address
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>, // no rate limit without one
+    pub burst: u32, // requests which can be made at once, the bucket refills at the rate limit
+    pub cells_per_minute: Option<u32>, // no quota without one
+}
 
 pub struct Config {
     pub width: i32,
     pub height: i32,
-    pub blank_character: char,
+    pub blank_character: String,
     pub canvas_location: String,
     pub temp_canvas_location: String,
+    pub snapshot_canvas_location: String,
+    pub lock_location: String,
+    pub listen: Listen,
+    pub fresh: bool,
+    pub on_mismatch: OnMismatch,
+    pub configured_canvas: ConfiguredCanvas, // only what was set, a saved canvas keeps anything else
+    pub strict: bool,
+    pub api_keys: Vec<ApiKey>, // no keys leaves every route open
+    pub rate_limits: RateLimits,
 }
 
-impl Config {
-    pub fn new(mut args: std::env::Args) -> Result<Config, &'static str> {
-        if args.len() > 0 {
-            // we want a fresh canvas, so clean-up
-            if Path::new(&CANVAS).exists() {
-                fs::remove_file(&CANVAS).unwrap();
-            }
+/// Reasons the application can't be configured, or `--help` was asked for
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    Help,
+    UnknownArgument(String),
+    MissingValue(String),
+    InvalidValue { setting: String, value: String, reason: String },
+    ConfigFile { path: String, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{}", USAGE),
+            ConfigError::UnknownArgument(argument) => write!(f, "Unknown argument '{}'.", argument),
+            ConfigError::MissingValue(flag) => write!(f, "{} needs a value.", flag),
+            ConfigError::InvalidValue { setting, value, reason } => {
+                write!(f, "Invalid {} '{}': {}.", setting, value, reason)
+            },
+            ConfigError::ConfigFile { path, reason } => write!(f, "Problem reading config file {}: {}", path, reason),
         }
+    }
+}
 
-        args.next();
+impl Error for ConfigError {}
 
-        let width = match args.next() {
-            Some(width) => width.parse::<i32>().unwrap_or_else(|err| {
-                println!("Problem parsing width: {}", err);
-                return CANVAS_WIDTH;
-            }),
-            None => CANVAS_WIDTH,
-        };
+/// Settings gathered from one source, where anything left out falls through to the next source
+#[derive(Debug, Default)]
+struct Settings {
+    width: Option<String>,
+    height: Option<String>,
+    blank: Option<String>,
+    data_dir: Option<String>,
+    address: Option<String>,
+    port: Option<String>,
+    fresh: Option<bool>,
+    on_mismatch: Option<String>,
+    strict: Option<bool>,
+    api_keys: Option<Vec<ApiKey>>,
+    rate_limit: Option<String>,
+    rate_burst: Option<String>,
+    cell_quota: Option<String>,
+}
+
+impl Settings {
+    /// Settings from this source, with any given by a more important source replacing them
+    fn overridden_by(self, other: Settings) -> Settings {
+        Settings {
+            width: other.width.or(self.width),
+            height: other.height.or(self.height),
+            blank: other.blank.or(self.blank),
+            data_dir: other.data_dir.or(self.data_dir),
+            address: other.address.or(self.address),
+            port: other.port.or(self.port),
+            fresh: other.fresh.or(self.fresh),
+            on_mismatch: other.on_mismatch.or(self.on_mismatch),
+            strict: other.strict.or(self.strict),
+            api_keys: other.api_keys.or(self.api_keys),
+            rate_limit: other.rate_limit.or(self.rate_limit),
+            rate_burst: other.rate_burst.or(self.rate_burst),
+            cell_quota: other.cell_quota.or(self.cell_quota),
+        }
+    }
 
-        let height = match args.next() {
-            Some(height) => height.parse::<i32>().unwrap_or_else(|err| {
-                println!("Problem parsing height: {}", err);
-                return CANVAS_HEIGHT;
-            }),
-            None => CANVAS_HEIGHT,
+    /// Sets a setting by its name, as used in the config file
+    /// Returns false if there is no setting with that name
+    fn set(&mut self, name: &str, value: String) -> Result<bool, ConfigError> {
+        match name {
+            "width" => self.width = Some(value),
+            "height" => self.height = Some(value),
+            "blank" => self.blank = Some(value),
+            "data_dir" => self.data_dir = Some(value),
+            "address" => self.address = Some(value),
+            "port" => self.port = Some(value),
+            // so that a restart never throws the canvas away
+            "fresh" => return Err(invalid(name, &value, "can only be given as the --fresh flag")),
+            "on_mismatch" => self.on_mismatch = Some(value),
+            "strict" => self.strict = Some(parse_bool(name, &value)?),
+            "rate_limit" => self.rate_limit = Some(value),
+            "rate_burst" => self.rate_burst = Some(value),
+            "cell_quota" => self.cell_quota = Some(value),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// The TOML file allows numbers to be written as numbers, so its settings are read as values first
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileSettings {
+    width: Option<toml::Value>,
+    height: Option<toml::Value>,
+    blank: Option<String>,
+    data_dir: Option<String>,
+    address: Option<String>,
+    port: Option<toml::Value>,
+    fresh: Option<toml::Value>, // refused, see `read_config_file`
+    on_mismatch: Option<String>,
+    strict: Option<bool>,
+    api_keys: Option<Vec<ApiKey>>,
+    rate_limit: Option<toml::Value>,
+    rate_burst: Option<toml::Value>,
+    cell_quota: Option<toml::Value>,
+}
+
+impl Config {
+    /// Builds the configuration from command-line arguments (including the program name) and environment variables
+    /// Defaults are overridden by the config file, then the environment, then the flags
+    pub fn new<A, V>(args: A, vars: V) -> Result<Config, ConfigError>
+    where
+        A: Iterator<Item = String>,
+        V: Iterator<Item = (String, String)>,
+    {
+        Config::with_default_file(args, vars, Path::new(CONFIG_FILE))
+    }
+
+    /// Like `new`, reading `default_file` if it exists when no config file is named
+    fn with_default_file<A, V>(args: A, vars: V, default_file: &Path) -> Result<Config, ConfigError>
+    where
+        A: Iterator<Item = String>,
+        V: Iterator<Item = (String, String)>,
+    {
+        let (flags, config_flag) = parse_args(args.skip(1))?;
+        let (environment, config_variable) = parse_vars(vars)?;
+
+        let file = match config_flag.or(config_variable) {
+            Some(path) => read_config_file(&path)?,
+            None if default_file.exists() => read_config_file(&default_file.to_string_lossy())?,
+            None => Settings::default(),
         };
 
-        let blank_character = match args.next() {
-            Some(blank_character) => blank_character
-                .chars()
-                .take(1)
-                .next()
-                .unwrap_or_else(|| {
-                    println!("Problem parsing blank character");
-                    return BLANK_CHARACTER;
+        Config::from_settings(file.overridden_by(environment).overridden_by(flags))
+    }
+
+    /// Checks every setting, falling back to the defaults for any left out
+    fn from_settings(settings: Settings) -> Result<Config, ConfigError> {
+        let width_is_set = settings.width.is_some();
+        let height_is_set = settings.height.is_some();
+        let blank_is_set = settings.blank.is_some();
+        let width = parse_size("width", settings.width, CANVAS_WIDTH)?;
+        let height = parse_size("height", settings.height, CANVAS_HEIGHT)?;
+
+        let blank_character = match settings.blank {
+            Some(blank) => {
+                let graphemes = canvas::graphemes(&blank);
+                if graphemes.len() != 1 {
+                    return Err(invalid("blank", &blank, "should be a single character"));
                 }
-            ),
-            None => BLANK_CHARACTER,
+                blank
+            },
+            None => String::from(BLANK_CHARACTER),
+        };
+
+        let data_dir = PathBuf::from(settings.data_dir.unwrap_or_else(|| String::from(DATA_DIR)));
+        let canvas_location = data_dir.join(CANVAS).to_string_lossy().into_owned();
+        let temp_canvas_location = data_dir.join(TEMP_CANVAS).to_string_lossy().into_owned();
+        let snapshot_canvas_location = data_dir.join(SNAPSHOT_CANVAS).to_string_lossy().into_owned();
+        let lock_location = data_dir.join(LOCK_FILE).to_string_lossy().into_owned();
+
+        let listen = listen_on(settings.address, settings.port)?;
+
+        let api_keys = settings.api_keys.unwrap_or_default();
+        check_api_keys(&api_keys)?;
+
+        let rate_limits = RateLimits {
+            requests_per_minute: parse_limit("rate_limit", settings.rate_limit)?,
+            burst: parse_limit("rate_burst", settings.rate_burst)?.unwrap_or(RATE_BURST),
+            cells_per_minute: parse_limit("cell_quota", settings.cell_quota)?,
         };
 
-        let canvas_location = match args.next() {
-            Some(canvas) => canvas,
-            None => String::from(CANVAS),
+        let on_mismatch = match settings.on_mismatch {
+            Some(value) => value.parse().map_err(|reason: String| invalid("on_mismatch", &value, &reason))?,
+            None => OnMismatch::default(),
         };
 
-        let temp_canvas_location = match args.next() {
-            Some(canvas) => canvas,
-            None => String::from(TEMP_CANVAS),
+        let configured_canvas = ConfiguredCanvas {
+            width: Some(width).filter(|_| width_is_set),
+            height: Some(height).filter(|_| height_is_set),
+            blank_character: Some(blank_character.clone()).filter(|_| blank_is_set),
         };
 
-        Ok(Config { width, height, blank_character, canvas_location, temp_canvas_location })
+        Ok(Config {
+            width,
+            height,
+            blank_character,
+            canvas_location,
+            temp_canvas_location,
+            snapshot_canvas_location,
+            lock_location,
+            listen,
+            fresh: settings.fresh.unwrap_or(false),
+            on_mismatch,
+            configured_canvas,
+            strict: settings.strict.unwrap_or(false),
+            api_keys,
+            rate_limits,
+        })
+    }
+}
+
+/// Reads `--flag value` and `--flag=value` arguments, and the name of any config file
+fn parse_args<A>(mut args: A) -> Result<(Settings, Option<String>), ConfigError>
+where
+    A: Iterator<Item = String>,
+{
+    let mut settings = Settings::default();
+    let mut config_file = None;
+
+    while let Some(argument) = args.next() {
+        let (flag, inline_value) = match argument.split_once('=') {
+            Some((flag, value)) => (String::from(flag), Some(String::from(value))),
+            None => (argument.clone(), None),
+        };
+
+        let name = match flag.strip_prefix("--") {
+            Some(name) => name.replace('-', "_"),
+            None => return Err(ConfigError::UnknownArgument(argument)),
+        };
+
+        match name.as_str() {
+            "help" => return Err(ConfigError::Help),
+            // switches are on when given without a value
+            "fresh" => {
+                let value = inline_value.unwrap_or_else(|| String::from("true"));
+                settings.fresh = Some(parse_bool(&name, &value)?);
+                continue;
+            },
+            "strict" => {
+                let value = inline_value.unwrap_or_else(|| String::from("true"));
+                settings.set(&name, value)?;
+                continue;
+            },
+            _ => {},
+        }
+
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(ConfigError::MissingValue(flag)),
+        };
+
+        if name == "config" {
+            config_file = Some(value);
+        } else if !settings.set(&name, value)? {
+            return Err(ConfigError::UnknownArgument(flag));
+        }
+    }
+
+    Ok((settings, config_file))
+}
+
+/// Picks out the `RUSTY_CANVAS_` environment variables, and the name of any config file
+fn parse_vars<V>(vars: V) -> Result<(Settings, Option<String>), ConfigError>
+where
+    V: Iterator<Item = (String, String)>,
+{
+    let mut settings = Settings::default();
+    let mut config_file = None;
+
+    for (key, value) in vars {
+        let name = match key.strip_prefix(ENV_PREFIX) {
+            Some(name) => name.to_ascii_lowercase(),
+            None => continue,
+        };
+
+        if name == "config" {
+            config_file = Some(value);
+        } else if !settings.set(&name, value)? {
+            return Err(ConfigError::UnknownArgument(key));
+        }
+    }
+
+    Ok((settings, config_file))
+}
+
+fn read_config_file(path: &str) -> Result<Settings, ConfigError> {
+    let file_error = |reason: String| ConfigError::ConfigFile { path: String::from(path), reason };
+
+    let contents = fs::read_to_string(path).map_err(|err| file_error(err.to_string()))?;
+    let file: FileSettings = toml::from_str(&contents).map_err(|err| file_error(err.to_string()))?;
+    // the file is read on every start, so fresh in it would throw the canvas away on every restart
+    if file.fresh.is_some() {
+        return Err(file_error(String::from("fresh can only be given as the --fresh flag")));
+    }
+
+    // numbers and strings are both accepted, so `port = 8080` and `port = "8080"` mean the same
+    let text = |value: Option<toml::Value>| value.map(|value| match value {
+        toml::Value::String(text) => text,
+        other => other.to_string(),
+    });
+
+    Ok(Settings {
+        width: text(file.width),
+        height: text(file.height),
+        blank: file.blank,
+        data_dir: file.data_dir,
+        address: file.address,
+        port: text(file.port),
+        fresh: None,
+        on_mismatch: file.on_mismatch,
+        strict: file.strict,
+        api_keys: file.api_keys,
+        rate_limit: text(file.rate_limit),
+        rate_burst: text(file.rate_burst),
+        cell_quota: text(file.cell_quota),
+    })
+}
+
+fn invalid(setting: &str, value: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        setting: String::from(setting),
+        value: String::from(value),
+        reason: String::from(reason),
+    }
+}
+
+/// Checks every API key has a name and a key, and that no key is given twice
+fn check_api_keys(api_keys: &[ApiKey]) -> Result<(), ConfigError> {
+    for (index, api_key) in api_keys.iter().enumerate() {
+        // the key itself is never repeated back, only the name it was given
+        if api_key.name.is_empty() {
+            return Err(invalid("api_keys", &api_key.name, "every key needs a name"));
+        }
+        if api_key.key.is_empty() {
+            return Err(invalid("api_keys", &api_key.name, "the key is empty"));
+        }
+        if api_keys[..index].iter().any(|other| other.key == api_key.key) {
+            return Err(invalid("api_keys", &api_key.name, "the key is the same as another one"));
+        }
+    }
+    Ok(())
+}
+
+/// Reads an optional canvas width or height, which can be at most `canvas::MAX_CANVAS_DIMENSION`
+fn parse_size(setting: &str, value: Option<String>, default: i32) -> Result<i32, ConfigError> {
+    match value {
+        Some(value) => match value.parse::<i32>() {
+            Ok(size) if size > canvas::MAX_CANVAS_DIMENSION => Err(invalid(
+                setting,
+                &value,
+                &format!("should be at most {}", canvas::MAX_CANVAS_DIMENSION),
+            )),
+            Ok(size) if size > 0 => Ok(size),
+            _ => Err(invalid(setting, &value, "should be a whole number above 0")),
+        },
+        None => Ok(default),
+    }
+}
+
+/// Reads an optional limit, which can't be 0 since that would refuse everything
+fn parse_limit(setting: &str, value: Option<String>) -> Result<Option<u32>, ConfigError> {
+    match value {
+        Some(value) => match value.parse::<u32>() {
+            Ok(limit) if limit > 0 => Ok(Some(limit)),
+            _ => Err(invalid(setting, &value, "should be a whole number above 0")),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_bool(setting: &str, value: &str) -> Result<bool, ConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(invalid(setting, value, "should be true or false")),
+    }
+}
+
+/// Works out where to listen from an address and a port
+/// An address starting with `unix:` names a Unix domain socket, and the port is ignored
+fn listen_on(address: Option<String>, port: Option<String>) -> Result<Listen, ConfigError> {
+    let address = address.unwrap_or_else(|| String::from(ADDRESS));
+    if let Some(path) = address.strip_prefix(UNIX_SOCKET_PREFIX) {
+        return Ok(Listen::Unix(PathBuf::from(path)));
+    }
+
+    let ip = address
+        .parse::<IpAddr>()
+        .map_err(|_| invalid("address", &address, "should be an IP address, or unix: followed by a path"))?;
+
+    let port = match port {
+        Some(port) => port.parse::<u16>().map_err(|_| invalid("port", &port, "should be a number from 0 to 65535"))?,
+        None => PORT,
+    };
+
+    Ok(Listen::Tcp(SocketAddr::new(ip, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> {
+        std::iter::once(String::from("rusty_canvas"))
+            .chain(line.split_whitespace().map(String::from))
+            .collect::<Vec<String>>()
+            .into_iter()
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (String::from(*key), String::from(*value)))
+            .collect::<Vec<(String, String)>>()
+            .into_iter()
     }
-}
\ No newline at end of file
+
+    /// Builds a config the way `Config::new` does, but never reads a config file from the working directory
+    fn new_config<A, V>(args: A, vars: V) -> Result<Config, ConfigError>
+    where
+        A: Iterator<Item = String>,
+        V: Iterator<Item = (String, String)>,
+    {
+        let missing = env::temp_dir().join(format!("{}.toml", Uuid::new_v4()));
+        Config::with_default_file(args, vars, &missing)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = new_config(args(""), vars(&[])).unwrap();
+
+        assert_eq!((30, 30), (config.width, config.height));
+        assert_eq!("⬛", config.blank_character);
+        assert_eq!(Path::new(".").join("canvas_data.txt").to_string_lossy(), config.canvas_location);
+        assert_eq!(Listen::Tcp(SocketAddr::from(([127, 0, 0, 1], 8080))), config.listen);
+        assert!(!config.fresh);
+        assert_eq!(OnMismatch::Refuse, config.on_mismatch);
+        assert!(!config.strict);
+        assert_eq!(ConfiguredCanvas::default(), config.configured_canvas);
+        assert_eq!(RateLimits { requests_per_minute: None, burst: 10, cells_per_minute: None }, config.rate_limits);
+    }
+
+    #[test]
+    fn test_flags() {
+        let config = new_config(
+            args("--width 20 --height=12 --blank 🟪 --data-dir /tmp/canvas --address 0.0.0.0 --port 9000 --fresh"),
+            vars(&[]),
+        ).unwrap();
+
+        assert_eq!((20, 12), (config.width, config.height));
+        assert_eq!("🟪", config.blank_character);
+        assert_eq!("/tmp/canvas/canvas_data.txt", config.canvas_location);
+        assert_eq!("/tmp/canvas/temp_canvas_data.txt", config.temp_canvas_location);
+        assert_eq!("/tmp/canvas/snapshot_canvas_data.txt", config.snapshot_canvas_location);
+        assert_eq!("/tmp/canvas/rusty_canvas.lock", config.lock_location);
+        assert_eq!(Listen::Tcp(SocketAddr::from(([0, 0, 0, 0], 9000))), config.listen);
+        assert!(config.fresh);
+        assert_eq!(
+            ConfiguredCanvas { width: Some(20), height: Some(12), blank_character: Some(String::from("🟪")) },
+            config.configured_canvas
+        );
+
+        let config = new_config(args("--on-mismatch migrate"), vars(&[("RUSTY_CANVAS_ON_MISMATCH", "resize")])).unwrap();
+        assert_eq!(OnMismatch::Migrate, config.on_mismatch);
+
+        let config = new_config(args("--strict"), vars(&[])).unwrap();
+        assert!(config.strict);
+        let config = new_config(args("--strict=false"), vars(&[("RUSTY_CANVAS_STRICT", "true")])).unwrap();
+        assert!(!config.strict);
+
+        let config = new_config(args("--rate-limit 60 --cell-quota=500"), vars(&[("RUSTY_CANVAS_RATE_BURST", "5")])).unwrap();
+        assert_eq!(RateLimits { requests_per_minute: Some(60), burst: 5, cells_per_minute: Some(500) }, config.rate_limits);
+
+        let config = new_config(args("--address unix:/tmp/canvas.sock --port 9000"), vars(&[])).unwrap();
+        assert_eq!(Listen::Unix(PathBuf::from("/tmp/canvas.sock")), config.listen);
+    }
+
+    #[test]
+    fn test_invalid_arguments_are_errors() {
+        assert_eq!(Err(ConfigError::Help), new_config(args("--width 3 --help"), vars(&[])).map(|_| ()));
+        assert_eq!(
+            Err(ConfigError::UnknownArgument(String::from("20"))),
+            new_config(args("20 30"), vars(&[])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ConfigError::UnknownArgument(String::from("--colour"))),
+            new_config(args("--colour red"), vars(&[])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ConfigError::MissingValue(String::from("--port"))),
+            new_config(args("--port"), vars(&[])).map(|_| ())
+        );
+        assert_eq!(Err(invalid("width", "wide", "should be a whole number above 0")), new_config(args("--width wide"), vars(&[])).map(|_| ()));
+        assert!(new_config(args("--height 0"), vars(&[])).is_err());
+        assert_eq!(Err(invalid("height", "5000", "should be at most 4096")), new_config(args("--height 5000"), vars(&[])).map(|_| ()));
+        assert!(new_config(args("--blank ab"), vars(&[])).is_err());
+        assert!(new_config(args("--port 70000"), vars(&[])).is_err());
+        assert!(new_config(args("--address localhost"), vars(&[])).is_err());
+        assert!(new_config(args("--fresh=maybe"), vars(&[])).is_err());
+        assert!(new_config(args("--on-mismatch ignore"), vars(&[])).is_err());
+        assert_eq!(Err(invalid("rate_limit", "0", "should be a whole number above 0")), new_config(args("--rate-limit 0"), vars(&[])).map(|_| ()));
+        assert!(new_config(args("--cell-quota lots"), vars(&[])).is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let config_file = env::temp_dir().join(format!("{}.toml", Uuid::new_v4()));
+        fs::write(&config_file, "width = 10\nheight = 11\nblank = \".\"\nport = 7000\n").unwrap();
+        let config_path = config_file.to_string_lossy().into_owned();
+
+        let config = new_config(
+            args(&format!("--config {} --height 13", config_path)),
+            vars(&[("RUSTY_CANVAS_HEIGHT", "12"), ("RUSTY_CANVAS_PORT", "7001"), ("HOME", "/root")]),
+        ).unwrap();
+
+        // the file sets the width and blank, the environment the port, and the flags the height
+        assert_eq!((10, 13), (config.width, config.height));
+        assert_eq!(".", config.blank_character);
+        assert_eq!(Listen::Tcp(SocketAddr::from(([127, 0, 0, 1], 7001))), config.listen);
+
+        // the config file can also be named by the environment
+        let config = new_config(args(""), vars(&[("RUSTY_CANVAS_CONFIG", &config_path)])).unwrap();
+        assert_eq!(11, config.height);
+
+        fs::remove_file(&config_file).unwrap();
+    }
+
+    #[test]
+    fn test_api_keys_are_read_from_the_config_file() {
+        let config_file = env::temp_dir().join(format!("{}.toml", Uuid::new_v4()));
+        let config_path = config_file.to_string_lossy().into_owned();
+        let keys = "[[api_keys]]\nname = \"ada\"\nkey = \"k1\"\nrole = \"admin\"\n\n\
+            [[api_keys]]\nname = \"bob\"\nkey = \"k2\"\nrole = \"viewer\"\n";
+        fs::write(&config_file, keys).unwrap();
+
+        let config = new_config(args(&format!("--config {}", config_path)), vars(&[])).unwrap();
+        assert_eq!(2, config.api_keys.len());
+        assert_eq!(ApiKey { name: String::from("bob"), key: String::from("k2"), role: Role::Viewer }, config.api_keys[1]);
+        assert!(Role::Viewer < Role::Drawer && Role::Drawer < Role::Admin);
+        assert!(new_config(args(""), vars(&[])).unwrap().api_keys.is_empty());
+
+        fs::write(&config_file, keys.replace("k2", "k1")).unwrap();
+        let repeated = new_config(args(&format!("--config {}", config_path)), vars(&[]));
+        assert_eq!(Err(invalid("api_keys", "bob", "the key is the same as another one")), repeated.map(|_| ()));
+
+        fs::write(&config_file, keys.replace("viewer", "owner")).unwrap();
+        let unknown_role = new_config(args(&format!("--config {}", config_path)), vars(&[]));
+        assert!(matches!(unknown_role, Err(ConfigError::ConfigFile { .. })));
+
+        fs::remove_file(&config_file).unwrap();
+    }
+
+    #[test]
+    fn test_bad_config_files_are_errors() {
+        let config_file = env::temp_dir().join(format!("{}.toml", Uuid::new_v4()));
+        let config_path = config_file.to_string_lossy().into_owned();
+
+        // a named config file has to exist
+        let missing = new_config(args(&format!("--config {}", config_path)), vars(&[]));
+        assert!(matches!(missing, Err(ConfigError::ConfigFile { .. })));
+
+        fs::write(&config_file, "colour = \"red\"\n").unwrap();
+        let unknown = new_config(args(&format!("--config {}", config_path)), vars(&[]));
+        assert!(matches!(unknown, Err(ConfigError::ConfigFile { .. })));
+
+        // fresh is only a flag, so a restart can't throw the canvas away
+        fs::write(&config_file, "fresh = true\n").unwrap();
+        let fresh = new_config(args(&format!("--config {}", config_path)), vars(&[]));
+        assert!(matches!(fresh, Err(ConfigError::ConfigFile { .. })));
+
+        fs::write(&config_file, "width = -4\n").unwrap();
+        let invalid_width = new_config(args(&format!("--config {}", config_path)), vars(&[]));
+        assert!(matches!(invalid_width, Err(ConfigError::InvalidValue { .. })));
+
+        fs::remove_file(&config_file).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_environment_variables_are_errors() {
+        assert_eq!(
+            Err(ConfigError::UnknownArgument(String::from("RUSTY_CANVAS_COLOUR"))),
+            new_config(args(""), vars(&[("RUSTY_CANVAS_COLOUR", "red")])).map(|_| ())
+        );
+        assert_eq!(
+            Err(invalid("fresh", "yes", "can only be given as the --fresh flag")),
+            new_config(args(""), vars(&[("RUSTY_CANVAS_FRESH", "yes")])).map(|_| ())
+        );
+    }
+}
@@ -1,22 +1,47 @@
+use std::fmt;
 use std::fs;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 use super::commands::CommandName;
+use super::history::{Action, Change, Edit};
 use super::operations;
 
 #[derive(Clone)]
 pub struct ApplicationOptions {
     pub width: i32,
     pub height: i32,
-    pub blank_character: char,
+    pub blank_character: String,
     pub canvas_path: String,
     pub canvas_temp_path: String,
+    pub canvas_snapshot_path: String, // the canvas as it was before the last save, used if the canvas file is damaged
+    pub strict: bool, // refuse draw commands which don't fit the canvas, unless they say otherwise
+}
+
+/// How a damaged canvas file was replaced when the application opened
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recovery {
+    pub error: super::storage::CanvasFileError, // what was wrong with the canvas file
+    pub quarantine_path: String, // where the damaged file was moved
+    pub recovered_from: String, // the temp file or snapshot the canvas was restored from
+}
+
+impl fmt::Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} The damaged canvas file was moved to {}, and the canvas was recovered from {}.",
+            self.error, self.quarantine_path, self.recovered_from,
+        )
+    }
 }
 
 #[derive(Clone)]
 pub struct DrawingApplication {
     config: ApplicationOptions,
+    clip: Option<super::clip::Clip>,
 }
 
 impl DrawingApplication {
@@ -25,19 +50,174 @@ impl DrawingApplication {
     pub fn initialize(config: ApplicationOptions) -> DrawingApplication {
         DrawingApplication {
             config,
+            clip: None,
         }
     }
 
-    /// Applies draw commands to the canvas
+    /// Applies draw commands to the canvas, returning it along with how many cells they changed
     /// Saves the canvas to file, creating one if none exists
+    /// Fails with the reason any command couldn't be applied, in which case nothing is saved
+    /// Commands which don't say whether they are strict follow the server's setting
     pub fn draw(
         &self,
-        commands:Vec<super::commands::DrawCommand>,
+        mut commands:Vec<super::commands::DrawCommand>,
+    ) -> Result<(super::canvas::Canvas, usize), super::draw_error::DrawError> {
+        for command in commands.iter_mut() {
+            command.strict = command.strict.or(Some(self.config.strict));
+        }
+
+        let mut cells_changed = 0;
+        let canvas = self.update(|canvas| {
+            let drawn = apply_draw_commands(canvas, commands, self.clip.as_ref())?;
+            cells_changed = canvas.cells_changed(&drawn);
+            *canvas = drawn;
+            Ok(())
+        })?;
+        Ok((canvas, cells_changed))
+    }
+
+    /// Restricts every following draw command to the clip, replacing any previous clip
+    /// The clip is kept in memory, so it lasts until it's cleared or the server restarts
+    /// Only a valid clip is saved to the canvas history, so a refused one leaves the canvas file alone
+    pub fn set_clip(&mut self, clip: super::clip::Clip, author: Option<&str>) -> io::Result<super::clip::Clip> {
+        clip.validate(&self.canvas()?)?;
+        self.record(Edit::SetClip, author, |_| Ok(()))?;
+        self.clip = Some(clip.clone());
+        Ok(clip)
+    }
+
+    /// Lets draw commands change the whole canvas again
+    pub fn clear_clip(&mut self, author: Option<&str>) -> io::Result<()> {
+        self.record(Edit::ClearClip, author, |_| Ok(()))?;
+        self.clip = None;
+        Ok(())
+    }
+
+    /// The clip draw commands are currently restricted to, if any
+    pub fn clip(&self) -> Option<&super::clip::Clip> {
+        self.clip.as_ref()
+    }
+
+    /// Adds an empty layer to the top of the canvas
+    pub fn add_layer(&self, name: &str, author: Option<&str>) -> io::Result<super::canvas::Canvas> {
+        self.record(Edit::CreateLayer, author, |canvas| Ok(canvas.add_layer(name)?))
+    }
+
+    /// Deletes a layer from the canvas
+    /// Refused if it changes what a locked region shows
+    pub fn remove_layer(&self, name: &str, author: Option<&str>) -> io::Result<super::canvas::Canvas> {
+        self.update_layers(Edit::DeleteLayer, author, |canvas| Ok(canvas.remove_layer(name)?))
+    }
+
+    /// Moves a layer and/or changes whether it is shown
+    /// Refused if it changes what a locked region shows
+    pub fn update_layer(
+        &self,
+        name: &str,
+        index: Option<usize>,
+        visible: Option<bool>,
+        author: Option<&str>,
+    ) -> io::Result<super::canvas::Canvas> {
+        self.update_layers(Edit::UpdateLayer, author, |canvas| {
+            if let Some(visible) = visible {
+                canvas.set_layer_visibility(name, visible)?;
+            }
+            if let Some(index) = index {
+                canvas.move_layer(name, index)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Changes the size of the canvas, keeping the content at the anchor in place
+    pub fn resize(
+        &self,
+        dimensions: &super::canvas::Dimensions,
+        anchor: super::resize::Anchor,
+        author: Option<&str>,
+    ) -> io::Result<super::canvas::Canvas> {
+        self.record(Edit::Resize, author, |canvas| Ok(canvas.resize(dimensions, anchor)?))
+    }
+
+    /// Cuts the canvas down to a rectangle
+    pub fn crop(
+        &self,
+        position: &super::canvas::Point,
+        dimensions: &super::canvas::Dimensions,
+        author: Option<&str>,
+    ) -> io::Result<super::canvas::Canvas> {
+        self.record(Edit::Crop, author, |canvas| Ok(canvas.crop(position, dimensions)?))
+    }
+
+    /// Stores a stamp with the canvas, replacing any stamp of the same name
+    pub fn add_stamp(
+        &self,
+        name: &str,
+        pixels: Vec<Vec<super::canvas::Cell>>,
+        author: Option<&str>,
     ) -> io::Result<super::canvas::Canvas> {
+        self.record(Edit::CreateStamp, author, |canvas| Ok(canvas.add_stamp(name, pixels)?))
+    }
+
+    /// Protects a region of the canvas under a name
+    pub fn add_lock(
+        &self,
+        name: &str,
+        lock: super::locks::Lock,
+        author: Option<&str>,
+    ) -> io::Result<super::canvas::Canvas> {
+        self.record(Edit::CreateLock, author, |canvas| Ok(canvas.add_lock(name, lock)?))
+    }
+
+    /// Removes a lock from the canvas
+    pub fn remove_lock(&self, name: &str, author: Option<&str>) -> io::Result<super::canvas::Canvas> {
+        self.record(Edit::DeleteLock, author, |canvas| Ok(canvas.remove_lock(name)?))
+    }
+
+    /// Loads the saved canvas when the server starts, creating a blank one if there is none
+    /// A damaged canvas file is recovered first, and how is returned so it can be reported
+    /// A saved canvas which differs from the configured size or blank character is refused, resized or migrated,
+    /// and anything which wasn't configured follows the saved canvas
+    pub fn open(
+        &self,
+        configured: &super::mismatch::ConfiguredCanvas,
+        on_mismatch: super::mismatch::OnMismatch,
+    ) -> io::Result<(super::canvas::Canvas, Option<Recovery>)> {
+        let recovery = self.recover()?;
+        let canvas = self.update(|canvas| Ok(canvas.reconcile(configured, on_mismatch)?))?;
+        Ok((canvas, recovery))
+    }
+
+    /// Loads the saved canvas without changing it, or a blank canvas if none has been saved yet
+    /// Nothing is written, so reading the canvas never touches the file or the snapshot
+    pub fn canvas(&self) -> io::Result<super::canvas::Canvas> {
+        match fs::read_to_string(&self.config.canvas_path) {
+            Ok(contents) => self.load_saved(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.load_saved(""),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Makes sure the saved canvas has reached the disk, before the application exits
+    pub fn flush(&self) -> io::Result<()> {
+        if Path::new(&self.config.canvas_path).exists() {
+            fs::File::open(&self.config.canvas_path)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Loads the canvas from file, applies a change to it and saves the result
+    /// The save is atomic, and the canvas being replaced is kept as a snapshot
+    /// Nothing is saved if the change fails
+    fn update<F>(&self, change: F) -> io::Result<super::canvas::Canvas>
+    where
+        F: FnOnce(&mut super::canvas::Canvas) -> io::Result<()>,
+    {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(&self.config.canvas_path);
         
         match file {
@@ -46,79 +226,186 @@ impl DrawingApplication {
                 let mut contents = String::new();
                 file.read_to_string(&mut contents)?;
 
-                let canvas: super::canvas::Canvas;
-
-                if contents.is_empty() {
-                    canvas = super::canvas::Canvas::blank_canvas(
-                        self.config.width, 
-                        self.config.height,
-                        self.config.blank_character,
-                    );
-                } else {
-                    canvas = super::canvas::Canvas::from_chars(
-                        contents
-                            .lines()
-                            .map(|line| {
-                                line
-                                    .chars()
-                                    .collect()
-                            })
-                            .collect(),
-                        self.config.width, 
-                        self.config.height,
-                    );
-                }
+                let mut canvas = self.load_saved(&contents)?;
             
-                let updated_canvas = apply_draw_commands(&canvas, commands);
+                change(&mut canvas)?;
             
-                let temp_file = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .open(&self.config.canvas_temp_path);
-                
-                match temp_file {
-                    Ok(mut temp_file) => {
-                        temp_file.write(updated_canvas.to_string().as_bytes())?;
-
-                        fs::remove_file(&self.config.canvas_path)?;
-                        fs::rename(
-                            &self.config.canvas_temp_path, 
-                            &self.config.canvas_path
-                        )?;
-                        Ok(updated_canvas)
-                    },
-                    Err(e) => Err(e),
-                }
+                super::storage::save(&self.files(), &canvas.serialise(), !contents.is_empty())?;
+                Ok(canvas)
             },
             Err(e) => Err(e),
         }
     }
+
+    /// Reads the contents of the canvas file, which is blank until the first save
+    /// A damaged file is left alone, so nothing is saved over it until an operator steps in
+    fn load_saved(&self, contents: &str) -> io::Result<super::canvas::Canvas> {
+        if contents.is_empty() {
+            return Ok(super::canvas::Canvas::blank_canvas(
+                self.config.width,
+                self.config.height,
+                &self.config.blank_character,
+            ));
+        }
+        Ok(self.load(contents).map_err(|error| self.damaged(error))?)
+    }
+
+    fn load(&self, contents: &str) -> Result<super::canvas::Canvas, super::storage::CanvasFileError> {
+        super::canvas::Canvas::load(
+            contents,
+            self.config.width,
+            self.config.height,
+            &self.config.blank_character,
+        )
+    }
+
+    /// Like `update`, also adding the change and who made it to the canvas history
+    fn record<F>(&self, edit: Edit, author: Option<&str>, change: F) -> io::Result<super::canvas::Canvas>
+    where
+        F: FnOnce(&mut super::canvas::Canvas) -> io::Result<()>,
+    {
+        self.update(|canvas| {
+            let before = canvas.clone();
+            change(canvas)?;
+            canvas.record_change(Change {
+                action: Action::Edit(edit),
+                author: author.map(String::from),
+                cells_changed: before.cells_changed(canvas),
+            });
+            Ok(())
+        })
+    }
+
+    /// Applies a change to the layers themselves, checking it against the locks before anything is saved
+    fn update_layers<F>(&self, edit: Edit, author: Option<&str>, change: F) -> io::Result<super::canvas::Canvas>
+    where
+        F: FnOnce(&mut super::canvas::Canvas) -> io::Result<()>,
+    {
+        self.record(edit, author, |canvas| {
+            let before = canvas.clone();
+            change(canvas)?;
+            Ok(before.check_layer_change(canvas)?)
+        })
+    }
+
+    fn files(&self) -> super::storage::CanvasFiles<'_> {
+        super::storage::CanvasFiles {
+            canvas: &self.config.canvas_path,
+            temp: &self.config.canvas_temp_path,
+            snapshot: &self.config.canvas_snapshot_path,
+        }
+    }
+
+    fn damaged(&self, error: super::storage::CanvasFileError) -> super::storage::DamagedCanvasFile {
+        super::storage::DamagedCanvasFile {
+            path: self.config.canvas_path.clone(),
+            error,
+        }
+    }
+
+    /// Replaces a damaged canvas file with the temp file or the last good snapshot, moving the damaged file aside
+    /// The temp file is tried first, since it only survives a save which was interrupted before it was renamed
+    /// If neither holds a valid canvas the damaged file is left where it is, and its error is returned
+    fn recover(&self) -> io::Result<Option<Recovery>> {
+        let contents = match fs::read_to_string(&self.config.canvas_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if contents.is_empty() {
+            return Ok(None);
+        }
+        let error = match self.load(&contents) {
+            Ok(_) => return Ok(None),
+            Err(error) => error,
+        };
+
+        let (recovered_from, canvas) = [&self.config.canvas_temp_path, &self.config.canvas_snapshot_path]
+            .iter()
+            .find_map(|fallback| {
+                let contents = fs::read_to_string(fallback).ok().filter(|contents| !contents.is_empty())?;
+                let canvas = self.load(&contents).ok()?;
+                Some((fallback.to_string(), canvas))
+            })
+            .ok_or_else(|| self.damaged(error.clone()))?;
+
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut quarantine_path = format!("{}.damaged-{}", self.config.canvas_path, since_epoch.as_millis());
+        // never overwrite an earlier damaged file
+        let mut attempt = 1;
+        while Path::new(&quarantine_path).exists() {
+            quarantine_path = format!("{}.damaged-{}-{}", self.config.canvas_path, since_epoch.as_millis(), attempt);
+            attempt += 1;
+        }
+        fs::rename(&self.config.canvas_path, &quarantine_path)?;
+        super::storage::save(&self.files(), &canvas.serialise(), false)?;
+
+        Ok(Some(Recovery {
+            error,
+            quarantine_path,
+            recovered_from,
+        }))
+    }
 }
+
 /// Given a previous canvas and a draw command, return a new updated canvas state
 /// Folds over a set of commands, returning a new canvas each time
+/// With a clip, anything a command changes outside of it is undone before the next command runs
+/// Locks are then enforced, and a command touching a rejecting lock fails the whole set,
+/// as does any command which can't be applied
+/// Each command is recorded in the canvas history along with its author
 pub fn apply_draw_commands(
     previous_state_canvas: &super::canvas::Canvas, 
     commands:Vec<super::commands::DrawCommand>,
-) -> super::canvas::Canvas {
+    clip: Option<&super::clip::Clip>,
+) -> Result<super::canvas::Canvas, super::draw_error::DrawError> {
     commands
         .iter()
-        .fold(previous_state_canvas.clone(), |previous_canvas, command| {
-            match command.name {
+        .try_fold(previous_state_canvas.clone(), |previous_canvas, command| {
+            let new_canvas = match command.name {
                 CommandName::FillRectangle => operations::draw_fill_rectangle::execute(&previous_canvas, command),
                 CommandName::OutlineRectangle => operations::draw_outline_rectangle::execute(&previous_canvas, command),
                 CommandName::FloodFill => operations::draw_flood_fill::execute(&previous_canvas, command),
-            }
+                CommandName::Copy |
+                CommandName::Cut |
+                CommandName::Paste => operations::draw_clipboard::execute(&previous_canvas, command),
+                CommandName::Stamp => operations::draw_stamp::execute(&previous_canvas, command),
+                CommandName::GradientFill => operations::draw_gradient::execute(&previous_canvas, command),
+                CommandName::Line => operations::draw_line::execute(&previous_canvas, command),
+                CommandName::OutlineEllipse => operations::draw_outline_ellipse::execute(&previous_canvas, command),
+                CommandName::FlipHorizontal |
+                CommandName::FlipVertical |
+                CommandName::Rotate90 |
+                CommandName::Rotate180 |
+                CommandName::Rotate270 |
+                CommandName::Transpose => operations::draw_transform::execute(&previous_canvas, command),
+            }?;
+
+            let new_canvas = match clip {
+                Some(clip) => clip.restrict(&previous_canvas, new_canvas)?,
+                None => new_canvas,
+            };
+
+            let mut new_canvas = previous_canvas.enforce_locks(new_canvas)?;
+            new_canvas.record_change(Change {
+                action: Action::Draw(command.name.clone()),
+                author: command.author.clone(),
+                cells_changed: previous_canvas.cells_changed(&new_canvas),
+            });
+            Ok(new_canvas)
         })
 }
 
 #[cfg(test)]
 mod tests {
+    use uuid::Uuid;
+
     use super::*;
-    use super::super::{commands, canvas};
+    use super::super::{commands, canvas, draw_error, mismatch, resize, storage};
 
     #[test]
     fn test_apply_draw_command() {
-        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(24, 9, ' ');
+        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(24, 9, " ");
 
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
@@ -127,7 +414,8 @@ mod tests {
                 width: 5,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
@@ -137,7 +425,8 @@ mod tests {
                 width: 5,
                 height: 3
             }),
-            character: '@'
+            character: String::from("@"),
+            ..Default::default()
         };
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
@@ -147,7 +436,8 @@ mod tests {
                 width: 14,
                 height: 6
             }),
-            character: 'O',
+            character: String::from("O"),
+            ..Default::default()
         };
 
         let fourth_command: commands::DrawCommand = commands::DrawCommand {
@@ -157,10 +447,11 @@ mod tests {
                 width: 14,
                 height: 6
             }),
-            character: 'X'
+            character: String::from("X"),
+            ..Default::default()
         };
 
-        let canvas = apply_draw_commands(&canvas, vec!(first_command, second_command, third_command, fourth_command));
+        let canvas = apply_draw_commands(&canvas, vec!(first_command, second_command, third_command, fourth_command), None).unwrap();
 
         let actual = canvas.to_string();
 
@@ -171,7 +462,7 @@ mod tests {
 
     #[test]
     fn test_multiple_commands() {
-        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(21, 8, ' ');
+        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(21, 8, " ");
 
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
@@ -180,7 +471,8 @@ mod tests {
                 width: 7,
                 height: 6
             }),
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
@@ -190,7 +482,8 @@ mod tests {
                 width: 8,
                 height: 4
             }),
-            character: ' ',
+            character: String::from(" "),
+            ..Default::default()
         };
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
@@ -200,7 +493,8 @@ mod tests {
                 width: 8,
                 height: 4
             }),
-            character: 'O',
+            character: String::from("O"),
+            ..Default::default()
         };
 
         let fourth_command: commands::DrawCommand = commands::DrawCommand {
@@ -210,10 +504,11 @@ mod tests {
                 width: 5,
                 height: 3
             }),
-            character: 'X'
+            character: String::from("X"),
+            ..Default::default()
         };
 
-        let canvas = apply_draw_commands(&canvas, vec!(first_command, second_command, third_command, fourth_command));
+        let canvas = apply_draw_commands(&canvas, vec!(first_command, second_command, third_command, fourth_command), None).unwrap();
 
         let actual = canvas.to_string();
 
@@ -225,7 +520,7 @@ mod tests {
 
     #[test]
     fn test_multiple_with_flood_fill_commands() {
-        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(21, 8, ' ');
+        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(21, 8, " ");
 
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
@@ -234,7 +529,8 @@ mod tests {
                 width: 7,
                 height: 6
             }),
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
@@ -244,7 +540,8 @@ mod tests {
                 width: 8,
                 height: 4
             }),
-            character: ' ',
+            character: String::from(" "),
+            ..Default::default()
         };
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
@@ -254,7 +551,8 @@ mod tests {
                 width: 8,
                 height: 4
             }),
-            character: 'O',
+            character: String::from("O"),
+            ..Default::default()
         };
 
         let fourth_command: commands::DrawCommand = commands::DrawCommand {
@@ -264,17 +562,18 @@ mod tests {
                 width: 5,
                 height: 3
             }),
-            character: 'X'
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let fifth_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FloodFill,
             position: canvas::Point {x: 0, y: 0},
-            dimensions: None,
-            character: '-'
+            character: String::from("-"),
+            ..Default::default()
         };
 
-        let canvas = apply_draw_commands(&canvas, vec!(first_command, second_command, third_command, fourth_command, fifth_command));
+        let canvas = apply_draw_commands(&canvas, vec!(first_command, second_command, third_command, fourth_command, fifth_command), None).unwrap();
 
         let actual = canvas.to_string();
 
@@ -282,4 +581,249 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_apply_draw_commands_with_clip() {
+        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(6, 3, ".");
+        let clip = super::super::clip::Clip {
+            regions: vec!(canvas::Region {
+                position: canvas::Point {x: 1, y: 1},
+                dimensions: canvas::Dimensions { width: 4, height: 5 },
+            }),
+            mask_layer: None,
+        };
+
+        let flood_fill: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FloodFill,
+            position: canvas::Point {x: 0, y: 0},
+            character: String::from("-"),
+            ..Default::default()
+        };
+
+        let rotate: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::Rotate90,
+            dimensions: None,
+            character: String::new(),
+            ..flood_fill.clone()
+        };
+
+        let filled = apply_draw_commands(&canvas, vec!(flood_fill.clone()), Some(&clip)).unwrap();
+        assert_eq!("......\n.----.\n.----.\n", filled.to_string());
+
+        // a turn of the whole canvas can't be kept inside the clip, so it fails the whole request
+        assert_eq!(
+            Err(draw_error::DrawError::Clipped(super::super::clip::ClipError::Reshaped)),
+            apply_draw_commands(&canvas, vec!(flood_fill, rotate), Some(&clip)).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_open_checks_the_saved_canvas() {
+        let options = ApplicationOptions {
+            width: 3,
+            height: 2,
+            blank_character: String::from("."),
+            canvas_path: Uuid::new_v4().to_string(),
+            canvas_temp_path: Uuid::new_v4().to_string(),
+            canvas_snapshot_path: Uuid::new_v4().to_string(),
+            strict: false,
+        };
+        let app = DrawingApplication::initialize(options.clone());
+        app.open(&mismatch::ConfiguredCanvas::default(), mismatch::OnMismatch::Refuse).unwrap();
+
+        // a restart with another size keeps the saved canvas unless told otherwise
+        let wider = mismatch::ConfiguredCanvas { width: Some(4), ..mismatch::ConfiguredCanvas::default() };
+        let error = app.open(&wider, mismatch::OnMismatch::Refuse).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        let (canvas, _) = app.open(&wider, mismatch::OnMismatch::Resize).unwrap();
+        assert_eq!("....\n....\n", canvas.to_string());
+
+        // clean up
+        if Path::new(&options.canvas_path).exists() {
+            fs::remove_file(&options.canvas_path).unwrap();
+        }
+        if Path::new(&options.canvas_temp_path).exists() {
+            fs::remove_file(&options.canvas_temp_path).unwrap();
+        }
+        if Path::new(&options.canvas_snapshot_path).exists() {
+            fs::remove_file(&options.canvas_snapshot_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_a_canvas_resized_while_running_is_kept_after_a_restart() {
+        let options = ApplicationOptions {
+            width: 3,
+            height: 2,
+            blank_character: String::from("."),
+            canvas_path: Uuid::new_v4().to_string(),
+            canvas_temp_path: Uuid::new_v4().to_string(),
+            canvas_snapshot_path: Uuid::new_v4().to_string(),
+            strict: false,
+        };
+        let app = DrawingApplication::initialize(options.clone());
+        app.open(&mismatch::ConfiguredCanvas::default(), mismatch::OnMismatch::Refuse).unwrap();
+        app.resize(&canvas::Dimensions { width: 5, height: 1 }, resize::Anchor::TopLeft, None).unwrap();
+
+        // the size and blank character weren't set, so the restarted server takes the saved canvas as it is
+        let restarted = DrawingApplication::initialize(ApplicationOptions { width: 30, height: 30, ..options.clone() });
+        let (canvas, _) = restarted.open(&mismatch::ConfiguredCanvas::default(), mismatch::OnMismatch::Refuse).unwrap();
+        assert_eq!(".....\n", canvas.to_string());
+
+        // clean up
+        if Path::new(&options.canvas_path).exists() {
+            fs::remove_file(&options.canvas_path).unwrap();
+        }
+        if Path::new(&options.canvas_temp_path).exists() {
+            fs::remove_file(&options.canvas_temp_path).unwrap();
+        }
+        if Path::new(&options.canvas_snapshot_path).exists() {
+            fs::remove_file(&options.canvas_snapshot_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_strict_setting_applies_to_commands_which_dont_choose() {
+        let options = ApplicationOptions {
+            width: 4,
+            height: 2,
+            blank_character: String::from("."),
+            canvas_path: Uuid::new_v4().to_string(),
+            canvas_temp_path: Uuid::new_v4().to_string(),
+            canvas_snapshot_path: Uuid::new_v4().to_string(),
+            strict: true,
+        };
+        let app = DrawingApplication::initialize(options.clone());
+
+        let fill: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point {x: 2, y: 0},
+            dimensions: Some(canvas::Dimensions { width: 3, height: 1 }),
+            character: String::from("#"),
+            ..Default::default()
+        };
+
+        let error = app.draw(vec!(fill.clone())).unwrap_err();
+        assert!(matches!(error, draw_error::DrawError::OffCanvas { .. }));
+        assert_eq!("....\n....\n", app.canvas().unwrap().to_string());
+
+        // a command can still ask for its shape to be clipped
+        let lenient = commands::DrawCommand { strict: Some(false), ..fill };
+        assert_eq!("..##\n....\n", app.draw(vec!(lenient)).unwrap().0.to_string());
+
+        // clean up
+        if Path::new(&options.canvas_path).exists() {
+            fs::remove_file(&options.canvas_path).unwrap();
+        }
+        if Path::new(&options.canvas_temp_path).exists() {
+            fs::remove_file(&options.canvas_temp_path).unwrap();
+        }
+        if Path::new(&options.canvas_snapshot_path).exists() {
+            fs::remove_file(&options.canvas_snapshot_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reading_the_canvas_and_refusing_a_clip_save_nothing() {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&directory).unwrap();
+        let location = |name: &str| directory.join(name).to_string_lossy().into_owned();
+        let mut app = DrawingApplication::initialize(ApplicationOptions {
+            width: 3,
+            height: 1,
+            blank_character: String::from("."),
+            canvas_path: location("canvas"),
+            canvas_temp_path: location("temp"),
+            canvas_snapshot_path: location("snapshot"),
+            strict: false,
+        });
+        let clip = |mask_layer: &str| super::super::clip::Clip {
+            regions: vec!(),
+            mask_layer: Some(String::from(mask_layer)),
+        };
+
+        // before the first save the canvas is blank, and no file is created
+        assert_eq!("...\n", app.canvas().unwrap().to_string());
+        assert!(app.set_clip(clip("sketch"), None).is_err());
+        assert!(!Path::new(&location("canvas")).exists());
+
+        app.add_layer("sketch", None).unwrap();
+        app.add_layer("notes", None).unwrap();
+        let saved = fs::read_to_string(location("canvas")).unwrap();
+        let snapshot = fs::read_to_string(location("snapshot")).unwrap();
+
+        assert_eq!(3, app.canvas().unwrap().layer_info().len());
+        assert!(app.set_clip(clip("missing"), None).is_err());
+        assert_eq!(saved, fs::read_to_string(location("canvas")).unwrap());
+        assert_eq!(snapshot, fs::read_to_string(location("snapshot")).unwrap());
+
+        // a clip which is set is attributed in the history
+        app.set_clip(clip("sketch"), Some("ada")).unwrap();
+        let last = app.canvas().unwrap().history.pop().unwrap();
+        assert_eq!((Action::Edit(Edit::SetClip), Some("ada")), (last.action, last.author.as_deref()));
+
+        // clean up
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_damaged_canvas_files_are_quarantined_and_recovered() {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&directory).unwrap();
+        let location = |name: &str| directory.join(name).to_string_lossy().into_owned();
+        let app = DrawingApplication::initialize(ApplicationOptions {
+            width: 3,
+            height: 2,
+            blank_character: String::from("."),
+            canvas_path: location("canvas"),
+            canvas_temp_path: location("temp"),
+            canvas_snapshot_path: location("snapshot"),
+            strict: false,
+        });
+        let fill = |character: &str| commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point { x: 0, y: 0 },
+            dimensions: Some(canvas::Dimensions { width: 1, height: 1 }),
+            character: String::from(character),
+            ..Default::default()
+        };
+        let quarantined = || fs::read_dir(&directory).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("canvas.damaged-"))
+            .count();
+
+        // the second save keeps the first as the snapshot
+        app.draw(vec!(fill("a"))).unwrap();
+        app.draw(vec!(fill("b"))).unwrap();
+
+        // while running, a ragged file is refused and left alone, so nothing is drawn over it
+        fs::write(location("canvas"), "b..\n.\n").unwrap();
+        let error = app.draw(vec!(fill("c"))).unwrap_err();
+        assert!(matches!(
+            error,
+            draw_error::DrawError::DamagedCanvas(storage::DamagedCanvasFile { error: storage::CanvasFileError::WrongRowWidth { row: 1, .. }, .. })
+        ));
+        assert_eq!("b..\n.\n", fs::read_to_string(location("canvas")).unwrap());
+        assert_eq!(0, quarantined());
+
+        // opening it falls back to the snapshot, and says where the damaged file went
+        let (canvas, recovery) = app.open(&mismatch::ConfiguredCanvas::default(), mismatch::OnMismatch::Refuse).unwrap();
+        assert_eq!("a..\n...\n", canvas.to_string());
+        let recovery = recovery.unwrap();
+        assert_eq!(location("snapshot"), recovery.recovered_from);
+        assert_eq!("b..\n.\n", fs::read_to_string(&recovery.quarantine_path).unwrap());
+        assert_eq!(1, quarantined());
+        assert_eq!(None, app.open(&mismatch::ConfiguredCanvas::default(), mismatch::OnMismatch::Refuse).unwrap().1);
+
+        // with nothing to fall back to, opening fails with the reason and the file stays put
+        fs::remove_file(location("snapshot")).unwrap();
+        fs::write(location("canvas"), "{\"dimensions\":").unwrap();
+        let error = app.open(&mismatch::ConfiguredCanvas::default(), mismatch::OnMismatch::Refuse).unwrap_err();
+        assert!(matches!(draw_error::DrawError::from(error), draw_error::DrawError::DamagedCanvas(_)));
+        assert!(Path::new(&location("canvas")).exists());
+        assert_eq!(1, quarantined());
+
+        // clean up
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
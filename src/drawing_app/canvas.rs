@@ -1,74 +1,331 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::history::Change;
+use super::layers::{Layer, BACKGROUND_LAYER};
+use super::locks::Lock;
+
+/// Marks a cell that lets the layers beneath it show through
+pub const TRANSPARENT_CHARACTER: &str = "";
+
+/// Largest width or height a canvas can have
+pub const MAX_CANVAS_DIMENSION: i32 = 4096;
+
+/// Splits text into user-perceived characters (grapheme clusters),
+/// so that flags, skin tones and ZWJ sequences such as 👩‍💻 are kept whole
+pub fn graphemes(text: &str) -> Vec<String> {
+    text
+        .graphemes(true)
+        .map(String::from)
+        .collect()
+}
 
 /// Represents a single point on the canvas
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 } // this could be stored simply as a tuple, with x, y order by convention.
 
 /// Represents the any rectangle, can also be canvas
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Dimensions {
     pub width: i32,
     pub height: i32,
 }
 
+/// A rectangle on the canvas, given by its upper-left corner and size
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Region {
+    pub position: Point,
+    pub dimensions: Dimensions,
+}
+
+impl Region {
+    /// Whether a point falls inside the rectangle
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x >= self.position.x &&
+        point.y >= self.position.y &&
+        (point.x as i64) < self.right() &&
+        (point.y as i64) < self.bottom()
+    }
+
+    /// The column just past the rectangle, widened so it can't overflow
+    pub fn right(&self) -> i64 {
+        self.position.x as i64 + self.dimensions.width as i64
+    }
+
+    /// The row just past the rectangle, widened so it can't overflow
+    pub fn bottom(&self) -> i64 {
+        self.position.y as i64 + self.dimensions.height as i64
+    }
+}
+
+/// A colour, either as true colour RGB components or an index into the 256 colour palette
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Colour {
+    Rgb(u8, u8, u8),
+    Palette(u8),
+}
+
+/// The first 16 entries of the 256 colour palette, the rest are computed
+const SYSTEM_COLOURS: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (128, 0, 0), (0, 128, 0), (128, 128, 0),
+    (0, 0, 128), (128, 0, 128), (0, 128, 128), (192, 192, 192),
+    (128, 128, 128), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (0, 0, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// Intensity steps used by the 6x6x6 colour cube (palette indices 16 to 231)
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Colour {
+    /// Resolves the colour to its RGB components
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Colour::Rgb(r, g, b) => (r, g, b),
+            Colour::Palette(index) => match index {
+                0..=15 => SYSTEM_COLOURS[index as usize],
+                16..=231 => {
+                    let cube_index = index - 16;
+                    (
+                        CUBE_STEPS[(cube_index / 36) as usize],
+                        CUBE_STEPS[(cube_index / 6 % 6) as usize],
+                        CUBE_STEPS[(cube_index % 6) as usize],
+                    )
+                },
+                _ => {
+                    let grey = 8 + (index - 232) * 10;
+                    (grey, grey, grey)
+                },
+            },
+        }
+    }
+
+    /// Formats the colour as a CSS hex colour, i.e. `#ff8800`
+    pub fn to_hex(self) -> String {
+        let (r, g, b) = self.to_rgb();
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// A single pixel of the canvas: a grapheme cluster with optional colours
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Cell {
+    pub character: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<Colour>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<Colour>,
+}
+
+impl Cell {
+    /// A cell which shows whatever is on the layers beneath it
+    pub fn transparent() -> Cell {
+        Cell::from(TRANSPARENT_CHARACTER)
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.character == TRANSPARENT_CHARACTER
+    }
+
+    /// Whether the cell carries any colour information
+    pub fn is_coloured(&self) -> bool {
+        self.foreground.is_some() || self.background.is_some()
+    }
+
+    /// Number of terminal columns the character takes up: 1, or 2 for wide glyphs like 中 and 🟥
+    pub fn width(&self) -> usize {
+        self.character.width().clamp(1, 2)
+    }
+
+    /// The character padded with spaces to fill a column of the given width
+    pub fn padded(&self, column_width: usize) -> String {
+        let padding = column_width.saturating_sub(self.width());
+        format!("{}{}", self.character, " ".repeat(padding))
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(character: &str) -> Cell {
+        Cell {
+            character: String::from(character),
+            foreground: None,
+            background: None,
+        }
+    }
+}
+
+/// Columns each pixel is rendered into, so that rows line up when wide and narrow glyphs are mixed
+/// Narrow glyphs are padded out to the width of the widest
+pub fn column_width(pixels: &[Vec<Cell>]) -> usize {
+    pixels
+        .iter()
+        .flatten()
+        .map(Cell::width)
+        .max()
+        .unwrap_or(1)
+}
+
 /// Data structure to represent the canvas
+/// The canvas is a stack of layers, ordered from bottom to top
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Canvas {
     pub dimensions: Dimensions,
-    pub pixels: Vec<Vec<char>>, // for strictly ASCII we could use a u8 here. Char gives us Unicode which is fun 😉
+    pub blank_character: String, // shown wherever every layer is transparent
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub clipboard: BTreeMap<String, Vec<Vec<Cell>>>, // regions copied by name, kept with the canvas so they survive a restart
+    #[serde(default)]
+    pub stamps: BTreeMap<String, Vec<Vec<Cell>>>, // uploaded grids which stamp commands draw by name
+    #[serde(default)]
+    pub locks: BTreeMap<String, Lock>, // protected rectangles by name, which draw commands may not change
+    #[serde(default)]
+    pub history: Vec<Change>, // the latest commands applied, oldest first
 }
 
 impl Canvas {
+    /// Builds a single layer canvas from rows of grapheme clusters
     pub fn from_chars(
-        ascii: Vec<Vec<char>>, 
-        width: i32, 
-        height: i32
+        ascii: Vec<Vec<String>>,
+        width: i32,
+        height: i32,
+        blank_character: &str,
     ) -> Canvas {
-        let canvas = Canvas {
+        Canvas {
             dimensions: Dimensions {
                 width,
                 height,
             },
-            pixels: ascii,
-        };
+            blank_character: String::from(blank_character),
+            layers: vec![Layer {
+                name: String::from(BACKGROUND_LAYER),
+                visible: true,
+                pixels: ascii
+                    .iter()
+                    .map(|row| row.iter().map(|character| Cell::from(character.as_str())).collect())
+                    .collect(),
+            }],
+            clipboard: BTreeMap::new(),
+            stamps: BTreeMap::new(),
+            locks: BTreeMap::new(),
+            history: Vec::new(),
+        }
+    }
 
-        return canvas;
+    pub fn blank_canvas(width: i32, height: i32, blank_character: &str) -> Canvas {
+        Canvas::from_chars(
+            vec![vec![String::from(blank_character); width as usize]; height as usize],
+            width,
+            height,
+            blank_character,
+        )
     }
 
-    pub fn blank_canvas(width: i32, height: i32, blank_character: char) -> Canvas {
-        let canvas = Canvas {
-            dimensions: Dimensions {
-                width: width,
-                height: height,
-            },
-            pixels: vec![vec![blank_character; width as usize]; height as usize]
-        };
+    /// Reads a plain text canvas, one row per line, as older canvas files were saved
+    /// These carry no colour or layers
+    pub fn from_text(contents: &str, width: i32, height: i32, blank_character: &str) -> Canvas {
+        Canvas::from_chars(
+            contents
+                .lines()
+                .map(graphemes)
+                .collect(),
+            width,
+            height,
+            blank_character,
+        )
+    }
+
+    /// Counts the cells which differ on any layer of another version of this canvas
+    /// A canvas of a different shape is counted as changed all over
+    pub fn cells_changed(&self, other: &Canvas) -> usize {
+        if self.dimensions != other.dimensions || self.layers.len() != other.layers.len() {
+            return (other.dimensions.width * other.dimensions.height) as usize;
+        }
+
+        (0..self.dimensions.height as usize)
+            .flat_map(|row_index| (0..self.dimensions.width as usize).map(move |column_index| (row_index, column_index)))
+            .filter(|(row_index, column_index)| {
+                self.layers
+                    .iter()
+                    .zip(&other.layers)
+                    .any(|(layer, other_layer)| layer.pixels[*row_index][*column_index] != other_layer.pixels[*row_index][*column_index])
+            })
+            .count()
+    }
 
-        return canvas;
+    /// Produces the stored representation of the canvas, which round-trips colours and layers
+    pub fn serialise(&self) -> String {
+        serde_json::to_string(self).expect("canvas should always serialise to JSON")
     }
 
-    pub fn to_string(&self) -> String { 
-        self.pixels
+    /// Renders the visible layers with ANSI escape codes for a terminal
+    /// Coloured pixels are wrapped in SGR sequences and reset afterwards
+    pub fn to_ansi(&self) -> String {
+        let pixels = self.composite();
+        let column_width = column_width(&pixels);
+
+        pixels
             .iter()
             .map(|row| {
                 row
                     .iter()
+                    .map(|cell| {
+                        if !cell.is_coloured() {
+                            return cell.padded(column_width);
+                        }
+
+                        let mut codes = vec!();
+                        if let Some(foreground) = cell.foreground {
+                            codes.push(ansi_colour(38, foreground));
+                        }
+                        if let Some(background) = cell.background {
+                            codes.push(ansi_colour(48, background));
+                        }
+                        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), cell.padded(column_width))
+                    })
                     .collect::<String>()
             })
             .fold(String::new(), |a, b| a + &b + "\n")
     }
 }
 
+/// SGR parameters selecting a colour, `layer` is 38 for the foreground and 48 for the background
+fn ansi_colour(layer: u8, colour: Colour) -> String {
+    match colour {
+        Colour::Rgb(r, g, b) => format!("{};2;{};{};{}", layer, r, g, b),
+        Colour::Palette(index) => format!("{};5;{}", layer, index),
+    }
+}
+
+/// Renders the visible layers, composited into plain text
+impl fmt::Display for Canvas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pixels = self.composite();
+        let column_width = column_width(&pixels);
+
+        for row in pixels {
+            let line: String = row
+                .iter()
+                .map(|cell| cell.padded(column_width))
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::{canvas};
 
     #[test]
     fn test_new_canvas() {
-        let actual = canvas::Canvas::blank_canvas(21, 9, ' ').to_string();
+        let actual = canvas::Canvas::blank_canvas(21, 9, " ").to_string();
         let expected = "                     \n                     \n                     \n                     \n                     \n                     \n                     \n                     \n                     \n";
 
         assert_eq!(expected, actual);
@@ -79,14 +336,96 @@ mod tests {
         let canvas_string = "              .......\n              .......\n              .......\nOOOOOOOO      .......\nO      O      .......\nO    XXXXX    .......\nOOOOOXXXXX           \n     XXXXX           \n";
         let chars = canvas_string
             .lines()
-            .map(|line| {
-                line
-                    .chars()
-                    .collect()
-            })
+            .map(canvas::graphemes)
             .collect();
-        let actual = canvas::Canvas::from_chars(chars, 21, 8).to_string();
+        let actual = canvas::Canvas::from_chars(chars, 21, 8, " ").to_string();
 
         assert_eq!(canvas_string, actual);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cells_changed() {
+        let before = canvas::Canvas::blank_canvas(4, 3, ".");
+        let mut after = before.clone();
+        after.layers[0].pixels[0][1] = canvas::Cell::from("#");
+        after.layers[0].pixels[2][3] = canvas::Cell::from("#");
+
+        assert_eq!(0, before.cells_changed(&before));
+        assert_eq!(2, before.cells_changed(&after));
+        assert_eq!(6, before.cells_changed(&canvas::Canvas::blank_canvas(3, 2, ".")));
+    }
+
+    #[test]
+    fn test_parse_plain_text_canvas() {
+        // canvas files written before colour support are plain text
+        let canvas_string = "OOO  \nO O  \nOOO  \n";
+        let actual = canvas::Canvas::from_text(canvas_string, 5, 3, " ");
+
+        assert_eq!(canvas_string, actual.to_string());
+        assert_eq!(actual.layers.len(), 1);
+        assert_eq!(actual.layers[0].pixels[1][1], canvas::Cell::from(" "));
+    }
+
+    #[test]
+    fn test_colours_round_trip() {
+        let mut canvas = canvas::Canvas::blank_canvas(4, 2, " ");
+        canvas.layers[0].pixels[1][2] = canvas::Cell {
+            character: String::from("#"),
+            foreground: Some(canvas::Colour::Rgb(255, 0, 0)),
+            background: Some(canvas::Colour::Palette(21)),
+        };
+
+        canvas.add_layer("overlay").unwrap();
+        canvas.set_layer_visibility("overlay", false).unwrap();
+
+        let actual = canvas::Canvas::load(&canvas.serialise(), 4, 2, " ").unwrap();
+
+        assert_eq!("    \n  # \n", actual.to_string());
+        assert_eq!(canvas.layers[0].pixels, actual.layers[0].pixels);
+        assert_eq!(canvas.layer_info().len(), 2);
+        assert!(!actual.layers[1].visible);
+        assert!(actual.layers[1].pixels[0][0].is_transparent());
+    }
+
+    #[test]
+    fn test_palette_to_rgb() {
+        assert_eq!((128, 0, 0), canvas::Colour::Palette(1).to_rgb());
+        assert_eq!((0, 0, 255), canvas::Colour::Palette(21).to_rgb());
+        assert_eq!((255, 255, 255), canvas::Colour::Palette(231).to_rgb());
+        assert_eq!((238, 238, 238), canvas::Colour::Palette(255).to_rgb());
+        assert_eq!("#ff8700", canvas::Colour::Palette(208).to_hex());
+    }
+
+    #[test]
+    fn test_wide_glyphs_line_up() {
+        // narrow glyphs are padded to two columns once any wide glyph is on the canvas
+        let mut canvas = canvas::Canvas::blank_canvas(3, 2, " ");
+        canvas.layers[0].pixels[0][0] = canvas::Cell::from("中");
+        canvas.layers[0].pixels[1][2] = canvas::Cell::from("👩‍💻");
+        canvas.layers[0].pixels[1][0] = canvas::Cell::from("a");
+
+        assert_eq!("中    \na   👩‍💻\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_parse_plain_text_graphemes() {
+        let canvas_string = "🇬🇧👍🏽\n⬛⬛\n";
+        let actual = canvas::Canvas::from_text(canvas_string, 2, 2, "⬛");
+
+        assert_eq!(actual.layers[0].pixels[0][0], canvas::Cell::from("🇬🇧"));
+        assert_eq!(actual.layers[0].pixels[0][1], canvas::Cell::from("👍🏽"));
+        assert_eq!(canvas_string, actual.to_string());
+    }
+
+    #[test]
+    fn test_to_ansi() {
+        let mut canvas = canvas::Canvas::blank_canvas(3, 1, ".");
+        canvas.layers[0].pixels[0][1] = canvas::Cell {
+            character: String::from("🟥"),
+            foreground: Some(canvas::Colour::Palette(196)),
+            background: Some(canvas::Colour::Rgb(0, 0, 0)),
+        };
+
+        assert_eq!(". \x1b[38;5;196;48;2;0;0;0m🟥\x1b[0m. \n", canvas.to_ansi());
+    }
+}
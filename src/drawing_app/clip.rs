@@ -0,0 +1,180 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+use super::canvas::{Canvas, Point, Region};
+
+/// The part of the canvas draw commands are allowed to change
+/// A pixel can be changed when it falls inside any of the regions (or there are no regions)
+/// and the mask layer isn't transparent there (or there is no mask layer)
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Clip {
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    #[serde(default)]
+    pub mask_layer: Option<String>,
+}
+
+/// Reasons a clip can be refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClipError {
+    Empty,
+    MaskLayerNotFound(String),
+    Reshaped, // a draw command changed the shape of the canvas, which can't be kept inside the clip
+}
+
+impl fmt::Display for ClipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipError::Empty => write!(f, "A clip needs at least one region or a mask layer."),
+            ClipError::MaskLayerNotFound(name) => write!(f, "No layer named '{}' exists to use as a mask.", name),
+            ClipError::Reshaped => write!(f, "Commands which change the shape of the canvas can't be drawn while a clip is set."),
+        }
+    }
+}
+
+impl Error for ClipError {}
+
+/// Clip errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<ClipError> for io::Error {
+    fn from(error: ClipError) -> io::Error {
+        let kind = match error {
+            ClipError::Empty | ClipError::Reshaped => io::ErrorKind::InvalidInput,
+            ClipError::MaskLayerNotFound(_) => io::ErrorKind::NotFound,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+impl Clip {
+    /// Checks the clip covers something and its mask layer exists on the canvas
+    pub fn validate(&self, canvas: &Canvas) -> Result<(), ClipError> {
+        if self.regions.is_empty() && self.mask_layer.is_none() {
+            return Err(ClipError::Empty);
+        }
+        if let Some(name) = &self.mask_layer {
+            if canvas.layer_index(&Some(name.clone())).is_none() {
+                return Err(ClipError::MaskLayerNotFound(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether draw commands may change a pixel of the canvas
+    /// Pixels are protected if the mask layer has since been removed
+    pub fn allows(&self, canvas: &Canvas, point: &Point) -> bool {
+        let in_regions = self.regions.is_empty() || self.regions.iter().any(|region| region.contains(point));
+
+        let in_mask = match &self.mask_layer {
+            Some(name) => canvas
+                .layer_index(&Some(name.clone()))
+                .and_then(|index| canvas.layers[index].pixels.get(point.y as usize)?.get(point.x as usize))
+                .is_some_and(|cell| !cell.is_transparent()),
+            None => true,
+        };
+
+        in_regions && in_mask
+    }
+
+    /// Undoes whatever a draw command changed outside the clip
+    pub fn restrict(&self, before: &Canvas, after: Canvas) -> Result<Canvas, ClipError> {
+        protect(before, after, |point| !self.allows(before, point))
+    }
+}
+
+/// Puts the protected pixels of every layer back as they were before a draw command
+/// A command which changed the shape of the canvas (a whole canvas rotation, for example)
+/// can't be partly undone, so it is refused
+pub fn protect<F>(before: &Canvas, mut after: Canvas, protected: F) -> Result<Canvas, ClipError>
+where
+    F: Fn(&Point) -> bool,
+{
+    if !same_shape(before, &after) {
+        return Err(ClipError::Reshaped);
+    }
+
+    for row_index in 0..before.dimensions.height {
+        for column_index in 0..before.dimensions.width {
+            if !protected(&Point { x: column_index, y: row_index }) {
+                continue;
+            }
+            for (layer, previous_layer) in after.layers.iter_mut().zip(&before.layers) {
+                layer.pixels[row_index as usize][column_index as usize] =
+                    previous_layer.pixels[row_index as usize][column_index as usize].clone();
+            }
+        }
+    }
+
+    Ok(after)
+}
+
+/// Whether two canvases have the same size and number of layers, so their pixels line up
+pub fn same_shape(before: &Canvas, after: &Canvas) -> bool {
+    before.dimensions == after.dimensions && before.layers.len() == after.layers.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::canvas::{Cell, Dimensions};
+
+    fn region(x: i32, y: i32, width: i32, height: i32) -> Region {
+        Region {
+            position: Point { x, y },
+            dimensions: Dimensions { width, height },
+        }
+    }
+
+    #[test]
+    fn test_restrict_to_regions() {
+        let before = Canvas::blank_canvas(4, 2, ".");
+        let after = Canvas::from_text("####\n####\n", 4, 2, ".");
+        let clip = Clip {
+            regions: vec![region(0, 0, 1, 2), region(2, 1, 5, 5)],
+            mask_layer: None,
+        };
+
+        assert_eq!("#...\n#.##\n", clip.restrict(&before, after).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_restrict_to_mask() {
+        let mut before = Canvas::blank_canvas(3, 1, ".");
+        before.add_layer("mask").unwrap();
+        before.set_layer_visibility("mask", false).unwrap();
+        before.layers[1].pixels[0][1] = Cell::from("m");
+        let mut after = before.clone();
+        after.layers[0].pixels[0] = vec![Cell::from("#"); 3];
+        let clip = Clip {
+            regions: vec![],
+            mask_layer: Some(String::from("mask")),
+        };
+
+        assert_eq!(".#.\n", clip.restrict(&before, after).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_reshaping_commands_are_refused() {
+        let before = Canvas::blank_canvas(3, 1, ".");
+        let after = Canvas::blank_canvas(1, 3, "#");
+        let clip = Clip {
+            regions: vec![region(0, 0, 3, 3)],
+            mask_layer: None,
+        };
+
+        assert_eq!(Err(ClipError::Reshaped), clip.restrict(&before, after).map(|_| ()));
+    }
+
+    #[test]
+    fn test_validate() {
+        let canvas = Canvas::blank_canvas(3, 1, ".");
+
+        assert_eq!(Err(ClipError::Empty), Clip::default().validate(&canvas));
+        assert_eq!(
+            Err(ClipError::MaskLayerNotFound(String::from("mask"))),
+            Clip { regions: vec![], mask_layer: Some(String::from("mask")) }.validate(&canvas)
+        );
+        assert_eq!(Ok(()), Clip { regions: vec![region(0, 0, 1, 1)], mask_layer: None }.validate(&canvas));
+    }
+}
@@ -1,11 +1,224 @@
 use serde::{Deserialize, Serialize};
 
+/// Clipboard used by copy, cut and paste commands which don't name one
+pub const DEFAULT_CLIPBOARD: &str = "default";
+
 /// Set of all possible operations
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum CommandName {
     FillRectangle,
     OutlineRectangle,
     FloodFill,
+    Copy,
+    Cut,
+    Paste,
+    Stamp,
+    GradientFill,
+    Line,
+    OutlineEllipse,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Transpose,
+}
+
+/// Ways of rearranging a block of pixels
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    FlipHorizontal,
+    FlipVertical,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Transpose,
+}
+
+impl Transform {
+    /// The transform carried out by a command, if it is a transform command
+    pub fn from_command(name: &CommandName) -> Option<Transform> {
+        match name {
+            CommandName::FlipHorizontal => Some(Transform::FlipHorizontal),
+            CommandName::FlipVertical => Some(Transform::FlipVertical),
+            CommandName::Rotate90 => Some(Transform::Rotate90),
+            CommandName::Rotate180 => Some(Transform::Rotate180),
+            CommandName::Rotate270 => Some(Transform::Rotate270),
+            CommandName::Transpose => Some(Transform::Transpose),
+            _ => None,
+        }
+    }
+
+    /// The command which carries out this transform on the canvas
+    pub fn command_name(self) -> CommandName {
+        match self {
+            Transform::FlipHorizontal => CommandName::FlipHorizontal,
+            Transform::FlipVertical => CommandName::FlipVertical,
+            Transform::Rotate90 => CommandName::Rotate90,
+            Transform::Rotate180 => CommandName::Rotate180,
+            Transform::Rotate270 => CommandName::Rotate270,
+            Transform::Transpose => CommandName::Transpose,
+        }
+    }
+}
+
+/// Which neighbours a flood fill spreads to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Connectivity {
+    #[default]
+    Four, // up, down, left and right
+    Eight, // diagonals as well
+}
+
+/// How a flood fill decides which pixels to change
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillMode {
+    #[default]
+    Flood, // spread over pixels matching the starting pixel
+    Boundary, // spread over anything until the boundary character is hit
+    GlobalReplace, // change every pixel on the layer matching the starting pixel
+}
+
+/// Settings only used by flood fill commands
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FloodFillOptions {
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    #[serde(default)]
+    pub mode: FillMode,
+    #[serde(default)]
+    pub boundary_character: Option<String>, // used by boundary fills
+    #[serde(default)]
+    pub clip: Option<super::canvas::Region>, // the fill never spreads outside this rectangle
+    #[serde(default)]
+    pub mask_layer: Option<String>, // the fill never spreads where this layer is transparent
+}
+
+/// 4x4 Bayer matrix giving the order pixels switch over in an ordered dither
+const BAYER_MATRIX: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Largest width or height of a tiled pattern
+pub const MAX_TILE_SIZE: usize = 16;
+
+/// Number of shades an ordered dither can produce between its two characters
+pub const DITHER_LEVELS: u8 = 16;
+
+/// A texture used in place of a single fill character
+/// Patterns line up with the canvas rather than the shape being filled, so neighbouring fills join up seamlessly
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Pattern {
+    Tile { rows: Vec<String> }, // a small grid of characters repeated across the area
+    Checkerboard { other_character: String }, // alternates between the command's character and another
+    Dither { other_character: String, level: u8 }, // shades from the command's character (0) to the other one (16)
+}
+
+impl Pattern {
+    /// The character the pattern puts at a point on the canvas, if it isn't the command's own character
+    fn character_at(&self, position: &super::canvas::Point) -> Option<String> {
+        let (x, y) = (position.x.rem_euclid(4) as usize, position.y.rem_euclid(4) as usize);
+
+        match self {
+            Pattern::Tile { rows } => {
+                let row = rows.get(position.y.rem_euclid(rows.len().max(1) as i32) as usize)?;
+                let characters = super::canvas::graphemes(row);
+                characters
+                    .get(position.x.rem_euclid(characters.len().max(1) as i32) as usize)
+                    .cloned()
+            },
+            Pattern::Checkerboard { other_character } => {
+                if (position.x + position.y).rem_euclid(2) == 1 {
+                    Some(other_character.clone())
+                } else {
+                    None
+                }
+            },
+            Pattern::Dither { other_character, level } => {
+                if BAYER_MATRIX[y][x] < (*level).min(DITHER_LEVELS) {
+                    Some(other_character.clone())
+                } else {
+                    None
+                }
+            },
+        }
+    }
+}
+
+/// Characters from lightest to darkest, used by gradients which don't give their own ramp
+pub const DEFAULT_RAMP: &str = " .:-=+*#%@";
+
+/// Which way a gradient runs across its rectangle
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientDirection {
+    Horizontal, // left to right
+    Vertical, // top to bottom
+    Diagonal, // top-left to bottom-right
+    Radial, // centre outwards
+}
+
+/// Settings only used by gradient fill commands
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Gradient {
+    pub direction: GradientDirection,
+    pub ramp: Vec<String>, // grapheme clusters the gradient steps through, from start to end
+    #[serde(default)]
+    pub dither: bool, // spread the rounding error between neighbouring pixels (Floyd–Steinberg)
+}
+
+/// Widest brush a line or outline can be drawn with
+pub const MAX_BRUSH_WIDTH: i32 = 32;
+
+/// Which side of a shape's edge a brush wider than one cell grows towards
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrushAlignment {
+    #[default]
+    Inward, // the outer edge of the stroke stays on the shape's edge
+    Outward, // the inner edge of the stroke stays on the shape's edge
+    #[serde(alias = "centered")]
+    Centred, // the stroke straddles the shape's edge, with any odd cell inside
+}
+
+/// How thick lines and outlines are drawn
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Brush {
+    #[serde(default = "Brush::default_width")]
+    pub width: i32,
+    #[serde(default)]
+    pub alignment: BrushAlignment, // unused by lines, which are always centred
+}
+
+impl Default for Brush {
+    fn default() -> Brush {
+        Brush {
+            width: Brush::default_width(),
+            alignment: BrushAlignment::default(),
+        }
+    }
+}
+
+impl Brush {
+    fn default_width() -> i32 {
+        1
+    }
+
+    /// How many cells the stroke reaches outside the shape's edge
+    pub fn outset(&self) -> i32 {
+        match self.alignment {
+            BrushAlignment::Inward => 0,
+            BrushAlignment::Outward => self.width - 1,
+            BrushAlignment::Centred => (self.width - 1) / 2,
+        }
+    }
 }
 
 /// Represents a drawing operation
@@ -13,6 +226,130 @@ pub enum CommandName {
 pub struct DrawCommand {
     pub name: CommandName, // name identifier of the operation
     pub position: super::canvas::Point, // upper-left corner start coordinates
-    pub dimensions: Option<super::canvas::Dimensions>, // flood fill doesn't need to define dimensions
-    pub character: char,
+    pub dimensions: Option<super::canvas::Dimensions>, // flood fill doesn't need to define dimensions, transforms without them apply to the whole canvas
+    #[serde(default)]
+    pub character: String, // a single grapheme cluster, unused by transforms
+    #[serde(default)]
+    pub foreground: Option<super::canvas::Colour>,
+    #[serde(default)]
+    pub background: Option<super::canvas::Colour>,
+    #[serde(default)]
+    pub layer: Option<String>, // no layer draws onto the bottom layer
+    #[serde(default)]
+    pub clipboard: Option<String>, // name of the clipboard to copy to or paste from, no name uses the default one
+    #[serde(default)]
+    pub blank_is_transparent: bool, // when pasting or stamping, leave pixels holding the blank character out
+    #[serde(default)]
+    pub stamp: Option<String>, // name of the stamp to draw
+    #[serde(default)]
+    pub transform: Option<Transform>, // flip or rotation applied to a stamp before it is drawn
+    #[serde(default)]
+    pub flood_fill: FloodFillOptions,
+    #[serde(default)]
+    pub pattern: Option<Pattern>, // texture used by fills instead of the single character
+    #[serde(default)]
+    pub gradient: Option<Gradient>,
+    #[serde(default)]
+    pub end: Option<super::canvas::Point>, // where a line finishes, it starts at the position
+    #[serde(default)]
+    pub brush: Brush,
+    #[serde(default)]
+    pub strict: Option<bool>, // refuse rather than clip shapes which don't fit the canvas, no value uses the server's setting
+    #[serde(default)]
+    pub author: Option<String>, // name of the API key which sent the command, none when the server has no keys
+}
+
+/// A fill rectangle at the origin with every optional field left out, so commands can be built from just the fields they use
+impl Default for DrawCommand {
+    fn default() -> DrawCommand {
+        DrawCommand {
+            name: CommandName::FillRectangle,
+            position: super::canvas::Point { x: 0, y: 0 },
+            dimensions: None,
+            character: String::new(),
+            foreground: None,
+            background: None,
+            layer: None,
+            clipboard: None,
+            blank_is_transparent: false,
+            stamp: None,
+            transform: None,
+            flood_fill: FloodFillOptions::default(),
+            pattern: None,
+            gradient: None,
+            end: None,
+            brush: Brush::default(),
+            strict: None,
+            author: None,
+        }
+    }
+}
+
+impl DrawCommand {
+    /// Whether shapes which are partly off the canvas or draw nothing are refused
+    pub fn is_strict(&self) -> bool {
+        self.strict.unwrap_or(false)
+    }
+
+    /// The cell this command paints onto the canvas
+    pub fn cell(&self) -> super::canvas::Cell {
+        super::canvas::Cell {
+            character: self.character.clone(),
+            foreground: self.foreground,
+            background: self.background,
+        }
+    }
+
+    /// The cell this command paints at a point on the canvas, following its pattern if it has one
+    pub fn cell_at(&self, position: &super::canvas::Point) -> super::canvas::Cell {
+        let character = self.pattern
+            .as_ref()
+            .and_then(|pattern| pattern.character_at(position))
+            .unwrap_or_else(|| self.character.clone());
+
+        super::canvas::Cell {
+            character,
+            ..self.cell()
+        }
+    }
+
+    /// The clipboard this command copies to or pastes from
+    pub fn clipboard_name(&self) -> String {
+        self.clipboard.clone().unwrap_or_else(|| String::from(DEFAULT_CLIPBOARD))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::canvas::Point;
+
+    fn characters(pattern: &Pattern, width: i32, height: i32) -> String {
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| pattern.character_at(&Point { x, y }).unwrap_or_else(|| String::from("_")))
+                    .collect::<String>() + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tile_repeats_across_the_canvas() {
+        let tile = Pattern::Tile { rows: vec![String::from("ab"), String::from("c")] };
+
+        assert_eq!("abab\ncccc\nabab\n", characters(&tile, 4, 3));
+        assert_eq!(Some(String::from("b")), tile.character_at(&Point { x: -1, y: -2 }));
+    }
+
+    #[test]
+    fn test_dither_levels() {
+        let none = Pattern::Dither { other_character: String::from("#"), level: 0 };
+        let half = Pattern::Dither { other_character: String::from("#"), level: 8 };
+        let all = Pattern::Dither { other_character: String::from("#"), level: DITHER_LEVELS };
+
+        assert_eq!("____\n____\n____\n____\n", characters(&none, 4, 4));
+        assert_eq!("#_#_\n_#_#\n#_#_\n_#_#\n", characters(&half, 4, 4));
+        assert_eq!("####\n####\n####\n####\n", characters(&all, 4, 4));
+    }
 }
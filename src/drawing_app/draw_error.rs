@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use super::canvas::{Dimensions, Point, Region};
+use super::clip::ClipError;
+use super::commands::MAX_BRUSH_WIDTH;
+use super::locks::LockError;
+use super::storage::DamagedCanvasFile;
+
+/// Reasons a draw command can't be applied
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawError {
+    MissingField(&'static str), // the command needs this field for its name
+    InvalidDimensions(Dimensions),
+    InvalidCharacter(String),
+    InvalidBrushWidth(i32),
+    OutOfBounds(Point),
+    OffCanvas { region: Region, canvas: Dimensions }, // only refused in strict mode, otherwise the region is clipped
+    ZeroSized(Dimensions), // only refused in strict mode, otherwise nothing is drawn
+    LayerNotFound(String),
+    MaskLayerNotFound(String),
+    StampNotFound(String),
+    ClipboardNotFound(String),
+    EmptyGradientRamp,
+    Clipped(ClipError), // the command can't be kept inside the clip
+    Locked(LockError),
+    DamagedCanvas(DamagedCanvasFile),
+    Storage(String), // the canvas couldn't be loaded or saved
+}
+
+impl fmt::Display for DrawError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrawError::MissingField(field) => write!(f, "This command needs a {}.", field),
+            DrawError::InvalidDimensions(dimensions) => write!(
+                f,
+                "Dimensions {} x {} are invalid, width and height can't be negative.",
+                dimensions.width, dimensions.height,
+            ),
+            DrawError::InvalidCharacter(character) => write!(f, "'{}' should be a single character.", character),
+            DrawError::InvalidBrushWidth(width) => write!(
+                f,
+                "A brush {} cells wide is invalid, the width must be between 1 and {}.",
+                width, MAX_BRUSH_WIDTH,
+            ),
+            DrawError::OutOfBounds(point) => write!(f, "Position ({}, {}) is outside the canvas.", point.x, point.y),
+            DrawError::OffCanvas { region, canvas } => write!(
+                f,
+                "The area from ({}, {}) to ({}, {}) goes past the {} of the {} x {} canvas, which strict mode refuses.",
+                region.position.x, region.position.y,
+                region.right() - 1, region.bottom() - 1,
+                crossed_edges(region, canvas), canvas.width, canvas.height,
+            ),
+            DrawError::ZeroSized(dimensions) => write!(
+                f,
+                "A {} x {} shape draws nothing, which strict mode refuses.",
+                dimensions.width, dimensions.height,
+            ),
+            DrawError::LayerNotFound(name) => write!(f, "There is no layer called '{}'.", name),
+            DrawError::MaskLayerNotFound(name) => write!(f, "There is no layer called '{}' to use as a mask.", name),
+            DrawError::StampNotFound(name) => write!(f, "There is no stamp called '{}'.", name),
+            DrawError::ClipboardNotFound(name) => write!(f, "Nothing has been copied to the '{}' clipboard.", name),
+            DrawError::EmptyGradientRamp => write!(f, "A gradient ramp needs at least one character."),
+            DrawError::Clipped(error) => write!(f, "{}", error),
+            DrawError::Locked(error) => write!(f, "{}", error),
+            DrawError::DamagedCanvas(damaged) => write!(f, "{}", damaged),
+            DrawError::Storage(reason) => write!(f, "Problem loading or saving the canvas: {}", reason),
+        }
+    }
+}
+
+impl Error for DrawError {}
+
+/// Names the edges of the canvas a region goes past, as in "left and bottom edges"
+fn crossed_edges(region: &Region, canvas: &Dimensions) -> String {
+    let edges: Vec<&str> = [
+        ("left", region.position.x < 0),
+        ("top", region.position.y < 0),
+        ("right", region.right() > canvas.width as i64),
+        ("bottom", region.bottom() > canvas.height as i64),
+    ]
+        .iter()
+        .filter(|(_, crossed)| *crossed)
+        .map(|(edge, _)| *edge)
+        .collect();
+
+    match edges.split_last() {
+        Some((last, [])) => format!("{} edge", last),
+        Some((last, rest)) => format!("{} and {} edges", rest.join(", "), last),
+        None => String::from("edges"),
+    }
+}
+
+impl From<ClipError> for DrawError {
+    fn from(error: ClipError) -> DrawError {
+        DrawError::Clipped(error)
+    }
+}
+
+impl From<LockError> for DrawError {
+    fn from(error: LockError) -> DrawError {
+        DrawError::Locked(error)
+    }
+}
+
+/// Draw errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<DrawError> for io::Error {
+    fn from(error: DrawError) -> io::Error {
+        let kind = match error {
+            DrawError::Locked(_) => io::ErrorKind::PermissionDenied,
+            DrawError::DamagedCanvas(_) => io::ErrorKind::InvalidData,
+            DrawError::Storage(_) => io::ErrorKind::Other,
+            _ => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// Unpacks a draw error or damaged canvas file carried by the storage layer,
+/// and treats any other failure as a storage failure
+impl From<io::Error> for DrawError {
+    fn from(error: io::Error) -> DrawError {
+        if let Some(inner) = error.get_ref() {
+            if let Some(draw_error) = inner.downcast_ref::<DrawError>() {
+                return draw_error.clone();
+            }
+            if let Some(damaged) = inner.downcast_ref::<DamagedCanvasFile>() {
+                return DrawError::DamagedCanvas(damaged.clone());
+            }
+        }
+        DrawError::Storage(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_errors_survive_the_storage_layer() {
+        let error = DrawError::LayerNotFound(String::from("sketch"));
+        assert_eq!(error.clone(), DrawError::from(io::Error::from(error)));
+
+        let off_canvas = DrawError::OffCanvas {
+            region: Region { position: Point { x: 8, y: -1 }, dimensions: Dimensions { width: 4, height: 3 } },
+            canvas: Dimensions { width: 10, height: 5 },
+        };
+        assert_eq!(
+            "The area from (8, -1) to (11, 1) goes past the top and right edges of the 10 x 5 canvas, which strict mode refuses.",
+            off_canvas.to_string()
+        );
+
+        let storage_error = DrawError::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(DrawError::Storage(String::from("no such file")), storage_error);
+    }
+}
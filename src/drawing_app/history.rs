@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use super::canvas::Canvas;
+use super::commands::CommandName;
+
+/// How many applied commands are remembered, the oldest are forgotten first
+pub const MAX_HISTORY: usize = 100;
+
+/// What was done to the canvas, shown as the name of the draw command or other change
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Action {
+    Draw(CommandName),
+    Edit(Edit),
+}
+
+/// Changes made to the canvas other than by draw commands
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Edit {
+    CreateLayer,
+    UpdateLayer,
+    DeleteLayer,
+    CreateStamp,
+    SetClip,
+    ClearClip,
+    CreateLock,
+    DeleteLock,
+    Resize,
+    Crop,
+}
+
+/// A change which was applied to the canvas, and who made it
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Change {
+    pub action: Action,
+    pub author: Option<String>, // name of the API key which made the change, none when the server has no keys
+    pub cells_changed: usize,
+}
+
+impl Canvas {
+    /// Records that a change was applied, forgetting the oldest change once the history is full
+    pub fn record_change(&mut self, change: Change) {
+        if self.history.len() >= MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(change);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_latest_changes_are_kept() {
+        let mut canvas = Canvas::blank_canvas(2, 2, ".");
+
+        for cells_changed in 0..MAX_HISTORY + 5 {
+            canvas.record_change(Change { action: Action::Draw(CommandName::FillRectangle), author: None, cells_changed });
+        }
+
+        assert_eq!(MAX_HISTORY, canvas.history.len());
+        assert_eq!(5, canvas.history[0].cells_changed);
+        assert_eq!(MAX_HISTORY + 4, canvas.history[MAX_HISTORY - 1].cells_changed);
+    }
+
+    #[test]
+    fn test_actions_are_named_by_their_command_or_edit() {
+        let draw = Change { action: Action::Draw(CommandName::FloodFill), author: None, cells_changed: 3 };
+        let edit = Change { action: Action::Edit(Edit::Resize), author: Some(String::from("ada")), cells_changed: 8 };
+        let json = serde_json::to_string(&vec![draw.clone(), edit.clone()]).unwrap();
+
+        assert_eq!(
+            r#"[{"action":"FloodFill","author":null,"cells_changed":3},{"action":"Resize","author":"ada","cells_changed":8}]"#,
+            json
+        );
+        assert_eq!(vec![draw, edit], serde_json::from_str::<Vec<Change>>(&json).unwrap());
+    }
+}
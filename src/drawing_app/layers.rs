@@ -0,0 +1,233 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+use super::canvas::{Canvas, Cell};
+
+/// Name given to the bottom layer of every new canvas
+pub const BACKGROUND_LAYER: &str = "background";
+
+/// A named grid of cells, layers are stacked on top of one another to make up the canvas
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub pixels: Vec<Vec<Cell>>,
+}
+
+impl Layer {
+    /// Creates a visible layer where every cell is transparent
+    pub fn transparent(name: &str, width: i32, height: i32) -> Layer {
+        Layer {
+            name: String::from(name),
+            visible: true,
+            pixels: vec![vec![Cell::transparent(); width as usize]; height as usize],
+        }
+    }
+}
+
+/// Summary of a layer, without its pixels
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LayerInfo {
+    pub name: String,
+    pub visible: bool,
+    pub index: usize,
+}
+
+/// Reasons a layer operation can be refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayerError {
+    NotFound(String),
+    AlreadyExists(String),
+    LastLayer,
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayerError::NotFound(name) => write!(f, "No layer named '{}' exists.", name),
+            LayerError::AlreadyExists(name) => write!(f, "A layer named '{}' already exists.", name),
+            LayerError::LastLayer => write!(f, "The canvas must keep at least one layer."),
+        }
+    }
+}
+
+impl Error for LayerError {}
+
+/// Layer errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<LayerError> for io::Error {
+    fn from(error: LayerError) -> io::Error {
+        let kind = match error {
+            LayerError::NotFound(_) => io::ErrorKind::NotFound,
+            LayerError::AlreadyExists(_) => io::ErrorKind::AlreadyExists,
+            LayerError::LastLayer => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+impl Canvas {
+    /// Finds the position of a layer in the stack
+    /// No name refers to the bottom layer
+    pub fn layer_index(&self, name: &Option<String>) -> Option<usize> {
+        match name {
+            Some(name) => self.layers.iter().position(|layer| &layer.name == name),
+            None => Some(0),
+        }
+    }
+
+    /// The layer a draw command should be applied to
+    pub fn layer_mut(&mut self, name: &Option<String>) -> Option<&mut Layer> {
+        let index = self.layer_index(name)?;
+        self.layers.get_mut(index)
+    }
+
+    /// What a pixel of a layer holds when nothing has been drawn there:
+    /// the blank character on the bottom layer, and transparency on the others
+    pub fn empty_cell(&self, layer_index: usize) -> Cell {
+        if layer_index == 0 {
+            Cell::from(self.blank_character.as_str())
+        } else {
+            Cell::transparent()
+        }
+    }
+
+    /// Lists the layers from bottom to top
+    pub fn layer_info(&self) -> Vec<LayerInfo> {
+        self.layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| LayerInfo {
+                name: layer.name.clone(),
+                visible: layer.visible,
+                index,
+            })
+            .collect()
+    }
+
+    /// Adds a new transparent layer on top of the stack
+    pub fn add_layer(&mut self, name: &str) -> Result<(), LayerError> {
+        if self.layers.iter().any(|layer| layer.name == name) {
+            return Err(LayerError::AlreadyExists(String::from(name)));
+        }
+
+        self.layers.push(Layer::transparent(name, self.dimensions.width, self.dimensions.height));
+        Ok(())
+    }
+
+    /// Deletes a layer and everything drawn on it
+    pub fn remove_layer(&mut self, name: &str) -> Result<(), LayerError> {
+        let index = self.find_layer(name)?;
+        if self.layers.len() == 1 {
+            return Err(LayerError::LastLayer);
+        }
+
+        self.layers.remove(index);
+        Ok(())
+    }
+
+    /// Moves a layer to a new position in the stack, 0 being the bottom
+    /// Positions past the top of the stack move the layer to the top
+    pub fn move_layer(&mut self, name: &str, index: usize) -> Result<(), LayerError> {
+        let current_index = self.find_layer(name)?;
+        let layer = self.layers.remove(current_index);
+        let index = index.min(self.layers.len());
+
+        self.layers.insert(index, layer);
+        Ok(())
+    }
+
+    /// Shows or hides a layer, hidden layers are left out when rendering
+    pub fn set_layer_visibility(&mut self, name: &str, visible: bool) -> Result<(), LayerError> {
+        let index = self.find_layer(name)?;
+        self.layers[index].visible = visible;
+        Ok(())
+    }
+
+    /// Flattens the visible layers into a single grid
+    /// Each pixel is taken from the top-most layer where it isn't transparent
+    pub fn composite(&self) -> Vec<Vec<Cell>> {
+        (0..self.dimensions.height as usize)
+            .map(|row_index| {
+                (0..self.dimensions.width as usize)
+                    .map(|column_index| self.composite_cell(row_index, column_index))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The pixel shown at one point of the flattened canvas
+    pub fn composite_cell(&self, row_index: usize, column_index: usize) -> Cell {
+        self.layers
+            .iter()
+            .rev()
+            .filter(|layer| layer.visible)
+            .filter_map(|layer| layer.pixels.get(row_index)?.get(column_index))
+            .find(|cell| !cell.is_transparent())
+            .cloned()
+            .unwrap_or_else(|| Cell::from(self.blank_character.as_str()))
+    }
+
+    fn find_layer(&self, name: &str) -> Result<usize, LayerError> {
+        self.layers
+            .iter()
+            .position(|layer| layer.name == name)
+            .ok_or_else(|| LayerError::NotFound(String::from(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::canvas;
+
+    #[test]
+    fn test_composite_top_down() {
+        let mut canvas = canvas::Canvas::blank_canvas(4, 2, ".");
+        canvas.add_layer("middle").unwrap();
+        canvas.add_layer("top").unwrap();
+        canvas.layers[1].pixels[0][0] = Cell::from("M");
+        canvas.layers[1].pixels[0][1] = Cell::from("M");
+        canvas.layers[2].pixels[0][1] = Cell::from("T");
+
+        assert_eq!("MT..\n....\n", canvas.to_string());
+
+        canvas.set_layer_visibility("top", false).unwrap();
+        assert_eq!("MM..\n....\n", canvas.to_string());
+
+        canvas.move_layer("middle", 0).unwrap();
+        assert_eq!("....\n....\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_transparent_base_shows_blank_character() {
+        let mut canvas = canvas::Canvas::blank_canvas(2, 1, ".");
+        canvas.layers[0].pixels[0][0] = Cell::transparent();
+
+        assert_eq!("..\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_layer_management_errors() {
+        let mut canvas = canvas::Canvas::blank_canvas(2, 2, " ");
+        canvas.add_layer("sketch").unwrap();
+
+        assert_eq!(Err(LayerError::AlreadyExists(String::from("sketch"))), canvas.add_layer("sketch"));
+        assert_eq!(Err(LayerError::NotFound(String::from("ink"))), canvas.remove_layer("ink"));
+
+        canvas.remove_layer(BACKGROUND_LAYER).unwrap();
+        assert_eq!(Err(LayerError::LastLayer), canvas.remove_layer("sketch"));
+    }
+
+    #[test]
+    fn test_move_layer_past_top() {
+        let mut canvas = canvas::Canvas::blank_canvas(2, 2, " ");
+        canvas.add_layer("one").unwrap();
+        canvas.add_layer("two").unwrap();
+        canvas.move_layer(BACKGROUND_LAYER, 10).unwrap();
+
+        let names: Vec<String> = canvas.layer_info().into_iter().map(|info| info.name).collect();
+        assert_eq!(vec!["one", "two", BACKGROUND_LAYER], names);
+    }
+}
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+use super::canvas::{Canvas, Point, Region};
+use super::clip;
+
+/// What happens to a draw command which would change a locked region
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockMode {
+    #[default]
+    Reject, // the whole draw request fails
+    Clip,   // the command still runs, but leaves the locked region alone
+}
+
+/// A protected rectangle of the canvas, kept with the canvas under a name
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Lock {
+    pub owner: String,
+    pub region: Region,
+    #[serde(default)]
+    pub mode: LockMode,
+}
+
+/// A lock together with its name
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LockInfo {
+    pub name: String,
+    pub owner: String,
+    pub region: Region,
+    pub mode: LockMode,
+}
+
+/// Reasons a lock can't be changed, or a draw command was refused because of one
+#[derive(Clone, Debug, PartialEq)]
+pub enum LockError {
+    EmptyName,
+    EmptyOwner,
+    AlreadyExists(String),
+    NotFound(String),
+    Locked(LockInfo),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::EmptyName => write!(f, "A lock needs a name."),
+            LockError::EmptyOwner => write!(f, "A lock needs an owner."),
+            LockError::AlreadyExists(name) => write!(f, "A lock named '{}' already exists.", name),
+            LockError::NotFound(name) => write!(f, "No lock named '{}' exists.", name),
+            LockError::Locked(lock) => write!(f, "The region is locked by '{}' ({}).", lock.owner, lock.name),
+        }
+    }
+}
+
+impl Error for LockError {}
+
+/// Lock errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<LockError> for io::Error {
+    fn from(error: LockError) -> io::Error {
+        let kind = match error {
+            LockError::EmptyName | LockError::EmptyOwner => io::ErrorKind::InvalidInput,
+            LockError::AlreadyExists(_) => io::ErrorKind::AlreadyExists,
+            LockError::NotFound(_) => io::ErrorKind::NotFound,
+            LockError::Locked(_) => io::ErrorKind::PermissionDenied,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+impl Canvas {
+    /// Protects a region of the canvas under a new name
+    pub fn add_lock(&mut self, name: &str, lock: Lock) -> Result<(), LockError> {
+        if name.is_empty() {
+            return Err(LockError::EmptyName);
+        }
+        if lock.owner.is_empty() {
+            return Err(LockError::EmptyOwner);
+        }
+        if self.locks.contains_key(name) {
+            return Err(LockError::AlreadyExists(String::from(name)));
+        }
+
+        self.locks.insert(String::from(name), lock);
+        Ok(())
+    }
+
+    /// Removes a lock, so its region can be drawn on again
+    pub fn remove_lock(&mut self, name: &str) -> Result<(), LockError> {
+        match self.locks.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(LockError::NotFound(String::from(name))),
+        }
+    }
+
+    /// Lists the locks in name order
+    pub fn lock_info(&self) -> Vec<LockInfo> {
+        self.locks
+            .iter()
+            .map(|(name, lock)| LockInfo {
+                name: name.clone(),
+                owner: lock.owner.clone(),
+                region: lock.region.clone(),
+                mode: lock.mode,
+            })
+            .collect()
+    }
+
+    /// Checks a draw command's result against the locks of the canvas it was drawn on
+    /// Changes inside clipping locks are undone. Any change left inside a rejecting lock fails the command,
+    /// naming the first such lock. A command which changed the shape of the canvas touches every lock
+    pub fn enforce_locks(&self, after: Canvas) -> Result<Canvas, LockError> {
+        let locked = |mode: LockMode, point: &Point| {
+            self.locks.values().any(|lock| lock.mode == mode && lock.region.contains(point))
+        };
+
+        // a command which changed the shape of the canvas can't be partly undone, so a clipping lock refuses it
+        let after = match self.lock_info().into_iter().find(|info| info.mode == LockMode::Clip) {
+            Some(info) => clip::protect(self, after, |point| locked(LockMode::Clip, point)).map_err(|_| LockError::Locked(info))?,
+            None => after,
+        };
+
+        let reshaped = !clip::same_shape(self, &after);
+        for info in self.lock_info().into_iter().filter(|info| info.mode == LockMode::Reject) {
+            if reshaped || self.changed_within(&after, &info.region) {
+                return Err(LockError::Locked(info));
+            }
+        }
+
+        Ok(after)
+    }
+
+    /// Checks a change to the layers themselves, such as removing, hiding or moving one, against every lock
+    /// Such a change can't be partly undone, so a lock of either mode refuses it if its region looks any different
+    pub fn check_layer_change(&self, after: &Canvas) -> Result<(), LockError> {
+        match self.lock_info().into_iter().find(|info| self.changed_within(after, &info.region)) {
+            Some(info) => Err(LockError::Locked(info)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether another canvas of the same size looks different inside a region, or any of its layers does
+    /// Layers are only compared one by one while the canvases have the same layers in the same order
+    fn changed_within(&self, other: &Canvas, region: &Region) -> bool {
+        let rows = region.position.y.max(0) as i64..region.bottom().min(self.dimensions.height as i64);
+        let columns = region.position.x.max(0) as i64..region.right().min(self.dimensions.width as i64);
+        let same_layers = self.layers.iter().map(|layer| &layer.name).eq(other.layers.iter().map(|layer| &layer.name));
+
+        rows.flat_map(|row_index| columns.clone().map(move |column_index| (row_index as usize, column_index as usize)))
+            .any(|(row_index, column_index)| {
+                let layer_changed = same_layers && self.layers
+                    .iter()
+                    .zip(&other.layers)
+                    .any(|(layer, other_layer)| layer.pixels[row_index][column_index] != other_layer.pixels[row_index][column_index]);
+                layer_changed || self.composite_cell(row_index, column_index) != other.composite_cell(row_index, column_index)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::canvas::{Cell, Dimensions};
+
+    fn lock(x: i32, y: i32, width: i32, height: i32, mode: LockMode) -> Lock {
+        Lock {
+            owner: String::from("ada"),
+            region: Region {
+                position: Point { x, y },
+                dimensions: Dimensions { width, height },
+            },
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove_locks() {
+        let mut canvas = Canvas::blank_canvas(4, 2, ".");
+
+        canvas.add_lock("header", lock(0, 0, 4, 1, LockMode::Reject)).unwrap();
+        assert_eq!(Err(LockError::AlreadyExists(String::from("header"))), canvas.add_lock("header", lock(0, 0, 1, 1, LockMode::Clip)));
+        assert_eq!(Err(LockError::EmptyName), canvas.add_lock("", lock(0, 0, 1, 1, LockMode::Clip)));
+        assert_eq!(Err(LockError::EmptyOwner), canvas.add_lock("footer", Lock { owner: String::new(), ..lock(0, 1, 4, 1, LockMode::Clip) }));
+        assert_eq!(vec!["header"], canvas.lock_info().iter().map(|info| info.name.as_str()).collect::<Vec<&str>>());
+
+        canvas.remove_lock("header").unwrap();
+        assert_eq!(Err(LockError::NotFound(String::from("header"))), canvas.remove_lock("header"));
+    }
+
+    #[test]
+    fn test_clipping_locks_undo_changes() {
+        let mut before = Canvas::blank_canvas(4, 2, ".");
+        before.add_lock("corner", lock(2, 0, 5, 1, LockMode::Clip)).unwrap();
+        let mut after = before.clone();
+        after.layers[0].pixels = vec![vec![Cell::from("#"); 4]; 2];
+
+        assert_eq!("##..\n####\n", before.enforce_locks(after).unwrap().to_string());
+
+        // a reshaped canvas can't be partly undone
+        let reshaped = Canvas::blank_canvas(2, 4, ".");
+        match before.enforce_locks(reshaped) {
+            Err(LockError::Locked(info)) => assert_eq!("corner", info.name),
+            _ => panic!("expected the corner lock to refuse the reshaped canvas"),
+        }
+    }
+
+    #[test]
+    fn test_rejecting_locks_refuse_changes() {
+        let mut before = Canvas::blank_canvas(4, 2, ".");
+        before.add_lock("header", lock(0, 0, 4, 1, LockMode::Reject)).unwrap();
+
+        // changes outside the lock are fine
+        let mut after = before.clone();
+        after.layers[0].pixels[1][0] = Cell::from("#");
+        assert!(before.enforce_locks(after).is_ok());
+
+        let mut after = before.clone();
+        after.layers[0].pixels[0][3] = Cell::from("#");
+        match before.enforce_locks(after) {
+            Err(LockError::Locked(info)) => assert_eq!("header", info.name),
+            _ => panic!("expected the header lock to refuse the change"),
+        }
+
+        let reshaped = Canvas::blank_canvas(2, 4, ".");
+        assert!(before.enforce_locks(reshaped).is_err());
+
+        // a lock reaching past i32::MAX only covers the canvas it overlaps
+        before.add_lock("edge", lock(3, 1, i32::MAX, i32::MAX, LockMode::Reject)).unwrap();
+        let mut after = before.clone();
+        after.layers[0].pixels[1][3] = Cell::from("#");
+        assert!(before.enforce_locks(after).is_err());
+    }
+
+    #[test]
+    fn test_layer_changes_showing_in_a_lock_are_refused() {
+        let mut before = Canvas::blank_canvas(4, 2, ".");
+        before.add_layer("header").unwrap();
+        before.add_layer("cover").unwrap();
+        before.layers[1].pixels[0] = vec![Cell::from("H"); 4];
+        before.layers[2].pixels[1] = vec![Cell::from("C"); 4];
+        before.add_lock("header", lock(0, 0, 4, 1, LockMode::Reject)).unwrap();
+
+        let refused = |change: &dyn Fn(&mut Canvas)| {
+            let mut after = before.clone();
+            change(&mut after);
+            matches!(before.check_layer_change(&after), Err(LockError::Locked(info)) if info.name == "header")
+        };
+
+        // deleting, hiding or covering the locked header changes what it shows
+        assert!(refused(&|canvas| canvas.remove_layer("header").unwrap()));
+        assert!(refused(&|canvas| canvas.set_layer_visibility("header", false).unwrap()));
+        assert!(refused(&|canvas| canvas.move_layer("background", 2).unwrap()));
+
+        // changes which only show outside the lock are fine
+        assert!(!refused(&|canvas| canvas.remove_layer("cover").unwrap()));
+        assert!(!refused(&|canvas| canvas.move_layer("cover", 1).unwrap()));
+    }
+}
@@ -0,0 +1,185 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use super::canvas::{Canvas, Dimensions};
+use super::resize::Anchor;
+
+/// What to do when the saved canvas doesn't have the configured size or blank character
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OnMismatch {
+    /// Stop, leaving the saved canvas untouched
+    #[default]
+    Refuse,
+    /// Resize the saved canvas to the configured size, keeping its blank character
+    Resize,
+    /// Resize the saved canvas and swap its blank character for the configured one
+    Migrate,
+}
+
+impl FromStr for OnMismatch {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<OnMismatch, String> {
+        match value {
+            "refuse" => Ok(OnMismatch::Refuse),
+            "resize" => Ok(OnMismatch::Resize),
+            "migrate" => Ok(OnMismatch::Migrate),
+            _ => Err(String::from("should be refuse, resize or migrate")),
+        }
+    }
+}
+
+/// The parts of the canvas which were set by flag, environment or config file, to check a saved canvas against
+/// Anything left out follows the saved canvas, so a canvas resized while the server ran is kept after a restart
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfiguredCanvas {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub blank_character: Option<String>,
+}
+
+/// The saved canvas differs from the configuration, and it was chosen to refuse it
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanvasMismatch {
+    pub saved: Dimensions,
+    pub saved_blank_character: String,
+    pub configured: Dimensions,
+    pub configured_blank_character: String,
+}
+
+impl fmt::Display for CanvasMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The saved canvas is {} x {} with '{}' as the blank character, but {} x {} with '{}' is configured. \
+            Use --on-mismatch resize or migrate to keep the saved canvas, or --fresh to start again.",
+            self.saved.width, self.saved.height, self.saved_blank_character,
+            self.configured.width, self.configured.height, self.configured_blank_character,
+        )
+    }
+}
+
+impl Error for CanvasMismatch {}
+
+/// Mismatches are carried inside an io::Error so they can pass through the application's storage layer
+impl From<CanvasMismatch> for io::Error {
+    fn from(error: CanvasMismatch) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl Canvas {
+    /// Brings a saved canvas in line with whatever size and blank character were configured, or refuses it
+    /// Resizing keeps the top left corner in place
+    pub fn reconcile(
+        &mut self,
+        configured: &ConfiguredCanvas,
+        on_mismatch: OnMismatch,
+    ) -> Result<(), CanvasMismatch> {
+        let dimensions = &Dimensions {
+            width: configured.width.unwrap_or(self.dimensions.width),
+            height: configured.height.unwrap_or(self.dimensions.height),
+        };
+        let blank_character = configured.blank_character.clone().unwrap_or_else(|| self.blank_character.clone());
+        let blank_character = blank_character.as_str();
+
+        let same_size = self.dimensions == *dimensions;
+        let same_blank = self.blank_character == blank_character;
+        if same_size && same_blank {
+            return Ok(());
+        }
+
+        if on_mismatch == OnMismatch::Refuse {
+            return Err(CanvasMismatch {
+                saved: self.dimensions.clone(),
+                saved_blank_character: self.blank_character.clone(),
+                configured: dimensions.clone(),
+                configured_blank_character: String::from(blank_character),
+            });
+        }
+
+        if !same_size {
+            // the configuration only allows sizes above 0, so this can't fail
+            let _ = self.resize(dimensions, Anchor::TopLeft);
+        }
+        if on_mismatch == OnMismatch::Migrate {
+            self.replace_blank_character(blank_character);
+        }
+        Ok(())
+    }
+
+    /// Changes the blank character, including every pixel of the bottom layer which still shows the old one
+    pub fn replace_blank_character(&mut self, blank_character: &str) {
+        let old = self.empty_cell(0);
+        if let Some(bottom) = self.layers.first_mut() {
+            for pixel in bottom.pixels.iter_mut().flatten() {
+                if *pixel == old {
+                    pixel.character = String::from(blank_character);
+                }
+            }
+        }
+        self.blank_character = String::from(blank_character);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved_canvas() -> Canvas {
+        let mut canvas = Canvas::blank_canvas(3, 2, ".");
+        canvas.layers[0].pixels[0][0].character = String::from("#");
+        canvas
+    }
+
+    fn configured(width: i32, height: i32, blank_character: &str) -> ConfiguredCanvas {
+        ConfiguredCanvas {
+            width: Some(width),
+            height: Some(height),
+            blank_character: Some(String::from(blank_character)),
+        }
+    }
+
+    #[test]
+    fn test_matching_canvas_is_kept() {
+        let mut canvas = saved_canvas();
+        canvas.reconcile(&configured(3, 2, "."), OnMismatch::Refuse).unwrap();
+        assert_eq!("#..\n...\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_mismatched_canvas_is_refused() {
+        let mut canvas = saved_canvas();
+        let error = canvas.reconcile(&configured(4, 2, "."), OnMismatch::Refuse).unwrap_err();
+        assert_eq!(Dimensions { width: 3, height: 2 }, error.saved);
+        assert_eq!(Dimensions { width: 4, height: 2 }, error.configured);
+        assert_eq!("#..\n...\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_resize_and_migrate() {
+        let mut resized = saved_canvas();
+        resized.reconcile(&configured(4, 1, " "), OnMismatch::Resize).unwrap();
+        assert_eq!("#...\n", resized.to_string());
+        assert_eq!(".", resized.blank_character);
+
+        let mut migrated = saved_canvas();
+        migrated.reconcile(&configured(4, 1, " "), OnMismatch::Migrate).unwrap();
+        assert_eq!("#   \n", migrated.to_string());
+        assert_eq!(" ", migrated.blank_character);
+    }
+
+    #[test]
+    fn test_settings_left_out_follow_the_saved_canvas() {
+        let mut canvas = saved_canvas();
+        canvas.reconcile(&ConfiguredCanvas::default(), OnMismatch::Refuse).unwrap();
+        assert_eq!("#..\n...\n", canvas.to_string());
+
+        let taller = ConfiguredCanvas { height: Some(3), ..ConfiguredCanvas::default() };
+        let error = canvas.reconcile(&taller, OnMismatch::Refuse).unwrap_err();
+        assert_eq!(Dimensions { width: 3, height: 3 }, error.configured);
+        assert_eq!(".", error.configured_blank_character);
+    }
+}
@@ -1,4 +1,13 @@
 pub mod application;
 pub mod canvas;
+pub mod clip;
 pub mod commands;
+pub mod draw_error;
+pub mod history;
+pub mod layers;
+pub mod locks;
+pub mod mismatch;
+pub mod resize;
+pub mod stamps;
+pub mod storage;
 mod operations;
\ No newline at end of file
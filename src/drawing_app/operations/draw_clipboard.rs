@@ -0,0 +1,165 @@
+use super::super::canvas;
+use super::super::commands;
+use super::super::commands::CommandName;
+use super::super::draw_error::DrawError;
+use super::utils;
+
+/// Executes a Copy, Cut or Paste command and returns a new canvas with the changes
+/// Copy and cut need dimensions, and only the part of the region on the canvas is taken.
+/// Pasting from a clipboard which hasn't been filled fails, as does using an unknown layer
+pub fn execute(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let layer_index = utils::command_layer_index(previous_state_canvas, command)?;
+
+    match command.name {
+        CommandName::Copy => copy(previous_state_canvas, command, layer_index, false),
+        CommandName::Cut => copy(previous_state_canvas, command, layer_index, true),
+        CommandName::Paste => paste(previous_state_canvas, command, layer_index),
+        _ => Ok(previous_state_canvas.clone()),
+    }
+}
+
+/// Stores a region of the command's layer in its clipboard, clearing the region when cutting
+fn copy(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+    layer_index: usize,
+    clear: bool,
+) -> Result<canvas::Canvas, DrawError> {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let dimensions = utils::command_dimensions(command)?;
+    utils::check_fits_canvas(&new_canvas, command, &command.position, dimensions)?;
+    let (start_point, region_dimensions) = match utils::clip_to_canvas(&new_canvas, &command.position, dimensions) {
+        Some(region) => region,
+        None => return Ok(new_canvas),
+    };
+
+    let region = utils::copy_region(&new_canvas.layers[layer_index], &start_point, &region_dimensions);
+    new_canvas.clipboard.insert(command.clipboard_name(), region);
+
+    if clear {
+        let empty_cell = new_canvas.empty_cell(layer_index);
+        let empty_region = vec![vec![empty_cell; region_dimensions.width as usize]; region_dimensions.height as usize];
+        let canvas_dimensions = new_canvas.dimensions.clone();
+        utils::place_pixels(&mut new_canvas.layers[layer_index], &canvas_dimensions, &start_point, &empty_region, |_| false);
+    }
+
+    Ok(new_canvas)
+}
+
+/// Draws the contents of a clipboard onto the command's layer with its top-left corner at the command's position
+/// With `blank_is_transparent` pixels holding the blank character (or nothing) are left out
+fn paste(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+    layer_index: usize,
+) -> Result<canvas::Canvas, DrawError> {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let pixels = match new_canvas.clipboard.get(&command.clipboard_name()) {
+        Some(pixels) => pixels.clone(),
+        None => return Err(DrawError::ClipboardNotFound(command.clipboard_name())),
+    };
+    utils::check_fits_canvas(&new_canvas, command, &command.position, &utils::grid_dimensions(&pixels))?;
+    let blank_character = new_canvas.blank_character.clone();
+    let dimensions = new_canvas.dimensions.clone();
+
+    utils::place_pixels(&mut new_canvas.layers[layer_index], &dimensions, &command.position, &pixels, |cell| {
+        command.blank_is_transparent && (cell.is_transparent() || cell.character == blank_character)
+    });
+
+    Ok(new_canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{commands, canvas};
+
+    fn clipboard_command(
+        name: commands::CommandName,
+        position: canvas::Point,
+        dimensions: Option<canvas::Dimensions>,
+    ) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name,
+            position,
+            dimensions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_copy_and_paste() {
+        let canvas = canvas::Canvas::from_text("ab..\ncd..\n", 4, 2, ".");
+        let copy = clipboard_command(
+            commands::CommandName::Copy,
+            canvas::Point { x: 0, y: 0 },
+            Some(canvas::Dimensions { width: 2, height: 2 }),
+        );
+        let paste = clipboard_command(commands::CommandName::Paste, canvas::Point { x: 2, y: 0 }, None);
+
+        let actual = execute(&execute(&canvas, &copy).unwrap(), &paste).unwrap();
+
+        assert_eq!("abab\ncdcd\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_cut_clears_region() {
+        let canvas = canvas::Canvas::from_text("ab..\ncd..\n", 4, 2, ".");
+        let cut = clipboard_command(
+            commands::CommandName::Cut,
+            canvas::Point { x: 0, y: 0 },
+            Some(canvas::Dimensions { width: 2, height: 1 }),
+        );
+        let paste = clipboard_command(commands::CommandName::Paste, canvas::Point { x: 3, y: 1 }, None);
+
+        let cut_canvas = execute(&canvas, &cut).unwrap();
+        assert_eq!("....\ncd..\n", cut_canvas.to_string());
+
+        // the part of the paste which falls off the canvas is dropped
+        let actual = execute(&cut_canvas, &paste).unwrap();
+        assert_eq!("....\ncd.a\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_paste_blank_as_transparent() {
+        let canvas = canvas::Canvas::from_text("a.\n..\nxy\n", 2, 3, ".");
+        let copy = clipboard_command(
+            commands::CommandName::Copy,
+            canvas::Point { x: 0, y: 0 },
+            Some(canvas::Dimensions { width: 2, height: 1 }),
+        );
+        let mut paste = clipboard_command(commands::CommandName::Paste, canvas::Point { x: 0, y: 2 }, None);
+
+        let copied = execute(&canvas, &copy).unwrap();
+        assert_eq!("a.\n..\na.\n", execute(&copied, &paste).unwrap().to_string());
+
+        paste.blank_is_transparent = true;
+        assert_eq!("a.\n..\nay\n", execute(&copied, &paste).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_named_clipboards_and_missing_clipboard() {
+        let canvas = canvas::Canvas::from_text("ab\n..\n", 2, 2, ".");
+        let mut copy = clipboard_command(
+            commands::CommandName::Copy,
+            canvas::Point { x: 1, y: 0 },
+            Some(canvas::Dimensions { width: 1, height: 1 }),
+        );
+        copy.clipboard = Some(String::from("b"));
+        let mut paste = clipboard_command(commands::CommandName::Paste, canvas::Point { x: 0, y: 1 }, None);
+
+        let copied = execute(&canvas, &copy).unwrap();
+        assert_eq!(
+            DrawError::ClipboardNotFound(String::from(commands::DEFAULT_CLIPBOARD)),
+            execute(&copied, &paste).unwrap_err()
+        );
+
+        paste.clipboard = Some(String::from("b"));
+        assert_eq!("ab\nb.\n", execute(&copied, &paste).unwrap().to_string());
+    }
+}
@@ -1,26 +1,26 @@
 use super::super::canvas;
 use super::super::commands;
+use super::super::draw_error::DrawError;
 use super::utils;
 
 /// Executes a FillRectangle command and returns a new canvas with the changes
+/// Fails without dimensions, with negative ones, with more than one character or with an unknown layer
 pub fn execute(
-    previous_state_canvas: &canvas::Canvas, 
-    command: &commands::DrawCommand
-) -> canvas::Canvas {
-    match command.clone().dimensions {
-        Some(dimensions) => {
-            if utils::rectangle_size_is_none_zero(&dimensions) {
-                return fill_rectangle(previous_state_canvas, &dimensions, command);
-            }
-            previous_state_canvas.clone()
-        },
-        None => {
-            previous_state_canvas.clone()
-        }
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let dimensions = utils::command_dimensions(command)?;
+    utils::check_character(command)?;
+    utils::command_layer_index(previous_state_canvas, command)?;
+    utils::check_fits_canvas(previous_state_canvas, command, &command.position, dimensions)?;
+
+    if utils::rectangle_size_is_none_zero(dimensions) {
+        return Ok(fill_rectangle(previous_state_canvas, dimensions, command));
     }
+    Ok(previous_state_canvas.clone())
 }
 
-/// Searches a canvas for the pixels to be updated, returns a new canvas with the changes
+/// Searches the command's layer for the pixels to be updated, returns a new canvas with the changes
 fn fill_rectangle(
     previous_state_canvas: &canvas::Canvas, 
     dimensions: &canvas::Dimensions,
@@ -28,28 +28,31 @@ fn fill_rectangle(
 ) -> canvas::Canvas {
     let mut new_canvas = previous_state_canvas.clone();
 
-    previous_state_canvas.pixels
-        .iter()
-        .enumerate()
-        .for_each(|(row_index, row)| {
-            row
-                .iter()
-                .enumerate()
-                .for_each(|(column_index, pixel)| {
-                    if pixel_should_change(
-                        &dimensions,
-                        &command.position, 
-                        row_index as i32, 
-                        column_index as i32
-                    ) {
-                        new_canvas.pixels[row_index][column_index] = command.character;
-                    } else {
-                        new_canvas.pixels[row_index][column_index] = pixel.clone()
-                    }
-                })
-        });
-
-        new_canvas
+    if let Some(layer) = new_canvas.layer_mut(&command.layer) {
+        layer.pixels
+            .iter_mut()
+            .enumerate()
+            .for_each(|(row_index, row)| {
+                row
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(column_index, pixel)| {
+                        if pixel_should_change(
+                            dimensions,
+                            &command.position, 
+                            row_index as i32, 
+                            column_index as i32
+                        ) {
+                            *pixel = command.cell_at(&canvas::Point {
+                                x: column_index as i32,
+                                y: row_index as i32,
+                            });
+                        }
+                    })
+            });
+    }
+
+    new_canvas
 }
 
 /// Given a rectangle with specific dimensions and start point:
@@ -71,7 +74,7 @@ mod tests {
     #[test]
     fn test_simple_draw_fill() {
         // simple rectangle draw operation
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -79,20 +82,21 @@ mod tests {
                 width: 3,
                 height: 4
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    XXX   \n    XXX   \n    XXX   \n    XXX   \n          \n";
 
         assert_eq!(expected, &actual.to_string());
-        assert_eq!(actual.pixels[3][3], ' ');
-        assert_eq!(actual.pixels[3][4], 'X');
+        assert_eq!(actual.layers[0].pixels[3][3].character, " ");
+        assert_eq!(actual.layers[0].pixels[3][4].character, "X");
     }
 
     #[test]
     fn test_width_out_of_bounds() {
         // draw operation should draw up to the end of the canvas and handle error
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -100,18 +104,63 @@ mod tests {
                 width: 8,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    XXXXXX\n    XXXXXX\n    XXXXXX\n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
     }
 
+    #[test]
+    fn test_strict_mode_refuses_rectangles_which_would_be_clipped() {
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
+        let command: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point {x: 4, y: 3},
+            dimensions: Some(canvas::Dimensions {
+                width: 8,
+                height: 3
+            }),
+            character: String::from("X"),
+            strict: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(DrawError::OffCanvas {
+                region: canvas::Region { position: canvas::Point { x: 4, y: 3 }, dimensions: canvas::Dimensions { width: 8, height: 3 } },
+                canvas: canvas::Dimensions { width: 10, height: 8 },
+            }),
+            execute(&canvas, &command).map(|_| ())
+        );
+
+        let empty = commands::DrawCommand { dimensions: Some(canvas::Dimensions { width: 0, height: 3 }), ..command.clone() };
+        assert_eq!(
+            Err(DrawError::ZeroSized(canvas::Dimensions { width: 0, height: 3 })),
+            execute(&canvas, &empty).map(|_| ())
+        );
+
+        // a rectangle whose far edge is past i32::MAX is refused rather than overflowing
+        let far = commands::DrawCommand {
+            position: canvas::Point { x: 2147483000, y: 0 },
+            dimensions: Some(canvas::Dimensions { width: 1000, height: 1 }),
+            ..command.clone()
+        };
+        let error = execute(&canvas, &far).err().unwrap();
+        assert!(matches!(error, DrawError::OffCanvas { .. }));
+        assert!(error.to_string().contains("(2147483999, 0)"));
+        assert_eq!(canvas.to_string(), execute(&canvas, &commands::DrawCommand { strict: None, ..far }).unwrap().to_string());
+
+        // rectangles which fit are drawn as usual
+        let fits = commands::DrawCommand { dimensions: Some(canvas::Dimensions { width: 6, height: 5 }), ..command };
+        assert_eq!(canvas::Cell::from("X"), execute(&canvas, &fits).unwrap().layers[0].pixels[7][9]);
+    }
+
     #[test]
     fn test_height_out_of_bounds() {
         // draw operation should draw up to the end of the canvas and handle error
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -119,9 +168,10 @@ mod tests {
                 width: 3,
                 height: 6
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    !!!   \n    !!!   \n    !!!   \n    !!!   \n    !!!   \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -130,7 +180,7 @@ mod tests {
     #[test]
     fn test_height_and_width_out_of_bounds() {
         // draw operation should draw up to the end of the canvas and handle error
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -138,9 +188,10 @@ mod tests {
                 width: 8,
                 height: 6
             }),
-            character: '4',
+            character: String::from("4"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    444444\n    444444\n    444444\n    444444\n    444444\n";
 
         assert_eq!(expected, &actual.to_string());
@@ -148,8 +199,8 @@ mod tests {
 
     #[test]
     fn test_negative_height() {
-        // a rectangle with negative height has no effect (Nb: one possiblity is to draw rectangle in the opposite direction, but since this feature is not specificed I will leave for now)
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        // a rectangle with negative height is refused (Nb: one possiblity is to draw rectangle in the opposite direction, but since this feature is not specificed I will leave for now)
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -157,18 +208,18 @@ mod tests {
                 width: 8,
                 height: -5
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
-        let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
+        let actual = execute(&canvas, &command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::InvalidDimensions(canvas::Dimensions { width: 8, height: -5 }), actual);
     }
 
     #[test]
     fn test_negative_width() {
-        // a rectangle with negative width has no effect
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        // a rectangle with negative width is refused
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -176,18 +227,18 @@ mod tests {
                 width: -3,
                 height: 5
             }),
-            character: '-',
+            character: String::from("-"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
-        let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
+        let actual = execute(&canvas, &command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::InvalidDimensions(canvas::Dimensions { width: -3, height: 5 }), actual);
     }
 
     #[test]
     fn test_0_height() {
         // a rectangle with 0 height has no effect
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -195,9 +246,10 @@ mod tests {
                 width: 8,
                 height: 0
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -206,7 +258,7 @@ mod tests {
     #[test]
     fn test_0_width() {
         // a rectangle with 0 width has no effect
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -214,9 +266,10 @@ mod tests {
                 width: 0,
                 height: 5
             }),
-            character: '-',
+            character: String::from("-"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -225,7 +278,7 @@ mod tests {
     #[test]
     fn test_negative_x_start_position() {
         // a command with negative x start position draws the first valid place on the canas
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: -3, y: 3},
@@ -233,9 +286,10 @@ mod tests {
                 width: 5,
                 height: 5
             }),
-            character: '-',
+            character: String::from("-"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n--        \n--        \n--        \n--        \n--        \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -244,7 +298,7 @@ mod tests {
     #[test]
     fn test_negative_y_start_position() {
         // a command with negative y start position draws the first valid place on the canvas
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 3, y: -3},
@@ -252,9 +306,10 @@ mod tests {
                 width: 5,
                 height: 5
             }),
-            character: '*',
+            character: String::from("*"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "   *****  \n   *****  \n          \n          \n          \n          \n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -262,27 +317,23 @@ mod tests {
 
     #[test]
     fn test_dimensions_empty() {
-        // for now, we'll do nothing if no dimensions is present (no dimensions == no rectangle)
-        // or we could throw...
-        // or we could beef out our return type, with an "error" or "notice" field, using a monad, so that we could chain together operations even if one of them is potentially erroneous
-        // "Notice: Fill Command posted with no dimensions, did you mean to send flood filL?"
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        // no dimensions == no rectangle, so the command is refused
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
-            dimensions: None,
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
-        let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
+        let actual = execute(&canvas, &command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::MissingField("dimensions"), actual);
     }
 
     #[test]
     fn test_repeated_operation_idempotence() {
         // draw operation should draw up to the end of the canvas and handle error
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -290,14 +341,62 @@ mod tests {
                 width: 8,
                 height: 6
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
 
-        let first_canvas = execute(&canvas, &command);
-        let actual = execute(&first_canvas, &command);
+        let first_canvas = execute(&canvas, &command).unwrap();
+        let actual = execute(&first_canvas, &command).unwrap();
 
         let expected = "          \n          \n          \n    !!!!!!\n    !!!!!!\n    !!!!!!\n    !!!!!!\n    !!!!!!\n";
 
         assert_eq!(expected, &actual.to_string());
     }
+
+    #[test]
+    fn test_fill_with_colour() {
+        // every filled pixel takes the command's colours
+        let canvas = canvas::Canvas::blank_canvas(4, 3, " ");
+        let command: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point {x: 1, y: 1},
+            dimensions: Some(canvas::Dimensions {
+                width: 2,
+                height: 2
+            }),
+            character: String::from("#"),
+            foreground: Some(canvas::Colour::Rgb(255, 0, 0)),
+            background: Some(canvas::Colour::Palette(232)),
+            ..Default::default()
+        };
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("    \n ## \n ## \n", &actual.to_string());
+        assert_eq!(actual.layers[0].pixels[2][2], command.cell());
+        assert_eq!(actual.layers[0].pixels[0][0], canvas::Cell::from(" "));
+    }
+
+    #[test]
+    fn test_fill_with_patterns() {
+        let canvas = canvas::Canvas::blank_canvas(5, 2, " ");
+        let mut command: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point {x: 1, y: 0},
+            dimensions: Some(canvas::Dimensions {
+                width: 4,
+                height: 2
+            }),
+            character: String::from("#"),
+            pattern: Some(commands::Pattern::Checkerboard { other_character: String::from(".") }),
+            ..Default::default()
+        };
+
+        // patterns line up with the canvas, not the rectangle
+        let actual = execute(&canvas, &command).unwrap();
+        assert_eq!(" .#.#\n #.#.\n", &actual.to_string());
+
+        command.pattern = Some(commands::Pattern::Tile { rows: vec![String::from("/-"), String::from("")] });
+        let actual = execute(&canvas, &command).unwrap();
+        assert_eq!(" -/-/\n ####\n", &actual.to_string());
+    }
 }
@@ -1,77 +1,173 @@
 use super::super::canvas;
 use super::super::commands;
+use super::super::draw_error::DrawError;
+use super::super::layers;
 use super::utils;
 
 /// Executes a FloodFill command and returns a new canvas with the changes
+/// The command's flood fill options pick the connectivity and the fill mode, see `commands::FillMode`.
+/// Pixels outside the clip rectangle, or where the mask layer is transparent, are never changed
+/// and stop the fill spreading.
+/// Fails if the fill starts off the canvas, if a layer is unknown, or if a boundary fill has no boundary character
+/// In strict mode a clip rectangle which draws nothing or doesn't lie wholly on the canvas is refused too
 pub fn execute(
     previous_state_canvas: &canvas::Canvas,
     command: &commands::DrawCommand
-) -> canvas::Canvas {
+) -> Result<canvas::Canvas, DrawError> {
     let mut new_canvas = previous_state_canvas.clone();
-    if utils::position_is_on_canvas(&new_canvas, &command.position) {
-        let current_character = utils::get_canvas_pixel(&new_canvas, &command.position);
-        flood_fill(
-            &mut new_canvas,
-            &command.position,
-            current_character,
-            command.character,
-        ); 
+    let dimensions = new_canvas.dimensions.clone();
+    let options = &command.flood_fill;
+    let flood_fill_cell = |position: &canvas::Point| command.cell_at(position);
+
+    utils::check_character(command)?;
+    let layer_index = utils::command_layer_index(&new_canvas, command)?;
+    if !utils::position_is_on_canvas(&new_canvas, &command.position) {
+        return Err(DrawError::OutOfBounds(command.position.clone()));
     }
-    return new_canvas;
-   
+    if options.mode == commands::FillMode::Boundary && options.boundary_character.is_none() {
+        return Err(DrawError::MissingField("boundary_character"));
+    }
+    if let Some(clip) = &options.clip {
+        utils::check_fits_canvas(&new_canvas, command, &clip.position, &clip.dimensions)?;
+    }
+
+    let mask = match &options.mask_layer {
+        Some(name) => match new_canvas.layer_index(&Some(name.clone())) {
+            Some(index) => Some(new_canvas.layers[index].clone()),
+            None => return Err(DrawError::MaskLayerNotFound(name.clone())),
+        },
+        None => None,
+    };
+    let allowed = |position: &canvas::Point| {
+        options.clip.as_ref().is_none_or(|clip| clip.contains(position)) &&
+        mask.as_ref().is_none_or(|mask| !utils::get_layer_pixel(mask, position).is_transparent())
+    };
+
+    if allowed(&command.position) {
+        let layer = &mut new_canvas.layers[layer_index];
+        let current_cell = utils::get_layer_pixel(layer, &command.position).clone();
+
+        match options.mode {
+            commands::FillMode::Flood => flood_fill(
+                layer,
+                &dimensions,
+                &command.position,
+                options.connectivity,
+                &|position, cell| allowed(position) && cell == &current_cell,
+                &flood_fill_cell,
+            ),
+            commands::FillMode::Boundary => {
+                if let Some(boundary_character) = &options.boundary_character {
+                    flood_fill(
+                        layer,
+                        &dimensions,
+                        &command.position,
+                        options.connectivity,
+                        &|position, cell| allowed(position) && &cell.character != boundary_character,
+                        &flood_fill_cell,
+                    )
+                }
+            },
+            commands::FillMode::GlobalReplace => replace_all(
+                layer,
+                &|position, cell| allowed(position) && cell == &current_cell,
+                &flood_fill_cell,
+            ),
+        }
+    }
+    Ok(new_canvas)
 }
 
 /// Flood Fill Algorithm
-/// Recursively searches the canvas for the current character, 
-/// changing it to the desired flood fill character.
-/// Moves up, down, left, right - mutating canvas as it goes
-pub fn flood_fill(
-    canvas: &mut canvas::Canvas,
+/// Scanline fill: from each seed the whole horizontal run of fillable pixels is changed to the
+/// flood fill cell for each position, then the rows above and below the run are scanned for new seeds.
+/// Seeds are kept on a heap allocated stack rather than recursing, so big canvases can't overflow the call stack.
+/// Spreading stops at pixels which `should_fill` rejects, and each pixel is visited at most once, so patterned
+/// fills which reuse the starting character still terminate. With 8-way connectivity the rows are scanned one
+/// pixel further either side
+pub fn flood_fill<F, G>(
+    layer: &mut layers::Layer,
+    dimensions: &canvas::Dimensions,
     position: &canvas::Point,
-    current_character: char,
-    flood_fill_character: char, 
-) {
-    if !utils::position_is_on_canvas(canvas, &position) {
-        return;
-    }
-    if utils::get_canvas_pixel(canvas, &position) != current_character {
-        return;
-    }
-    if utils::get_canvas_pixel(canvas, &position) == flood_fill_character {
-        return;
+    connectivity: commands::Connectivity,
+    should_fill: &F,
+    flood_fill_cell: &G,
+) where
+    F: Fn(&canvas::Point, &canvas::Cell) -> bool,
+    G: Fn(&canvas::Point) -> canvas::Cell,
+{
+    let mut visited = vec![vec![false; dimensions.width as usize]; dimensions.height as usize];
+    let diagonal_reach = match connectivity {
+        commands::Connectivity::Four => 0,
+        commands::Connectivity::Eight => 1,
+    };
+    let fillable = |layer: &layers::Layer, visited: &[Vec<bool>], x: i32, y: i32| {
+        let position = canvas::Point { x, y };
+        utils::position_is_within(dimensions, &position) &&
+        !visited[y as usize][x as usize] &&
+        should_fill(&position, utils::get_layer_pixel(layer, &position))
+    };
+
+    let mut seeds = vec![(position.x, position.y)];
+
+    while let Some((x, y)) = seeds.pop() {
+        if !fillable(layer, &visited, x, y) {
+            continue;
+        }
+
+        let mut left = x;
+        while fillable(layer, &visited, left - 1, y) {
+            left -= 1;
+        }
+        let mut right = x;
+        while fillable(layer, &visited, right + 1, y) {
+            right += 1;
+        }
+
+        for column in left..=right {
+            layer.pixels[y as usize][column as usize] = flood_fill_cell(&canvas::Point { x: column, y });
+            visited[y as usize][column as usize] = true;
+        }
+
+        for row in [y - 1, y + 1] {
+            let mut in_run = false;
+            for column in (left - diagonal_reach)..=(right + diagonal_reach) {
+                if fillable(layer, &visited, column, row) {
+                    if !in_run {
+                        seeds.push((column, row));
+                        in_run = true;
+                    }
+                } else {
+                    in_run = false;
+                }
+            }
+        }
     }
+}
 
-    canvas.pixels[position.y as usize][position.x as usize] = flood_fill_character;
-
-    // move up, down, left, right recursively
-    flood_fill(
-        canvas,
-        &canvas::Point{ x: position.x, y: position.y + 1 }, 
-        current_character, 
-        flood_fill_character
-    );
-    flood_fill(
-        canvas,
-        &canvas::Point{ x: position.x, y: position.y - 1 }, 
-        current_character, 
-        flood_fill_character
-    );
-    flood_fill(
-        canvas,
-        &canvas::Point{ x: position.x + 1, y: position.y }, 
-        current_character, 
-        flood_fill_character
-    ); 
-    flood_fill(
-        canvas,
-        &canvas::Point{ x: position.x - 1, y: position.y }, 
-        current_character, 
-        flood_fill_character
-    );
+/// Changes every pixel of the layer which `should_replace` picks out, connected or not
+fn replace_all<F, G>(
+    layer: &mut layers::Layer,
+    should_replace: &F,
+    flood_fill_cell: &G,
+) where
+    F: Fn(&canvas::Point, &canvas::Cell) -> bool,
+    G: Fn(&canvas::Point) -> canvas::Cell,
+{
+    for (row_index, row) in layer.pixels.iter_mut().enumerate() {
+        for (column_index, pixel) in row.iter_mut().enumerate() {
+            let position = canvas::Point { x: column_index as i32, y: row_index as i32 };
+            if should_replace(&position, pixel) {
+                *pixel = flood_fill_cell(&position);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::VecDeque;
+
     use super::*;
     use super::super::{draw_fill_rectangle, draw_outline_rectangle};
     use super::super::super::{commands, canvas};
@@ -79,7 +175,7 @@ mod tests {
     #[test]
     fn test_simple_flood_fill_operation() {
         // simple rectangle draw operation - should fill all space betewen two squares with space between them
-        let canvas = canvas::Canvas::blank_canvas(8, 9, ' ');
+        let canvas = canvas::Canvas::blank_canvas(8, 9, " ");
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x:0, y: 5},
@@ -87,7 +183,8 @@ mod tests {
                 width: 3,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
@@ -97,19 +194,20 @@ mod tests {
                 width: 3,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FloodFill,
             position: canvas::Point {x:1, y: 0},
-            dimensions: None,
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
-        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command);
-        let canvas2 = draw_fill_rectangle::execute(&canvas1, &second_command);
-        let actual = execute(&canvas2, &third_command);
+        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command).unwrap();
+        let canvas2 = draw_fill_rectangle::execute(&canvas1, &second_command).unwrap();
+        let actual = execute(&canvas2, &third_command).unwrap();
 
         let expected = "....XXX.\n....XXX.\n....XXX.\n........\n........\nXXX.....\nXXX.....\nXXX.....\n........\n";
 
@@ -119,7 +217,7 @@ mod tests {
     #[test]
     fn test_fill_already_existing_rectangle() {
         // should replace the characters in a rectangle with new fill operator
-        let canvas = canvas::Canvas::blank_canvas(8, 9, ' ');
+        let canvas = canvas::Canvas::blank_canvas(8, 9, " ");
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x:0, y: 5},
@@ -127,18 +225,19 @@ mod tests {
                 width: 3,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FloodFill,
             position: canvas::Point {x:0, y: 5},
-            dimensions: None,
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
-        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command);
-        let actual = execute(&canvas1, &second_command);
+        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command).unwrap();
+        let actual = execute(&canvas1, &second_command).unwrap();
 
         let expected = "        \n        \n        \n        \n        \n...     \n...     \n...     \n        \n";
 
@@ -148,7 +247,7 @@ mod tests {
     #[test]
     fn test_rectangles_touching_at_corners_shouldnt_fill() {
         // when two corners are touching at the edge, the other rectangle isn't filled
-        let canvas = canvas::Canvas::blank_canvas(8, 9, ' ');
+        let canvas = canvas::Canvas::blank_canvas(8, 9, " ");
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x:0, y: 5},
@@ -156,7 +255,8 @@ mod tests {
                 width: 3,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
@@ -166,19 +266,20 @@ mod tests {
                 width: 3,
                 height: 1
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x:0, y: 5},
-            dimensions: None,
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
-        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command);
-        let canvas2 = draw_fill_rectangle::execute(&canvas1, &second_command);
-        let actual = execute(&canvas2, &third_command);
+        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command).unwrap();
+        let canvas2 = draw_fill_rectangle::execute(&canvas1, &second_command).unwrap();
+        let actual = execute(&canvas2, &third_command).unwrap();
         let expected = "        \n        \n        \n        \n        \n...     \n...     \n...     \n   XXX  \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -186,7 +287,7 @@ mod tests {
 
     #[test]
     fn test_complex_flood_fill_commands() {
-        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(21, 8, ' ');
+        let canvas: canvas::Canvas = canvas::Canvas::blank_canvas(21, 8, " ");
 
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
@@ -195,7 +296,8 @@ mod tests {
                 width: 7,
                 height: 6
             }),
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
@@ -205,7 +307,8 @@ mod tests {
                 width: 8,
                 height: 4
             }),
-            character: ' ',
+            character: String::from(" "),
+            ..Default::default()
         };
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
@@ -215,7 +318,8 @@ mod tests {
                 width: 8,
                 height: 4
             }),
-            character: 'O',
+            character: String::from("O"),
+            ..Default::default()
         };
 
         let fourth_command: commands::DrawCommand = commands::DrawCommand {
@@ -225,22 +329,23 @@ mod tests {
                 width: 5,
                 height: 3
             }),
-            character: 'X'
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let fifth_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FloodFill,
             position: canvas::Point {x: 0, y: 0},
-            dimensions: None,
-            character: '-'
+            character: String::from("-"),
+            ..Default::default()
         };
 
-        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command);
-        let canvas2 = draw_fill_rectangle::execute(&canvas1, &second_command);
-        let canvas3 = draw_outline_rectangle::execute(&canvas2, &third_command);
-        let canvas4 = draw_fill_rectangle::execute(&canvas3, &fourth_command);
+        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command).unwrap();
+        let canvas2 = draw_fill_rectangle::execute(&canvas1, &second_command).unwrap();
+        let canvas3 = draw_outline_rectangle::execute(&canvas2, &third_command).unwrap();
+        let canvas4 = draw_fill_rectangle::execute(&canvas3, &fourth_command).unwrap();
         
-        let actual = execute(&canvas4, &fifth_command);
+        let actual = execute(&canvas4, &fifth_command).unwrap();
 
         let expected = "--------------.......\n--------------.......\n--------------.......\nOOOOOOOO------.......\nO      O------.......\nO    XXXXX----.......\nOOOOOXXXXX-----------\n     XXXXX-----------\n";
 
@@ -249,8 +354,8 @@ mod tests {
 
     #[test]
     fn test_out_of_bounds() {
-        // do nothing when either x or y is out of bounds
-        let canvas = canvas::Canvas::blank_canvas(8, 9, ' ');
+        // refuse the fill when either x or y is out of bounds
+        let canvas = canvas::Canvas::blank_canvas(8, 9, " ");
         let first_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FillRectangle,
             position: canvas::Point {x:0, y: 5},
@@ -258,31 +363,305 @@ mod tests {
                 width: 3,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
 
         let second_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FloodFill,
             position: canvas::Point {x:0, y: -1},
-            dimensions: None,
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
         };
 
-        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command);
-        let actual = execute(&canvas1, &second_command);
-        let expected = "        \n        \n        \n        \n        \nXXX     \nXXX     \nXXX     \n        \n";
+        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command).unwrap();
+        let actual = execute(&canvas1, &second_command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::OutOfBounds(canvas::Point { x: 0, y: -1 }), actual);
 
         let third_command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::FloodFill,
             position: canvas::Point {x:-1, y: 0},
-            dimensions: None,
-            character: '.',
+            character: String::from("."),
+            ..Default::default()
+        };
+
+        let actual2 = execute(&canvas1, &third_command).unwrap_err();
+
+        assert_eq!(DrawError::OutOfBounds(canvas::Point { x: -1, y: 0 }), actual2);
+    }
+
+    #[test]
+    fn test_flood_fill_recolours_matching_cells() {
+        // the same character in another colour is a different region
+        let canvas = canvas::Canvas::blank_canvas(6, 2, "X");
+        let first_command: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point {x: 3, y: 0},
+            dimensions: Some(canvas::Dimensions {
+                width: 3,
+                height: 2
+            }),
+            character: String::from("X"),
+            foreground: Some(canvas::Colour::Palette(9)),
+            ..Default::default()
+        };
+
+        let second_command: commands::DrawCommand = commands::DrawCommand {
+            name: commands::CommandName::FloodFill,
+            position: canvas::Point {x: 0, y: 0},
+            character: String::from("X"),
+            foreground: Some(canvas::Colour::Rgb(0, 0, 255)),
+            ..Default::default()
+        };
+
+        let canvas1 = draw_fill_rectangle::execute(&canvas, &first_command).unwrap();
+        let actual = execute(&canvas1, &second_command).unwrap();
+
+        assert_eq!("XXXXXX\nXXXXXX\n", &actual.to_string());
+        assert_eq!(actual.layers[0].pixels[1][2].foreground, Some(canvas::Colour::Rgb(0, 0, 255)));
+        assert_eq!(actual.layers[0].pixels[1][3].foreground, Some(canvas::Colour::Palette(9)));
+    }
+
+    fn flood_fill_command(position: canvas::Point, options: commands::FloodFillOptions) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::FloodFill,
+            position,
+            character: String::from("o"),
+            flood_fill: options,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_eight_way_flood_fill_crosses_diagonals() {
+        let canvas = canvas::Canvas::from_text(".#.\n#.#\n.#.\n", 3, 3, ".");
+
+        let four_way = execute(&canvas, &flood_fill_command(canvas::Point { x: 1, y: 1 }, commands::FloodFillOptions::default())).unwrap();
+        assert_eq!(".#.\n#o#\n.#.\n", four_way.to_string());
+
+        let options = commands::FloodFillOptions {
+            connectivity: commands::Connectivity::Eight,
+            ..commands::FloodFillOptions::default()
+        };
+        let eight_way = execute(&canvas, &flood_fill_command(canvas::Point { x: 1, y: 1 }, options)).unwrap();
+        assert_eq!("o#o\n#o#\no#o\n", eight_way.to_string());
+    }
+
+    #[test]
+    fn test_boundary_fill_ignores_contents() {
+        let canvas = canvas::Canvas::from_text("#####\n#a.b#\n#.c.#\n#####\n.....\n", 5, 5, ".");
+        let options = commands::FloodFillOptions {
+            mode: commands::FillMode::Boundary,
+            boundary_character: Some(String::from("#")),
+            ..commands::FloodFillOptions::default()
+        };
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 2, y: 1 }, options)).unwrap();
+
+        assert_eq!("#####\n#ooo#\n#ooo#\n#####\n.....\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_boundary_fill_without_boundary_is_refused() {
+        let canvas = canvas::Canvas::from_text("ab\n..\n", 2, 2, ".");
+        let options = commands::FloodFillOptions {
+            mode: commands::FillMode::Boundary,
+            ..commands::FloodFillOptions::default()
+        };
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 0, y: 0 }, options)).unwrap_err();
+
+        assert_eq!(DrawError::MissingField("boundary_character"), actual);
+    }
+
+    #[test]
+    fn test_global_replace() {
+        let canvas = canvas::Canvas::from_text("a.a\n.#.\na.a\n", 3, 3, ".");
+        let options = commands::FloodFillOptions {
+            mode: commands::FillMode::GlobalReplace,
+            ..commands::FloodFillOptions::default()
+        };
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 2, y: 2 }, options)).unwrap();
+
+        assert_eq!("o.o\n.#.\no.o\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_fill_stops_at_clip() {
+        let canvas = canvas::Canvas::blank_canvas(5, 3, ".");
+        let options = commands::FloodFillOptions {
+            clip: Some(canvas::Region {
+                position: canvas::Point { x: 1, y: 1 },
+                dimensions: canvas::Dimensions { width: 3, height: 5 },
+            }),
+            ..commands::FloodFillOptions::default()
+        };
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 2, y: 2 }, options.clone())).unwrap();
+        assert_eq!(".....\n.ooo.\n.ooo.\n", actual.to_string());
+
+        // starting outside the clip changes nothing
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 0, y: 0 }, options.clone())).unwrap();
+        assert_eq!(".....\n.....\n.....\n", actual.to_string());
+
+        // strict mode refuses a clip which goes past the bottom of the canvas
+        let strict = commands::DrawCommand {
+            strict: Some(true),
+            ..flood_fill_command(canvas::Point { x: 2, y: 2 }, options)
         };
+        assert!(matches!(execute(&canvas, &strict), Err(DrawError::OffCanvas { .. })));
+    }
 
-        let actual2 = execute(&actual, &third_command);
+    #[test]
+    fn test_global_replace_respects_clip() {
+        let canvas = canvas::Canvas::from_text("a.a.a\n", 5, 1, ".");
+        let options = commands::FloodFillOptions {
+            mode: commands::FillMode::GlobalReplace,
+            clip: Some(canvas::Region {
+                position: canvas::Point { x: 0, y: 0 },
+                dimensions: canvas::Dimensions { width: 3, height: 1 },
+            }),
+            ..commands::FloodFillOptions::default()
+        };
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 0, y: 0 }, options)).unwrap();
+
+        assert_eq!("o.o.a\n", actual.to_string());
+    }
 
-        assert_eq!(expected, &actual2.to_string());
+    #[test]
+    fn test_fill_stops_where_mask_is_transparent() {
+        let mut canvas = canvas::Canvas::blank_canvas(4, 2, ".");
+        canvas.add_layer("mask").unwrap();
+        canvas.set_layer_visibility("mask", false).unwrap();
+        for (x, y) in [(0, 0), (1, 0), (1, 1), (3, 1)] {
+            canvas.layers[1].pixels[y][x] = canvas::Cell::from("m");
+        }
+        let options = commands::FloodFillOptions {
+            mask_layer: Some(String::from("mask")),
+            ..commands::FloodFillOptions::default()
+        };
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 0, y: 0 }, options.clone())).unwrap();
+        assert_eq!("oo..\n.o..\n", actual.to_string());
+
+        let unknown_mask = commands::FloodFillOptions {
+            mask_layer: Some(String::from("stencil")),
+            ..options
+        };
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 0, y: 0 }, unknown_mask)).unwrap_err();
+        assert_eq!(DrawError::MaskLayerNotFound(String::from("stencil")), actual);
+    }
+
+    #[test]
+    fn test_patterned_fill_reusing_the_starting_character() {
+        // half of the checkerboard is the character being replaced, the fill must still cover the whole area
+        let canvas = canvas::Canvas::from_text("...#\n...#\n", 4, 2, ".");
+        let mut command = flood_fill_command(canvas::Point { x: 0, y: 0 }, commands::FloodFillOptions::default());
+        command.pattern = Some(commands::Pattern::Checkerboard { other_character: String::from(".") });
+
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("o.o#\n.o.#\n", actual.to_string());
+    }
+
+    /// Canvas with walls every other row, each with a gap at alternating ends,
+    /// so the fillable area is one long path which doubles back on itself
+    fn serpentine_canvas(width: i32, height: i32) -> canvas::Canvas {
+        let mut canvas = canvas::Canvas::blank_canvas(width, height, " ");
+        for row in (1..height).step_by(2) {
+            let gap = if row % 4 == 1 { width - 1 } else { 0 };
+            for column in 0..width {
+                if column != gap {
+                    canvas.layers[0].pixels[row as usize][column as usize] = canvas::Cell::from("#");
+                }
+            }
+        }
+        canvas
+    }
+
+    /// Canvas scattered with walls from a fixed pseudo-random sequence
+    fn scattered_canvas(width: i32, height: i32) -> canvas::Canvas {
+        let mut canvas = canvas::Canvas::blank_canvas(width, height, " ");
+        let mut seed: u32 = 12345;
+        for pixel in canvas.layers[0].pixels.iter_mut().flatten() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            if (seed >> 16) % 5 < 2 {
+                *pixel = canvas::Cell::from("#");
+            }
+        }
+        canvas
+    }
+
+    /// Straightforward breadth first flood fill to check the scanline fill against
+    fn reference_flood_fill(canvas: &canvas::Canvas, position: &canvas::Point, connectivity: commands::Connectivity) -> canvas::Canvas {
+        let mut new_canvas = canvas.clone();
+        let pixels = &mut new_canvas.layers[0].pixels;
+        let current_cell = pixels[position.y as usize][position.x as usize].clone();
+        let fill_cell = canvas::Cell::from("o");
+        let neighbours: Vec<(i32, i32)> = match connectivity {
+            commands::Connectivity::Four => vec![(0, 1), (0, -1), (1, 0), (-1, 0)],
+            commands::Connectivity::Eight => vec![(0, 1), (0, -1), (1, 0), (-1, 0), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+        };
+
+        let mut queue = VecDeque::new();
+        queue.push_back((position.x, position.y));
+        while let Some((x, y)) = queue.pop_front() {
+            if !utils::position_is_within(&canvas.dimensions, &canvas::Point { x, y }) {
+                continue;
+            }
+            let pixel = &mut pixels[y as usize][x as usize];
+            if *pixel != current_cell || *pixel == fill_cell {
+                continue;
+            }
+            *pixel = fill_cell.clone();
+            for (dx, dy) in &neighbours {
+                queue.push_back((x + dx, y + dy));
+            }
+        }
+
+        new_canvas
+    }
+
+    #[test]
+    fn test_large_blank_canvas_fill() {
+        // a recursive fill overflows the stack long before this
+        let canvas = canvas::Canvas::blank_canvas(2000, 2000, " ");
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 1000, y: 1000 }, commands::FloodFillOptions::default())).unwrap();
+
+        assert!(actual.layers[0].pixels.iter().flatten().all(|cell| cell.character == "o"));
+    }
+
+    #[test]
+    fn test_large_serpentine_fill() {
+        let canvas = serpentine_canvas(2000, 2000);
+
+        let actual = execute(&canvas, &flood_fill_command(canvas::Point { x: 0, y: 0 }, commands::FloodFillOptions::default())).unwrap();
+
+        let filled = actual.layers[0].pixels.iter().flatten().filter(|cell| cell.character == "o").count();
+        let open = canvas.layers[0].pixels.iter().flatten().filter(|cell| cell.character == " ").count();
+        assert_eq!(open, filled);
+    }
+
+    #[test]
+    fn test_scanline_matches_reference_fill() {
+        let canvas = scattered_canvas(120, 80);
+
+        for connectivity in [commands::Connectivity::Four, commands::Connectivity::Eight] {
+            for position in [canvas::Point { x: 0, y: 0 }, canvas::Point { x: 60, y: 40 }, canvas::Point { x: 119, y: 79 }] {
+                let options = commands::FloodFillOptions {
+                    connectivity,
+                    ..commands::FloodFillOptions::default()
+                };
+
+                let actual = execute(&canvas, &flood_fill_command(position.clone(), options)).unwrap();
+                let expected = reference_flood_fill(&canvas, &position, connectivity);
+
+                assert_eq!(expected.to_string(), actual.to_string());
+            }
+        }
     }
 }
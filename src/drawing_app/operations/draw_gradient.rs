@@ -0,0 +1,204 @@
+use super::super::canvas;
+use super::super::commands;
+use super::super::draw_error::DrawError;
+use super::utils;
+
+/// Executes a GradientFill command and returns a new canvas with the changes
+/// Each pixel of the rectangle is given a character from the ramp according to how far along the
+/// gradient it lies. The gradient is worked out over the whole rectangle, so parts falling off the canvas
+/// don't squash it. Fails without dimensions, a gradient or a ramp, or with an unknown layer
+pub fn execute(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let dimensions = utils::command_dimensions(command)?;
+    let gradient = command.gradient.as_ref().ok_or(DrawError::MissingField("gradient"))?;
+    if gradient.ramp.is_empty() {
+        return Err(DrawError::EmptyGradientRamp);
+    }
+    utils::command_layer_index(&new_canvas, command)?;
+    utils::check_fits_canvas(&new_canvas, command, &command.position, dimensions)?;
+    if !utils::rectangle_size_is_none_zero(dimensions) {
+        return Ok(new_canvas);
+    }
+
+    let indices = ramp_indices(dimensions, gradient);
+    let canvas_dimensions = new_canvas.dimensions.clone();
+
+    if let Some(layer) = new_canvas.layer_mut(&command.layer) {
+        let pixels: Vec<Vec<canvas::Cell>> = indices
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|index| canvas::Cell {
+                        character: gradient.ramp[*index].clone(),
+                        ..command.cell()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        utils::place_pixels(layer, &canvas_dimensions, &command.position, &pixels, |_| false);
+    }
+
+    Ok(new_canvas)
+}
+
+/// How far along the gradient a pixel of the rectangle lies, from 0 at the start to 1 at the end
+fn gradient_position(
+    dimensions: &canvas::Dimensions,
+    direction: commands::GradientDirection,
+    row_index: i32,
+    column_index: i32,
+) -> f64 {
+    let fraction = |index: i32, length: i32| {
+        if length > 1 { index as f64 / (length - 1) as f64 } else { 0.0 }
+    };
+
+    match direction {
+        commands::GradientDirection::Horizontal => fraction(column_index, dimensions.width),
+        commands::GradientDirection::Vertical => fraction(row_index, dimensions.height),
+        commands::GradientDirection::Diagonal => {
+            (fraction(column_index, dimensions.width) + fraction(row_index, dimensions.height)) / 2.0
+        },
+        commands::GradientDirection::Radial => {
+            let centre_x = (dimensions.width - 1) as f64 / 2.0;
+            let centre_y = (dimensions.height - 1) as f64 / 2.0;
+            let furthest = centre_x.hypot(centre_y);
+            if furthest == 0.0 {
+                return 0.0;
+            }
+            (column_index as f64 - centre_x).hypot(row_index as f64 - centre_y) / furthest
+        },
+    }
+}
+
+/// Picks a ramp character for every pixel of the rectangle, as an index into the ramp
+/// Without dithering each pixel takes the nearest step. With dithering the rounding error is passed on to
+/// the pixels right and below (Floyd–Steinberg), so in-between shades come out as a mix of the steps either side
+fn ramp_indices(dimensions: &canvas::Dimensions, gradient: &commands::Gradient) -> Vec<Vec<usize>> {
+    let (width, height) = (dimensions.width as usize, dimensions.height as usize);
+    let last_step = (gradient.ramp.len() - 1) as f64;
+    let mut errors = vec![vec![0.0; width]; height];
+    let mut indices = vec![vec![0; width]; height];
+
+    for row_index in 0..height {
+        for column_index in 0..width {
+            let target = gradient_position(dimensions, gradient.direction, row_index as i32, column_index as i32) * last_step;
+            let value = if gradient.dither { target + errors[row_index][column_index] } else { target };
+            let step = value.round().clamp(0.0, last_step);
+            indices[row_index][column_index] = step as usize;
+
+            if gradient.dither {
+                let error = value - step;
+                let mut spread = |row: usize, column: Option<usize>, weight: f64| {
+                    if let Some(column) = column.filter(|column| *column < width) {
+                        if row < height {
+                            errors[row][column] += error * weight;
+                        }
+                    }
+                };
+                spread(row_index, Some(column_index + 1), 7.0 / 16.0);
+                spread(row_index + 1, column_index.checked_sub(1), 3.0 / 16.0);
+                spread(row_index + 1, Some(column_index), 5.0 / 16.0);
+                spread(row_index + 1, Some(column_index + 1), 1.0 / 16.0);
+            }
+        }
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{commands, canvas};
+
+    fn gradient_command(
+        position: canvas::Point,
+        dimensions: canvas::Dimensions,
+        direction: commands::GradientDirection,
+        ramp: &str,
+        dither: bool,
+    ) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::GradientFill,
+            position,
+            dimensions: Some(dimensions),
+            gradient: Some(commands::Gradient {
+                direction,
+                ramp: canvas::graphemes(ramp),
+                dither,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_linear_gradients() {
+        let canvas = canvas::Canvas::blank_canvas(5, 3, " ");
+        let dimensions = canvas::Dimensions { width: 5, height: 3 };
+        let origin = canvas::Point { x: 0, y: 0 };
+
+        let horizontal = gradient_command(origin.clone(), dimensions.clone(), commands::GradientDirection::Horizontal, "abcde", false);
+        assert_eq!("abcde\nabcde\nabcde\n", execute(&canvas, &horizontal).unwrap().to_string());
+
+        let vertical = gradient_command(origin.clone(), dimensions.clone(), commands::GradientDirection::Vertical, "abc", false);
+        assert_eq!("aaaaa\nbbbbb\nccccc\n", execute(&canvas, &vertical).unwrap().to_string());
+
+        let diagonal = gradient_command(origin, dimensions, commands::GradientDirection::Diagonal, "abcde", false);
+        assert_eq!("abbcc\nbccdd\ncddee\n", execute(&canvas, &diagonal).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_radial_gradient() {
+        let canvas = canvas::Canvas::blank_canvas(5, 5, " ");
+        let command = gradient_command(
+            canvas::Point { x: 0, y: 0 },
+            canvas::Dimensions { width: 5, height: 5 },
+            commands::GradientDirection::Radial,
+            "o.",
+            false,
+        );
+
+        assert_eq!(".....\n..o..\n.ooo.\n..o..\n.....\n", execute(&canvas, &command).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_dithering_mixes_neighbouring_steps() {
+        // the middle row of a vertical gradient over a two step ramp sits exactly halfway between the steps
+        let canvas = canvas::Canvas::blank_canvas(6, 3, " ");
+        let mut command = gradient_command(
+            canvas::Point { x: 0, y: 0 },
+            canvas::Dimensions { width: 6, height: 3 },
+            commands::GradientDirection::Vertical,
+            ".#",
+            false,
+        );
+
+        let rounded = execute(&canvas, &command).unwrap().to_string();
+        assert_eq!("######", rounded.lines().nth(1).unwrap());
+
+        command.gradient.as_mut().unwrap().dither = true;
+        let dithered = execute(&canvas, &command).unwrap().to_string();
+        let middle_row = dithered.lines().nth(1).unwrap();
+        assert_eq!("......", dithered.lines().next().unwrap());
+        assert!(middle_row.contains('#') && middle_row.contains('.'));
+    }
+
+    #[test]
+    fn test_gradient_is_clipped_not_squashed() {
+        let canvas = canvas::Canvas::blank_canvas(3, 1, " ");
+        let command = gradient_command(
+            canvas::Point { x: -2, y: 0 },
+            canvas::Dimensions { width: 5, height: 1 },
+            commands::GradientDirection::Horizontal,
+            "abcde",
+            false,
+        );
+
+        assert_eq!("cde\n", execute(&canvas, &command).unwrap().to_string());
+    }
+}
@@ -0,0 +1,218 @@
+use super::super::canvas;
+use super::super::commands;
+use super::super::draw_error::DrawError;
+use super::utils;
+
+/// Executes a Line command and returns a new canvas with the changes
+/// The line runs from the command's position to its end, both included. Each point of the line is painted
+/// with a square brush `brush.width` cells across, centred on the point. Fails without an end, with a brush width out of range, or with an unknown layer
+pub fn execute(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let end = command.end.as_ref().ok_or(DrawError::MissingField("end"))?;
+    utils::check_character(command)?;
+    utils::check_brush(command)?;
+    utils::command_layer_index(&new_canvas, command)?;
+    if command.is_strict() {
+        if let Some(point) = [&command.position, end].iter().find(|point| !utils::position_is_on_canvas(&new_canvas, point)) {
+            return Err(DrawError::OutOfBounds((*point).clone()));
+        }
+    }
+
+    let canvas_dimensions = new_canvas.dimensions.clone();
+    let reach = (command.brush.width - 1) / 2;
+
+    if let Some(layer) = new_canvas.layer_mut(&command.layer) {
+        for point in line_points(&command.position, end, &canvas_dimensions, reach + 1) {
+            for row_index in point.y - reach..point.y - reach + command.brush.width {
+                for column_index in point.x - reach..point.x - reach + command.brush.width {
+                    let position = canvas::Point { x: column_index, y: row_index };
+                    if utils::position_is_within(&canvas_dimensions, &position) {
+                        layer.pixels[row_index as usize][column_index as usize] = command.cell();
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(new_canvas)
+}
+
+/// The points of a line between two points, both included, with no gaps or corners doubled up
+/// Each step along the longer axis moves along the shorter one by its share of the line, rounding halves up,
+/// which picks the same points as Bresenham's algorithm. Only the steps which come within `margin` cells of
+/// the canvas are visited, so a line reaching far off the canvas costs no more than one across it
+fn line_points(
+    start: &canvas::Point,
+    end: &canvas::Point,
+    dimensions: &canvas::Dimensions,
+    margin: i32,
+) -> Vec<canvas::Point> {
+    // wide enough that no difference or product of coordinates can overflow
+    let (start_x, start_y, end_x, end_y) = (start.x as i128, start.y as i128, end.x as i128, end.y as i128);
+    let (length_x, length_y) = ((end_x - start_x).abs(), (end_y - start_y).abs());
+    let (step_x, step_y) = ((end_x - start_x).signum(), (end_y - start_y).signum());
+    let (margin, width, height) = (margin as i128, dimensions.width as i128, dimensions.height as i128);
+
+    let x_is_major = length_x >= length_y;
+    let (major_start, major_length, major_step, major_size) = if x_is_major {
+        (start_x, length_x, step_x, width)
+    } else {
+        (start_y, length_y, step_y, height)
+    };
+    let minor_length = if x_is_major { length_y } else { length_x };
+
+    // the steps whose coordinate along the longer axis is within the margin of the canvas
+    let (low, high) = (-margin, major_size - 1 + margin);
+    let (first, last) = match major_step {
+        1 => (low - major_start, high - major_start),
+        -1 => (major_start - high, major_start - low),
+        _ => (0, 0),
+    };
+
+    (first.max(0)..=last.min(major_length))
+        .filter_map(|step| {
+            let minor = if major_length == 0 { 0 } else { (2 * step * minor_length + major_length) / (2 * major_length) };
+            let (x, y) = if x_is_major {
+                (start_x + step_x * step, start_y + step_y * minor)
+            } else {
+                (start_x + step_x * minor, start_y + step_y * step)
+            };
+            let near_canvas = x >= -margin && x < width + margin && y >= -margin && y < height + margin;
+            if near_canvas {
+                Some(canvas::Point { x: x as i32, y: y as i32 })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{commands, canvas};
+
+    fn line_command(start: canvas::Point, end: canvas::Point, width: i32) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::Line,
+            position: start,
+            character: String::from("#"),
+            end: Some(end),
+            brush: commands::Brush {
+                width,
+                alignment: commands::BrushAlignment::default(),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_thin_lines() {
+        let canvas = canvas::Canvas::blank_canvas(6, 3, ".");
+
+        let horizontal = line_command(canvas::Point { x: 1, y: 1 }, canvas::Point { x: 4, y: 1 }, 1);
+        assert_eq!("......\n.####.\n......\n", execute(&canvas, &horizontal).unwrap().to_string());
+
+        // lines can be drawn in either direction
+        let diagonal = line_command(canvas::Point { x: 5, y: 2 }, canvas::Point { x: 0, y: 0 }, 1);
+        assert_eq!("##....\n..##..\n....##\n", execute(&canvas, &diagonal).unwrap().to_string());
+
+        let point = line_command(canvas::Point { x: 2, y: 2 }, canvas::Point { x: 2, y: 2 }, 1);
+        assert_eq!("......\n......\n..#...\n", execute(&canvas, &point).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_thick_lines_are_clipped_to_the_canvas() {
+        let canvas = canvas::Canvas::blank_canvas(6, 4, ".");
+
+        let thick = line_command(canvas::Point { x: 1, y: 0 }, canvas::Point { x: 4, y: 0 }, 3);
+        assert_eq!("######\n######\n......\n......\n", execute(&canvas, &thick).unwrap().to_string());
+
+        // even widths put the extra cell below and to the right
+        let even = line_command(canvas::Point { x: 2, y: 1 }, canvas::Point { x: 2, y: 2 }, 2);
+        assert_eq!("......\n..##..\n..##..\n..##..\n", execute(&canvas, &even).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_strict_lines_must_start_and_end_on_the_canvas() {
+        let canvas = canvas::Canvas::blank_canvas(6, 3, ".");
+
+        let off_canvas = commands::DrawCommand {
+            strict: Some(true),
+            ..line_command(canvas::Point { x: 1, y: 1 }, canvas::Point { x: 7, y: 1 }, 1)
+        };
+        assert_eq!(Err(DrawError::OutOfBounds(canvas::Point { x: 7, y: 1 })), execute(&canvas, &off_canvas).map(|_| ()));
+
+        // thick strokes may still spill over the edge, as only the points of the line are checked
+        let thick = commands::DrawCommand {
+            strict: Some(true),
+            ..line_command(canvas::Point { x: 0, y: 0 }, canvas::Point { x: 5, y: 0 }, 3)
+        };
+        assert_eq!("######\n######\n......\n", execute(&canvas, &thick).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_lines_reaching_far_off_the_canvas() {
+        let canvas = canvas::Canvas::blank_canvas(6, 3, ".");
+
+        // only the part of the line near the canvas is stepped through
+        let far = line_command(canvas::Point { x: 0, y: 1 }, canvas::Point { x: 2_000_000_000, y: 1 }, 3);
+        assert_eq!("######\n######\n######\n", execute(&canvas, &far).unwrap().to_string());
+
+        let extreme = line_command(canvas::Point { x: i32::MIN, y: i32::MIN }, canvas::Point { x: i32::MAX, y: i32::MAX }, 1);
+        assert_eq!("#.....\n.#....\n..#...\n", execute(&canvas, &extreme).unwrap().to_string());
+
+        // a shallow line which only crosses the canvas partway along keeps the same slope
+        let shallow = line_command(canvas::Point { x: -6, y: -1 }, canvas::Point { x: 11, y: 2 }, 1);
+        assert_eq!("###...\n...###\n......\n", execute(&canvas, &shallow).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_line_points_match_bresenham() {
+        // Bresenham's algorithm, stepping through every point
+        fn bresenham(start: &canvas::Point, end: &canvas::Point) -> Vec<canvas::Point> {
+            let (delta_x, delta_y) = ((end.x - start.x).abs(), -(end.y - start.y).abs());
+            let (step_x, step_y) = ((end.x - start.x).signum(), (end.y - start.y).signum());
+            let mut error = delta_x + delta_y;
+            let (mut x, mut y) = (start.x, start.y);
+            let mut points = vec![];
+            loop {
+                points.push(canvas::Point { x, y });
+                if x == end.x && y == end.y {
+                    return points;
+                }
+                let doubled_error = 2 * error;
+                if doubled_error >= delta_y {
+                    error += delta_y;
+                    x += step_x;
+                }
+                if doubled_error <= delta_x {
+                    error += delta_x;
+                    y += step_y;
+                }
+            }
+        }
+
+        let dimensions = canvas::Dimensions { width: 1, height: 1 };
+        let start = canvas::Point { x: 0, y: 0 };
+        for x in -12..=12 {
+            for y in -12..=12 {
+                let end = canvas::Point { x, y };
+                assert_eq!(bresenham(&start, &end), line_points(&start, &end, &dimensions, 20));
+            }
+        }
+    }
+
+    #[test]
+    fn test_brush_width_must_be_in_range() {
+        let canvas = canvas::Canvas::blank_canvas(6, 3, ".");
+        for width in [0, commands::MAX_BRUSH_WIDTH + 1, i32::MAX] {
+            let command = line_command(canvas::Point { x: 1, y: 1 }, canvas::Point { x: 4, y: 1 }, width);
+            assert_eq!(Err(DrawError::InvalidBrushWidth(width)), execute(&canvas, &command).map(|_| ()));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use super::super::canvas;
+use super::super::commands;
+use super::super::draw_error::DrawError;
+use super::utils;
+
+/// Executes an OutlineEllipse command and returns a new canvas with the changes
+/// The ellipse fits the rectangle given by the command's position and dimensions. The stroke is the part of the
+/// ellipse grown by `brush.outset()` cells which lies outside the same ellipse shrunk by `brush.width` cells.
+/// Fails without dimensions, with negative ones, with a brush width out of range, or with an unknown layer
+pub fn execute(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let dimensions = utils::command_dimensions(command)?;
+    utils::check_character(command)?;
+    utils::check_brush(command)?;
+    utils::command_layer_index(&new_canvas, command)?;
+    utils::check_outline_fits_canvas(&new_canvas, command, &command.position, dimensions)?;
+    if !utils::rectangle_size_is_none_zero(dimensions) {
+        return Ok(new_canvas);
+    }
+
+    let outer = Ellipse::inset(&command.position, dimensions, -command.brush.outset());
+    let inner = Ellipse::inset(&command.position, dimensions, command.brush.width - command.brush.outset());
+
+    if let Some(layer) = new_canvas.layer_mut(&command.layer) {
+        layer.pixels
+            .iter_mut()
+            .enumerate()
+            .for_each(|(row_index, row)| {
+                row
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(column_index, pixel)| {
+                        let (x, y) = (column_index as f64 + 0.5, row_index as f64 + 0.5);
+                        let in_outer = outer.as_ref().is_some_and(|ellipse| ellipse.contains(x, y));
+                        let in_inner = inner.as_ref().is_some_and(|ellipse| ellipse.contains(x, y));
+                        if in_outer && !in_inner {
+                            *pixel = command.cell();
+                        }
+                    })
+            });
+    }
+
+    Ok(new_canvas)
+}
+
+/// An ellipse with its axes along the rows and columns of the canvas
+struct Ellipse {
+    centre_x: f64,
+    centre_y: f64,
+    radius_x: f64,
+    radius_y: f64,
+}
+
+impl Ellipse {
+    /// The ellipse fitting a rectangle moved in by `inset` cells on every side, if anything is left of it
+    fn inset(position: &canvas::Point, dimensions: &canvas::Dimensions, inset: i32) -> Option<Ellipse> {
+        let (width, height) = (dimensions.width - 2 * inset, dimensions.height - 2 * inset);
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        Some(Ellipse {
+            centre_x: position.x as f64 + dimensions.width as f64 / 2.0,
+            centre_y: position.y as f64 + dimensions.height as f64 / 2.0,
+            radius_x: width as f64 / 2.0,
+            radius_y: height as f64 / 2.0,
+        })
+    }
+
+    /// Whether a point, in cell units, lies inside the ellipse or on its edge
+    fn contains(&self, x: f64, y: f64) -> bool {
+        let (along_x, along_y) = ((x - self.centre_x) / self.radius_x, (y - self.centre_y) / self.radius_y);
+        along_x * along_x + along_y * along_y <= 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{commands, canvas};
+
+    fn ellipse_command(
+        position: canvas::Point,
+        dimensions: canvas::Dimensions,
+        width: i32,
+        alignment: commands::BrushAlignment,
+    ) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::OutlineEllipse,
+            position,
+            dimensions: Some(dimensions),
+            character: String::from("o"),
+            brush: commands::Brush { width, alignment },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_thin_ellipse() {
+        let canvas = canvas::Canvas::blank_canvas(5, 5, ".");
+        let command = ellipse_command(
+            canvas::Point { x: 0, y: 0 },
+            canvas::Dimensions { width: 5, height: 5 },
+            1,
+            commands::BrushAlignment::Inward,
+        );
+
+        assert_eq!(".ooo.\no...o\no...o\no...o\n.ooo.\n", execute(&canvas, &command).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_brush_alignment() {
+        let canvas = canvas::Canvas::blank_canvas(9, 9, ".");
+        let dimensions = canvas::Dimensions { width: 5, height: 5 };
+
+        let inward = ellipse_command(canvas::Point { x: 2, y: 2 }, dimensions.clone(), 2, commands::BrushAlignment::Inward);
+        let outward = ellipse_command(canvas::Point { x: 2, y: 2 }, dimensions, 2, commands::BrushAlignment::Outward);
+        let inward = execute(&canvas, &inward).unwrap().to_string();
+        let outward = execute(&canvas, &outward).unwrap().to_string();
+
+        // an inward stroke stays inside the rectangle, an outward one leaves the thin ellipse's inside alone
+        assert_eq!(".........", inward.lines().nth(1).unwrap());
+        assert_eq!("..oo.oo..", inward.lines().nth(4).unwrap());
+        assert_eq!(".........", outward.lines().next().unwrap());
+        assert_eq!("...ooo...", outward.lines().nth(1).unwrap());
+        assert_eq!(".oo...oo.", outward.lines().nth(4).unwrap());
+    }
+
+    #[test]
+    fn test_ellipse_is_clipped_to_the_canvas() {
+        let canvas = canvas::Canvas::blank_canvas(5, 3, ".");
+        let command = ellipse_command(
+            canvas::Point { x: 0, y: -1 },
+            canvas::Dimensions { width: 5, height: 5 },
+            1,
+            commands::BrushAlignment::Inward,
+        );
+
+        assert_eq!("o...o\no...o\no...o\n", execute(&canvas, &command).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_strict_ellipses_count_the_stroke_outside_the_rectangle() {
+        let canvas = canvas::Canvas::blank_canvas(7, 7, ".");
+        let mut command = ellipse_command(
+            canvas::Point { x: 1, y: 1 },
+            canvas::Dimensions { width: 5, height: 5 },
+            2,
+            commands::BrushAlignment::Outward,
+        );
+        command.strict = Some(true);
+        assert!(execute(&canvas, &command).is_ok());
+
+        command.position = canvas::Point { x: 0, y: 1 };
+        assert!(matches!(execute(&canvas, &command), Err(DrawError::OffCanvas { .. })));
+
+        command.brush.alignment = commands::BrushAlignment::Inward;
+        assert!(execute(&canvas, &command).is_ok());
+    }
+
+    #[test]
+    fn test_brush_width_must_be_in_range() {
+        let canvas = canvas::Canvas::blank_canvas(5, 5, ".");
+        for width in [0, commands::MAX_BRUSH_WIDTH + 1] {
+            let command = ellipse_command(
+                canvas::Point { x: 0, y: 0 },
+                canvas::Dimensions { width: 5, height: 5 },
+                width,
+                commands::BrushAlignment::Outward,
+            );
+            assert_eq!(Err(DrawError::InvalidBrushWidth(width)), execute(&canvas, &command).map(|_| ()));
+        }
+    }
+}
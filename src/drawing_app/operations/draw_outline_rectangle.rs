@@ -1,53 +1,74 @@
 use super::super::canvas;
 use super::super::commands;
+use super::super::draw_error::DrawError;
 use super::utils;
 
 /// Executes an OutlineRectangle command and returns a new canvas with the changes
+/// Fails without dimensions, with negative ones, with more than one character, with a brush width out of range
+/// or with an unknown layer
 pub fn execute(
     previous_state_canvas: &canvas::Canvas,
     command: &commands::DrawCommand,
-) -> canvas::Canvas {
-    match command.clone().dimensions {
-        Some(dimensions) => {
-            if utils::rectangle_size_is_none_zero(&dimensions) {
-                return draw_outline(previous_state_canvas, &dimensions, command);
-            }
-            previous_state_canvas.clone()
-        },
-        None => {
-            previous_state_canvas.clone()
-        }
+) -> Result<canvas::Canvas, DrawError> {
+    let dimensions = utils::command_dimensions(command)?;
+    utils::check_character(command)?;
+    utils::check_brush(command)?;
+    utils::command_layer_index(previous_state_canvas, command)?;
+    utils::check_outline_fits_canvas(previous_state_canvas, command, &command.position, dimensions)?;
+
+    if utils::rectangle_size_is_none_zero(dimensions) {
+        return Ok(draw_outline(previous_state_canvas, dimensions, command));
     }
+    Ok(previous_state_canvas.clone())
 }
 
-/// Searches a canvas for the pixels to be updated, returns a new canvas with the changes
+/// Searches the command's layer for the pixels to be updated, returns a new canvas with the changes
+/// The stroke is made of `brush.width` nested rings, starting `brush.outset()` rings outside the rectangle
+/// and stepping inwards. Rings falling partly off the canvas are clipped like any other edge
 fn draw_outline(
     previous_state_canvas: &canvas::Canvas,
     dimensions: &canvas::Dimensions,
     command: &commands::DrawCommand,
 ) -> canvas::Canvas {
     let mut new_canvas = previous_state_canvas.clone();
+    let rings: Vec<(canvas::Point, canvas::Dimensions)> = (0..command.brush.width)
+        .map(|ring| ring - command.brush.outset())
+        .map(|inset| (
+            canvas::Point {
+                x: command.position.x + inset,
+                y: command.position.y + inset,
+            },
+            canvas::Dimensions {
+                width: dimensions.width - 2 * inset,
+                height: dimensions.height - 2 * inset,
+            },
+        ))
+        .take_while(|(_, ring_dimensions)| utils::rectangle_size_is_none_zero(ring_dimensions))
+        .collect();
+
+    if let Some(layer) = new_canvas.layer_mut(&command.layer) {
+        layer.pixels
+            .iter_mut()
+            .enumerate()
+            .for_each(|(row_index, row)| {
+                row
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(column_index, pixel)| {
+                        let on_stroke = rings.iter().any(|(ring_position, ring_dimensions)| utils::is_edge(
+                            ring_dimensions,
+                            ring_position,
+                            row_index as i32,
+                            column_index as i32
+                        ));
+                        if on_stroke {
+                            *pixel = command.cell();
+                        }
+                    })
+            });
+    }
 
-    previous_state_canvas.pixels
-        .iter()
-        .enumerate()
-        .for_each(|(row_index, row)| {
-            row
-                .iter()
-                .enumerate()
-                .for_each(|(column_index, _)| {
-                    if utils::is_edge(
-                        dimensions,
-                        &command.position, 
-                        row_index as i32, 
-                        column_index as i32
-                    ) {
-                        new_canvas.pixels[row_index][column_index] = command.character;
-                    }
-                })
-        });
-
-        new_canvas
+    new_canvas
 }
 
 #[cfg(test)]
@@ -58,7 +79,7 @@ mod tests {
     #[test]
     fn test_simple_outline() {
         // simple ouline rectangle draw operation
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -66,24 +87,25 @@ mod tests {
                 width: 3,
                 height: 4
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    XXX   \n    X X   \n    X X   \n    XXX   \n          \n";
 
         assert_eq!(expected, &actual.to_string());
-        assert_eq!(actual.pixels[3][3], ' ');
-        assert_eq!(actual.pixels[3][4], 'X');
+        assert_eq!(actual.layers[0].pixels[3][3].character, " ");
+        assert_eq!(actual.layers[0].pixels[3][4].character, "X");
         // second row of shape should be empty in the middle and only include edges
-        assert_eq!(actual.pixels[4][4], 'X');
+        assert_eq!(actual.layers[0].pixels[4][4].character, "X");
         // the middle is left empty
-        assert_eq!(actual.pixels[4][5], ' ');
+        assert_eq!(actual.layers[0].pixels[4][5].character, " ");
     }
 
     #[test]
     fn test_width_out_of_bounds() {
         // we don't draw an edge in this case where it runs out of bounds, but we could
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -91,9 +113,10 @@ mod tests {
                 width: 8,
                 height: 3
             }),
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    XXXXXX\n    X     \n    XXXXXX\n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -102,7 +125,7 @@ mod tests {
     #[test]
     fn test_height_out_of_bounds() {
         // we don't draw an edge in this case, but we could
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -110,9 +133,10 @@ mod tests {
                 width: 3,
                 height: 6
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    !!!   \n    ! !   \n    ! !   \n    ! !   \n    ! !   \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -121,7 +145,7 @@ mod tests {
     #[test]
     fn test_height_and_width_out_of_bounds() {
         // draw operation should draw up to the end of the canvas and handle error
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -129,9 +153,10 @@ mod tests {
                 width: 8,
                 height: 6
             }),
-            character: '4',
+            character: String::from("4"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n    444444\n    4     \n    4     \n    4     \n    4     \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -139,8 +164,8 @@ mod tests {
 
     #[test]
     fn test_negative_height() {
-        // a rectangle with negative height has no effect (Nb: one possiblity is to draw rectangle in the opposite direction, but since this feature is not specificed I will leave for now)
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        // a rectangle with negative height is refused (Nb: one possiblity is to draw rectangle in the opposite direction, but since this feature is not specificed I will leave for now)
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -148,18 +173,18 @@ mod tests {
                 width: 8,
                 height: -5
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
-        let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
+        let actual = execute(&canvas, &command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::InvalidDimensions(canvas::Dimensions { width: 8, height: -5 }), actual);
     }
 
     #[test]
     fn test_negative_width() {
-        // a rectangle with negative width has no effect
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        // a rectangle with negative width is refused
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -167,18 +192,18 @@ mod tests {
                 width: -3,
                 height: 5
             }),
-            character: '-',
+            character: String::from("-"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
-        let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
+        let actual = execute(&canvas, &command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::InvalidDimensions(canvas::Dimensions { width: -3, height: 5 }), actual);
     }
 
     #[test]
     fn test_0_height() {
         // a rectangle with 0 height has no effect
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -186,9 +211,10 @@ mod tests {
                 width: 8,
                 height: 0
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -197,7 +223,7 @@ mod tests {
     #[test]
     fn test_0_width() {
         // a rectangle with 0 width has no effect
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -205,9 +231,10 @@ mod tests {
                 width: 0,
                 height: 5
             }),
-            character: '-',
+            character: String::from("-"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -216,7 +243,7 @@ mod tests {
     #[test]
     fn test_negative_x_start_position() {
         // a command with negative x start position draws if the edge is a valid position on canvas
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: -3, y: 3},
@@ -224,9 +251,10 @@ mod tests {
                 width: 5,
                 height: 5
             }),
-            character: '-',
+            character: String::from("-"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "          \n          \n          \n--        \n -        \n -        \n -        \n--        \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -235,7 +263,7 @@ mod tests {
     #[test]
     fn test_negative_y_start_position() {
         // a command with negative y start position draws if the edge is a valid position on canvas
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 3, y: -3},
@@ -243,9 +271,10 @@ mod tests {
                 width: 5,
                 height: 5
             }),
-            character: '*',
+            character: String::from("*"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
+        let actual = execute(&canvas, &command).unwrap();
         let expected = "   *   *  \n   *****  \n          \n          \n          \n          \n          \n          \n";
 
         assert_eq!(expected, &actual.to_string());
@@ -253,24 +282,23 @@ mod tests {
 
     #[test]
     fn test_dimensions_empty() {
-        // take no action if no dimension is specified (see similar note in FillRectangle)
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        // refuse the command if no dimension is specified (see similar note in FillRectangle)
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
-            dimensions: None,
-            character: 'X',
+            character: String::from("X"),
+            ..Default::default()
         };
-        let actual = execute(&canvas, &command);
-        let expected = "          \n          \n          \n          \n          \n          \n          \n          \n";
+        let actual = execute(&canvas, &command).unwrap_err();
 
-        assert_eq!(expected, &actual.to_string());
+        assert_eq!(DrawError::MissingField("dimensions"), actual);
     }
 
     #[test]
     fn test_repeated_operation_idempotence() {
         // draw operation should draw up to the end of the canvas and handle error
-        let canvas = canvas::Canvas::blank_canvas(10, 8, ' ');
+        let canvas = canvas::Canvas::blank_canvas(10, 8, " ");
         let command: commands::DrawCommand = commands::DrawCommand {
             name: commands::CommandName::OutlineRectangle,
             position: canvas::Point {x: 4, y: 3},
@@ -278,14 +306,122 @@ mod tests {
                 width: 8,
                 height: 6
             }),
-            character: '!',
+            character: String::from("!"),
+            ..Default::default()
         };
 
-        let first_canvas = execute(&canvas, &command);
-        let actual = execute(&first_canvas, &command);
+        let first_canvas = execute(&canvas, &command).unwrap();
+        let actual = execute(&first_canvas, &command).unwrap();
 
         let expected = "          \n          \n          \n    !!!!!!\n    !     \n    !     \n    !     \n    !     \n";
 
         assert_eq!(expected, &actual.to_string());
     }
+
+    fn brush_command(
+        position: canvas::Point,
+        dimensions: canvas::Dimensions,
+        width: i32,
+        alignment: commands::BrushAlignment,
+    ) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::OutlineRectangle,
+            position,
+            dimensions: Some(dimensions),
+            character: String::from("#"),
+            brush: commands::Brush { width, alignment },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_thick_outlines() {
+        let canvas = canvas::Canvas::blank_canvas(10, 8, ".");
+        let thick_square = "..........\n..######..\n..######..\n..##..##..\n..##..##..\n..######..\n..######..\n..........\n";
+
+        // an inward stroke grows into the rectangle, an outward one grows out of it
+        let inward = brush_command(
+            canvas::Point {x: 2, y: 1},
+            canvas::Dimensions {width: 6, height: 6},
+            2,
+            commands::BrushAlignment::Inward,
+        );
+        assert_eq!(thick_square, &execute(&canvas, &inward).unwrap().to_string());
+
+        let outward = brush_command(
+            canvas::Point {x: 3, y: 2},
+            canvas::Dimensions {width: 4, height: 4},
+            2,
+            commands::BrushAlignment::Outward,
+        );
+        assert_eq!(thick_square, &execute(&canvas, &outward).unwrap().to_string());
+
+        let centred = brush_command(
+            canvas::Point {x: 2, y: 1},
+            canvas::Dimensions {width: 6, height: 6},
+            3,
+            commands::BrushAlignment::Centred,
+        );
+        let expected = ".########.\n.########.\n.########.\n.###..###.\n.###..###.\n.########.\n.########.\n.########.\n";
+        assert_eq!(expected, &execute(&canvas, &centred).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_thick_outline_is_clipped_to_the_canvas() {
+        let canvas = canvas::Canvas::blank_canvas(5, 4, ".");
+        let command = brush_command(
+            canvas::Point {x: 0, y: 0},
+            canvas::Dimensions {width: 3, height: 3},
+            2,
+            commands::BrushAlignment::Outward,
+        );
+
+        assert_eq!("####.\n#.##.\n####.\n####.\n", &execute(&canvas, &command).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_strict_outlines_count_the_stroke_outside_the_rectangle() {
+        let canvas = canvas::Canvas::blank_canvas(5, 5, ".");
+        let mut command = brush_command(
+            canvas::Point {x: 1, y: 1},
+            canvas::Dimensions {width: 3, height: 3},
+            2,
+            commands::BrushAlignment::Outward,
+        );
+        command.strict = Some(true);
+
+        // the stroke reaches the edge of the canvas all round
+        assert_eq!("#####\n#####\n##.##\n#####\n#####\n", &execute(&canvas, &command).unwrap().to_string());
+
+        // the rectangle fits, but its stroke would go past the right edge
+        command.position = canvas::Point {x: 2, y: 1};
+        assert_eq!(
+            Err(DrawError::OffCanvas {
+                region: canvas::Region {
+                    position: canvas::Point {x: 1, y: 0},
+                    dimensions: canvas::Dimensions {width: 5, height: 5},
+                },
+                canvas: canvas::Dimensions {width: 5, height: 5},
+            }),
+            execute(&canvas, &command).map(|_| ())
+        );
+
+        // an inward stroke stays within the rectangle
+        command.brush.alignment = commands::BrushAlignment::Inward;
+        assert!(execute(&canvas, &command).is_ok());
+    }
+
+    #[test]
+    fn test_brush_width_must_be_in_range() {
+        let canvas = canvas::Canvas::blank_canvas(5, 5, ".");
+        for width in [0, -3, commands::MAX_BRUSH_WIDTH + 1, i32::MIN] {
+            let command = brush_command(
+                canvas::Point {x: 1, y: 1},
+                canvas::Dimensions {width: 3, height: 3},
+                width,
+                commands::BrushAlignment::Centred,
+            );
+            assert_eq!(Err(DrawError::InvalidBrushWidth(width)), execute(&canvas, &command).map(|_| ()));
+        }
+    }
 }
@@ -0,0 +1,118 @@
+use super::super::canvas;
+use super::super::commands;
+use super::super::draw_error::DrawError;
+use super::draw_transform;
+use super::utils;
+
+/// Executes a Stamp command and returns a new canvas with the changes
+/// The stamp is flipped or rotated first if the command has a transform, then drawn with its top-left
+/// corner at the command's position. Transparent stamp pixels leave the canvas as it was, as do pixels
+/// holding the blank character with `blank_is_transparent`. The command's colours, if any, are given
+/// to every pixel drawn. Fails without a stamp name, or with an unknown stamp or layer
+pub fn execute(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let layer_index = utils::command_layer_index(&new_canvas, command)?;
+    let name = command.stamp.as_ref().ok_or(DrawError::MissingField("stamp"))?;
+    let pixels = match new_canvas.stamps.get(name) {
+        Some(pixels) => pixels,
+        None => return Err(DrawError::StampNotFound(name.clone())),
+    };
+    let pixels = match command.transform {
+        Some(transform) => draw_transform::transform_grid(pixels, transform),
+        None => pixels.clone(),
+    };
+    let pixels: Vec<Vec<canvas::Cell>> = pixels
+        .into_iter()
+        .map(|row| row.into_iter().map(|cell| colour_cell(cell, command)).collect())
+        .collect();
+
+    utils::check_fits_canvas(&new_canvas, command, &command.position, &utils::grid_dimensions(&pixels))?;
+
+    let blank_character = new_canvas.blank_character.clone();
+    let dimensions = new_canvas.dimensions.clone();
+
+    utils::place_pixels(&mut new_canvas.layers[layer_index], &dimensions, &command.position, &pixels, |cell| {
+        cell.is_transparent() || (command.blank_is_transparent && cell.character == blank_character)
+    });
+
+    Ok(new_canvas)
+}
+
+fn colour_cell(cell: canvas::Cell, command: &commands::DrawCommand) -> canvas::Cell {
+    canvas::Cell {
+        foreground: command.foreground.or(cell.foreground),
+        background: command.background.or(cell.background),
+        ..cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{commands, canvas, stamps};
+
+    fn stamp_command(name: &str, position: canvas::Point) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::Stamp,
+            position,
+            stamp: Some(String::from(name)),
+            ..Default::default()
+        }
+    }
+
+    fn canvas_with_arrow() -> canvas::Canvas {
+        let mut canvas = canvas::Canvas::blank_canvas(4, 3, ".");
+        let pixels = stamps::stamp_pixels(&[String::from(">-"), String::from("_.")], Some("_"));
+        canvas.add_stamp("arrow", pixels).unwrap();
+        canvas
+    }
+
+    #[test]
+    fn test_stamp_skips_transparent_pixels() {
+        let mut canvas = canvas_with_arrow();
+        canvas.layers[0].pixels[2][1] = canvas::Cell::from("x");
+
+        let actual = execute(&canvas, &stamp_command("arrow", canvas::Point { x: 1, y: 1 })).unwrap();
+
+        assert_eq!("....\n.>-.\n.x..\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_stamp_blank_as_transparent() {
+        let mut canvas = canvas_with_arrow();
+        canvas.layers[0].pixels[1][1] = canvas::Cell::from("x");
+        let mut command = stamp_command("arrow", canvas::Point { x: 0, y: 0 });
+        command.blank_is_transparent = true;
+
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!(">-..\n.x..\n....\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_stamp_with_transform_and_colour() {
+        let canvas = canvas_with_arrow();
+        let mut command = stamp_command("arrow", canvas::Point { x: 2, y: 0 });
+        command.transform = Some(commands::Transform::FlipHorizontal);
+        command.foreground = Some(canvas::Colour::Palette(1));
+
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("..->\n....\n....\n", actual.to_string());
+        assert_eq!(Some(canvas::Colour::Palette(1)), actual.layers[0].pixels[0][3].foreground);
+        assert_eq!(None, actual.layers[0].pixels[0][1].foreground);
+    }
+
+    #[test]
+    fn test_unknown_stamp_is_refused() {
+        let canvas = canvas_with_arrow();
+
+        let actual = execute(&canvas, &stamp_command("star", canvas::Point { x: 0, y: 0 })).unwrap_err();
+
+        assert_eq!(DrawError::StampNotFound(String::from("star")), actual);
+    }
+}
@@ -0,0 +1,230 @@
+use super::super::canvas;
+use super::super::commands;
+use super::super::commands::Transform;
+use super::super::draw_error::DrawError;
+use super::utils;
+
+/// Executes a flip, rotate or transpose command and returns a new canvas with the changes
+/// Without dimensions every layer of the whole canvas is transformed, and quarter turns and
+/// transposing swap the canvas width and height.
+/// With dimensions only that region of the command's layer is transformed, see `transform_region`.
+/// Fails with negative dimensions or an unknown layer
+pub fn execute(
+    previous_state_canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+) -> Result<canvas::Canvas, DrawError> {
+    let transform = match Transform::from_command(&command.name) {
+        Some(transform) => transform,
+        None => return Ok(previous_state_canvas.clone()),
+    };
+
+    match &command.dimensions {
+        Some(dimensions) => {
+            let dimensions = utils::valid_dimensions(dimensions)?;
+            utils::command_layer_index(previous_state_canvas, command)?;
+            utils::check_fits_canvas(previous_state_canvas, command, &command.position, dimensions)?;
+            Ok(transform_region(previous_state_canvas, dimensions, command, transform))
+        },
+        None => Ok(transform_canvas(previous_state_canvas, transform)),
+    }
+}
+
+/// Rearranges a grid of pixels
+/// Quarter turns are clockwise, and they and transposing turn a W x H grid into an H x W one
+pub fn transform_grid(pixels: &[Vec<canvas::Cell>], transform: Transform) -> Vec<Vec<canvas::Cell>> {
+    let height = pixels.len();
+    let width = pixels.first().map_or(0, |row| row.len());
+    let (new_width, new_height) = match transform {
+        Transform::Rotate90 | Transform::Rotate270 | Transform::Transpose => (height, width),
+        _ => (width, height),
+    };
+
+    (0..new_height)
+        .map(|row_index| {
+            (0..new_width)
+                .map(|column_index| {
+                    let (source_row, source_column) = match transform {
+                        Transform::FlipHorizontal => (row_index, width - 1 - column_index),
+                        Transform::FlipVertical => (height - 1 - row_index, column_index),
+                        Transform::Rotate90 => (height - 1 - column_index, row_index),
+                        Transform::Rotate180 => (height - 1 - row_index, width - 1 - column_index),
+                        Transform::Rotate270 => (column_index, width - 1 - row_index),
+                        Transform::Transpose => (column_index, row_index),
+                    };
+                    pixels[source_row][source_column].clone()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Transforms every layer, resizing the canvas to fit the result
+fn transform_canvas(
+    previous_state_canvas: &canvas::Canvas,
+    transform: Transform,
+) -> canvas::Canvas {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    for layer in new_canvas.layers.iter_mut() {
+        layer.pixels = transform_grid(&layer.pixels, transform);
+    }
+
+    if let Some(first_layer) = new_canvas.layers.first() {
+        new_canvas.dimensions = canvas::Dimensions {
+            width: first_layer.pixels.first().map_or(0, |row| row.len()) as i32,
+            height: first_layer.pixels.len() as i32,
+        };
+    }
+
+    new_canvas
+}
+
+/// Transforms a region of the command's layer in place
+/// The region is first clipped to the canvas. The transformed block keeps the region's top-left
+/// corner and is clipped to the region, so a rotated non-square region loses whatever sticks out
+/// and the uncovered part of the region is cleared (blank on the bottom layer, transparent above it)
+fn transform_region(
+    previous_state_canvas: &canvas::Canvas,
+    dimensions: &canvas::Dimensions,
+    command: &commands::DrawCommand,
+    transform: Transform,
+) -> canvas::Canvas {
+    let mut new_canvas = previous_state_canvas.clone();
+
+    let (start_point, region_dimensions) = match utils::clip_to_canvas(&new_canvas, &command.position, dimensions) {
+        Some(region) => region,
+        None => return new_canvas,
+    };
+
+    let layer_index = match new_canvas.layer_index(&command.layer) {
+        Some(layer_index) => layer_index,
+        None => return new_canvas,
+    };
+    let empty_cell = new_canvas.empty_cell(layer_index);
+    let layer = &mut new_canvas.layers[layer_index];
+
+    let region = utils::copy_region(layer, &start_point, &region_dimensions);
+    let transformed = transform_grid(&region, transform);
+
+    for row_index in 0..region_dimensions.height as usize {
+        for column_index in 0..region_dimensions.width as usize {
+            layer.pixels[start_point.y as usize + row_index][start_point.x as usize + column_index] = transformed
+                .get(row_index)
+                .and_then(|row| row.get(column_index))
+                .cloned()
+                .unwrap_or_else(|| empty_cell.clone());
+        }
+    }
+
+    new_canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::{commands, canvas};
+
+    fn transform_command(
+        name: commands::CommandName,
+        position: canvas::Point,
+        dimensions: Option<canvas::Dimensions>,
+    ) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name,
+            position,
+            dimensions,
+            ..Default::default()
+        }
+    }
+
+    fn lettered_canvas() -> canvas::Canvas {
+        canvas::Canvas::from_text("abc\ndef\n", 3, 2, ".")
+    }
+
+    #[test]
+    fn test_whole_canvas_flips() {
+        let canvas = lettered_canvas();
+        let origin = canvas::Point { x: 0, y: 0 };
+
+        let actual = execute(&canvas, &transform_command(commands::CommandName::FlipHorizontal, origin.clone(), None)).unwrap();
+        assert_eq!("cba\nfed\n", actual.to_string());
+
+        let actual = execute(&canvas, &transform_command(commands::CommandName::FlipVertical, origin, None)).unwrap();
+        assert_eq!("def\nabc\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_whole_canvas_rotations_swap_dimensions() {
+        let canvas = lettered_canvas();
+        let origin = canvas::Point { x: 0, y: 0 };
+
+        let actual = execute(&canvas, &transform_command(commands::CommandName::Rotate90, origin.clone(), None)).unwrap();
+        assert_eq!("da\neb\nfc\n", actual.to_string());
+        assert_eq!(2, actual.dimensions.width);
+        assert_eq!(3, actual.dimensions.height);
+
+        let actual = execute(&canvas, &transform_command(commands::CommandName::Rotate180, origin.clone(), None)).unwrap();
+        assert_eq!("fed\ncba\n", actual.to_string());
+
+        let actual = execute(&canvas, &transform_command(commands::CommandName::Rotate270, origin.clone(), None)).unwrap();
+        assert_eq!("cf\nbe\nad\n", actual.to_string());
+
+        let actual = execute(&canvas, &transform_command(commands::CommandName::Transpose, origin, None)).unwrap();
+        assert_eq!("ad\nbe\ncf\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_whole_canvas_transform_includes_every_layer() {
+        let mut canvas = lettered_canvas();
+        canvas.add_layer("top").unwrap();
+        canvas.layers[1].pixels[0][0] = canvas::Cell::from("X");
+
+        let command = transform_command(commands::CommandName::Rotate180, canvas::Point { x: 0, y: 0 }, None);
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("fed\ncbX\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_region_flip() {
+        let canvas = canvas::Canvas::from_text("abcd\nefgh\nijkl\n", 4, 3, ".");
+        let command = transform_command(
+            commands::CommandName::FlipHorizontal,
+            canvas::Point { x: 1, y: 1 },
+            Some(canvas::Dimensions { width: 3, height: 5 }),
+        );
+
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("abcd\nehgf\nilkj\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_non_square_region_rotation_is_clipped_to_region() {
+        // the 3 x 2 region becomes 2 x 3 - the third row is clipped and the third column is cleared
+        let canvas = canvas::Canvas::from_text("abc\ndef\nxyz\n", 3, 3, ".");
+        let command = transform_command(
+            commands::CommandName::Rotate90,
+            canvas::Point { x: 0, y: 0 },
+            Some(canvas::Dimensions { width: 3, height: 2 }),
+        );
+
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("da.\neb.\nxyz\n", actual.to_string());
+    }
+
+    #[test]
+    fn test_region_off_canvas_has_no_effect() {
+        let canvas = lettered_canvas();
+        let command = transform_command(
+            commands::CommandName::Rotate180,
+            canvas::Point { x: 5, y: 0 },
+            Some(canvas::Dimensions { width: 2, height: 2 }),
+        );
+
+        let actual = execute(&canvas, &command).unwrap();
+
+        assert_eq!("abc\ndef\n", actual.to_string());
+    }
+}
@@ -1,4 +1,10 @@
 pub mod draw_fill_rectangle;
 pub mod draw_outline_rectangle;
+pub mod draw_outline_ellipse;
+pub mod draw_line;
 pub mod draw_flood_fill;
+pub mod draw_gradient;
+pub mod draw_clipboard;
+pub mod draw_stamp;
+pub mod draw_transform;
 mod utils;
\ No newline at end of file
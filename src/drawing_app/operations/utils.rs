@@ -1,20 +1,189 @@
 use super::super::canvas;
+use super::super::commands;
+use super::super::draw_error::DrawError;
+use super::super::layers;
 
-pub fn get_canvas_pixel(
+/// The dimensions a rectangular command works on, which can't be left out or negative
+pub fn command_dimensions(command: &commands::DrawCommand) -> Result<&canvas::Dimensions, DrawError> {
+    match &command.dimensions {
+        Some(dimensions) => valid_dimensions(dimensions),
+        None => Err(DrawError::MissingField("dimensions")),
+    }
+}
+
+/// Checks neither side of a rectangle is negative
+/// Zero sized rectangles are allowed, and draw nothing
+pub fn valid_dimensions(dimensions: &canvas::Dimensions) -> Result<&canvas::Dimensions, DrawError> {
+    if dimensions.width < 0 || dimensions.height < 0 {
+        return Err(DrawError::InvalidDimensions(dimensions.clone()));
+    }
+    Ok(dimensions)
+}
+
+/// In strict mode, refuses a rectangle which draws nothing or doesn't lie wholly on the canvas
+/// Otherwise such rectangles are clipped to the canvas
+pub fn check_fits_canvas(
     canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+    position: &canvas::Point,
+    dimensions: &canvas::Dimensions,
+) -> Result<(), DrawError> {
+    if !command.is_strict() {
+        return Ok(());
+    }
+    if !rectangle_size_is_none_zero(dimensions) {
+        return Err(DrawError::ZeroSized(dimensions.clone()));
+    }
+
+    let region = canvas::Region { position: position.clone(), dimensions: dimensions.clone() };
+    let fits = position.x >= 0 &&
+        position.y >= 0 &&
+        region.right() <= canvas.dimensions.width as i64 &&
+        region.bottom() <= canvas.dimensions.height as i64;
+    if !fits {
+        return Err(DrawError::OffCanvas { region, canvas: canvas.dimensions.clone() });
+    }
+    Ok(())
+}
+
+/// Like `check_fits_canvas` for an outline, whose stroke also reaches `brush.outset()` cells outside the shape
+pub fn check_outline_fits_canvas(
+    canvas: &canvas::Canvas,
+    command: &commands::DrawCommand,
+    position: &canvas::Point,
+    dimensions: &canvas::Dimensions,
+) -> Result<(), DrawError> {
+    if command.is_strict() && !rectangle_size_is_none_zero(dimensions) {
+        return Err(DrawError::ZeroSized(dimensions.clone()));
+    }
+
+    let outset = command.brush.outset();
+    let stroke_position = canvas::Point {
+        x: position.x.saturating_sub(outset),
+        y: position.y.saturating_sub(outset),
+    };
+    let stroke_dimensions = canvas::Dimensions {
+        width: dimensions.width.saturating_add(2 * outset),
+        height: dimensions.height.saturating_add(2 * outset),
+    };
+    check_fits_canvas(canvas, command, &stroke_position, &stroke_dimensions)
+}
+
+/// The size of a grid of pixels, such as a stamp or the contents of a clipboard
+pub fn grid_dimensions(pixels: &[Vec<canvas::Cell>]) -> canvas::Dimensions {
+    canvas::Dimensions {
+        width: pixels.first().map_or(0, |row| row.len()) as i32,
+        height: pixels.len() as i32,
+    }
+}
+
+/// The index of the layer a command draws on
+pub fn command_layer_index(canvas: &canvas::Canvas, command: &commands::DrawCommand) -> Result<usize, DrawError> {
+    canvas
+        .layer_index(&command.layer)
+        .ok_or_else(|| DrawError::LayerNotFound(command.layer.clone().unwrap_or_default()))
+}
+
+/// Checks the brush a line or outline is drawn with is between 1 and `MAX_BRUSH_WIDTH` cells wide
+pub fn check_brush(command: &commands::DrawCommand) -> Result<(), DrawError> {
+    if command.brush.width < 1 || command.brush.width > commands::MAX_BRUSH_WIDTH {
+        return Err(DrawError::InvalidBrushWidth(command.brush.width));
+    }
+    Ok(())
+}
+
+/// Checks the character a command draws with is a single grapheme cluster, or empty to draw transparency
+pub fn check_character(command: &commands::DrawCommand) -> Result<(), DrawError> {
+    if canvas::graphemes(&command.character).len() > 1 {
+        return Err(DrawError::InvalidCharacter(command.character.clone()));
+    }
+    Ok(())
+}
+
+pub fn get_layer_pixel<'a>(
+    layer: &'a layers::Layer,
     position: &canvas::Point, 
-) -> char {
-    canvas.pixels[position.y as usize][position.x as usize]
+) -> &'a canvas::Cell {
+    &layer.pixels[position.y as usize][position.x as usize]
 }
 
 pub fn position_is_on_canvas(
     canvas: &canvas::Canvas, 
     start_point: &canvas::Point, 
+) -> bool {
+    position_is_within(&canvas.dimensions, start_point)
+}
+
+/// Determine whether a point falls inside an area starting at [0, 0]
+pub fn position_is_within(
+    dimensions: &canvas::Dimensions, 
+    start_point: &canvas::Point, 
 ) -> bool {
     start_point.x >= 0 && 
     start_point.y >= 0 && 
-    start_point.x < canvas.dimensions.width &&
-    start_point.y < canvas.dimensions.height
+    start_point.x < dimensions.width &&
+    start_point.y < dimensions.height
+}
+
+/// The part of a rectangle which falls on the canvas, if any
+pub fn clip_to_canvas(
+    canvas: &canvas::Canvas,
+    start_point: &canvas::Point,
+    dimensions: &canvas::Dimensions,
+) -> Option<(canvas::Point, canvas::Dimensions)> {
+    let left = start_point.x.max(0);
+    let top = start_point.y.max(0);
+    let right = start_point.x.saturating_add(dimensions.width).min(canvas.dimensions.width);
+    let bottom = start_point.y.saturating_add(dimensions.height).min(canvas.dimensions.height);
+
+    if left >= right || top >= bottom {
+        return None;
+    }
+
+    Some((
+        canvas::Point { x: left, y: top },
+        canvas::Dimensions { width: right - left, height: bottom - top },
+    ))
+}
+
+/// Copies the pixels of a rectangle which lies entirely within the layer
+pub fn copy_region(
+    layer: &layers::Layer,
+    start_point: &canvas::Point,
+    dimensions: &canvas::Dimensions,
+) -> Vec<Vec<canvas::Cell>> {
+    let (left, top) = (start_point.x as usize, start_point.y as usize);
+    let (right, bottom) = (left + dimensions.width as usize, top + dimensions.height as usize);
+
+    layer.pixels[top..bottom]
+        .iter()
+        .map(|row| row[left..right].to_vec())
+        .collect()
+}
+
+/// Draws a block of pixels onto a layer with its top-left corner at `start_point`
+/// Pixels falling off the canvas are dropped, as are any the `skip` test picks out
+pub fn place_pixels<F>(
+    layer: &mut layers::Layer,
+    dimensions: &canvas::Dimensions,
+    start_point: &canvas::Point,
+    pixels: &[Vec<canvas::Cell>],
+    skip: F,
+) where
+    F: Fn(&canvas::Cell) -> bool,
+{
+    for (row_index, row) in pixels.iter().enumerate() {
+        for (column_index, cell) in row.iter().enumerate() {
+            let position = canvas::Point {
+                x: start_point.x + column_index as i32,
+                y: start_point.y + row_index as i32,
+            };
+
+            if position_is_within(dimensions, &position) && !skip(cell) {
+                layer.pixels[position.y as usize][position.x as usize] = cell.clone();
+            }
+        }
+    }
 }
 
 /// Determine whether an index occurs within a specific range
@@ -60,7 +229,7 @@ fn is_on_line(
     point_y: i32,
 ) -> bool {
     (point_x == start_position_x || point_x == start_position_x + length - 1) &&
-    (point_y >= start_position_y && point_y <= start_position_y + distance - 1)
+    (point_y >= start_position_y && point_y < start_position_y + distance)
 }
 
 
@@ -0,0 +1,231 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+use super::canvas::{Canvas, Cell, Dimensions, Point, MAX_CANVAS_DIMENSION};
+
+/// Which part of the canvas stays put when it is resized
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// How far along each axis the anchor sits, in halves: 0 for the start, 1 for the middle and 2 for the end
+    fn halves(self) -> (i32, i32) {
+        match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        }
+    }
+}
+
+/// Reasons a resize or crop can be refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResizeError {
+    InvalidDimensions,
+    TooLarge,
+    CropOutsideCanvas,
+}
+
+impl fmt::Display for ResizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResizeError::InvalidDimensions => write!(f, "Width and height must both be greater than 0."),
+            ResizeError::TooLarge => write!(f, "Width and height can be at most {}.", MAX_CANVAS_DIMENSION),
+            ResizeError::CropOutsideCanvas => write!(f, "The crop rectangle doesn't overlap the canvas."),
+        }
+    }
+}
+
+impl Error for ResizeError {}
+
+/// Resize errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<ResizeError> for io::Error {
+    fn from(error: ResizeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+impl Canvas {
+    /// Changes the size of the canvas, keeping the content at the anchor in place
+    /// Growing pads the bottom layer with the blank character and other layers with transparency,
+    /// shrinking crops whatever no longer fits
+    pub fn resize(&mut self, dimensions: &Dimensions, anchor: Anchor) -> Result<(), ResizeError> {
+        check_dimensions(dimensions)?;
+
+        // both sizes are within the maximum, so the offset fits an i32 once it has been worked out
+        let (horizontal, vertical) = anchor.halves();
+        let offset = |current: i32, new: i32, halves: i32| ((current as i64 - new as i64) * halves as i64 / 2) as i32;
+        let offset = Point {
+            x: offset(self.dimensions.width, dimensions.width, horizontal),
+            y: offset(self.dimensions.height, dimensions.height, vertical),
+        };
+
+        self.reframe(&offset, dimensions);
+        Ok(())
+    }
+
+    /// Cuts the canvas down to a rectangle
+    /// Parts of the rectangle which fall off the canvas are ignored
+    pub fn crop(&mut self, position: &Point, dimensions: &Dimensions) -> Result<(), ResizeError> {
+        check_dimensions(dimensions)?;
+
+        let left = position.x.max(0);
+        let top = position.y.max(0);
+        let right = position.x.saturating_add(dimensions.width).min(self.dimensions.width);
+        let bottom = position.y.saturating_add(dimensions.height).min(self.dimensions.height);
+
+        if left >= right || top >= bottom {
+            return Err(ResizeError::CropOutsideCanvas);
+        }
+
+        self.reframe(
+            &Point { x: left, y: top },
+            &Dimensions { width: right - left, height: bottom - top },
+        );
+        Ok(())
+    }
+
+    /// Replaces every layer with a window onto it, starting at `offset` in the current canvas
+    fn reframe(&mut self, offset: &Point, dimensions: &Dimensions) {
+        let paddings: Vec<Cell> = (0..self.layers.len())
+            .map(|index| self.empty_cell(index))
+            .collect();
+
+        for (layer, padding) in self.layers.iter_mut().zip(paddings) {
+            layer.pixels = (0..dimensions.height)
+                .map(|row_index| {
+                    (0..dimensions.width)
+                        .map(|column_index| {
+                            let source_row = row_index + offset.y;
+                            let source_column = column_index + offset.x;
+
+                            if source_row < 0 || source_column < 0 {
+                                return padding.clone();
+                            }
+
+                            layer.pixels
+                                .get(source_row as usize)
+                                .and_then(|row| row.get(source_column as usize))
+                                .cloned()
+                                .unwrap_or_else(|| padding.clone())
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+
+        self.dimensions = dimensions.clone();
+    }
+}
+
+/// Checks both sides of a new canvas size are above 0 and within the maximum
+fn check_dimensions(dimensions: &Dimensions) -> Result<(), ResizeError> {
+    if dimensions.width <= 0 || dimensions.height <= 0 {
+        return Err(ResizeError::InvalidDimensions);
+    }
+    if dimensions.width > MAX_CANVAS_DIMENSION || dimensions.height > MAX_CANVAS_DIMENSION {
+        return Err(ResizeError::TooLarge);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_canvas() -> Canvas {
+        Canvas::from_text("123\n456\n789\n", 3, 3, ".")
+    }
+
+    #[test]
+    fn test_grow_from_top_left() {
+        let mut canvas = numbered_canvas();
+        canvas.resize(&Dimensions { width: 5, height: 4 }, Anchor::TopLeft).unwrap();
+
+        assert_eq!("123..\n456..\n789..\n.....\n", canvas.to_string());
+        assert_eq!(5, canvas.dimensions.width);
+        assert_eq!(4, canvas.dimensions.height);
+    }
+
+    #[test]
+    fn test_grow_from_center() {
+        let mut canvas = numbered_canvas();
+        canvas.resize(&Dimensions { width: 5, height: 5 }, Anchor::Center).unwrap();
+
+        assert_eq!(".....\n.123.\n.456.\n.789.\n.....\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_shrink_from_bottom_right() {
+        let mut canvas = numbered_canvas();
+        canvas.resize(&Dimensions { width: 2, height: 1 }, Anchor::BottomRight).unwrap();
+
+        assert_eq!("89\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_grow_pads_upper_layers_with_transparency() {
+        let mut canvas = numbered_canvas();
+        canvas.add_layer("top").unwrap();
+        canvas.resize(&Dimensions { width: 4, height: 3 }, Anchor::Right).unwrap();
+
+        assert_eq!(Cell::from("."), canvas.layers[0].pixels[0][0]);
+        assert!(canvas.layers[1].pixels[0][0].is_transparent());
+        assert_eq!(4, canvas.layers[1].pixels[2].len());
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut canvas = numbered_canvas();
+        canvas.crop(&Point { x: 1, y: 1 }, &Dimensions { width: 5, height: 5 }).unwrap();
+
+        assert_eq!("56\n89\n", canvas.to_string());
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        let mut canvas = numbered_canvas();
+
+        assert_eq!(
+            Err(ResizeError::InvalidDimensions),
+            canvas.resize(&Dimensions { width: 0, height: 3 }, Anchor::Center)
+        );
+        assert_eq!(
+            Err(ResizeError::CropOutsideCanvas),
+            canvas.crop(&Point { x: 3, y: 0 }, &Dimensions { width: 2, height: 2 })
+        );
+        assert_eq!(
+            Err(ResizeError::TooLarge),
+            canvas.resize(&Dimensions { width: 2_000_000_000, height: 2_000_000_000 }, Anchor::BottomRight)
+        );
+        assert_eq!(
+            Err(ResizeError::TooLarge),
+            canvas.crop(&Point { x: i32::MAX, y: 0 }, &Dimensions { width: MAX_CANVAS_DIMENSION + 1, height: 1 })
+        );
+        assert_eq!("123\n456\n789\n", canvas.to_string());
+
+        // the largest canvas keeps its content in place
+        canvas.resize(&Dimensions { width: MAX_CANVAS_DIMENSION, height: 3 }, Anchor::Right).unwrap();
+        assert_eq!("1", canvas.layers[0].pixels[0][MAX_CANVAS_DIMENSION as usize - 3].character);
+    }
+}
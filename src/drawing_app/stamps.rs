@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+use super::canvas::{self, Canvas, Cell, Dimensions};
+
+/// Largest width or height a stamp can have
+pub const MAX_STAMP_SIZE: usize = 64;
+
+/// Summary of a stamp, without its pixels
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StampInfo {
+    pub name: String,
+    pub dimensions: Dimensions,
+}
+
+/// Reasons a stamp can be refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum StampError {
+    EmptyName,
+    Empty,
+    TooLarge,
+}
+
+impl fmt::Display for StampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StampError::EmptyName => write!(f, "A stamp needs a name."),
+            StampError::Empty => write!(f, "A stamp needs at least one character."),
+            StampError::TooLarge => write!(f, "Stamps can be at most {} characters wide and tall.", MAX_STAMP_SIZE),
+        }
+    }
+}
+
+impl Error for StampError {}
+
+/// Stamp errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<StampError> for io::Error {
+    fn from(error: StampError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+/// Turns rows of text into a grid of cells
+/// Each grapheme cluster becomes a cell, any matching `transparent_character` becomes transparent,
+/// and short rows are padded with transparency so the grid is rectangular
+pub fn stamp_pixels(rows: &[String], transparent_character: Option<&str>) -> Vec<Vec<Cell>> {
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| canvas::graphemes(row)).collect();
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+    rows.iter()
+        .map(|row| {
+            (0..width)
+                .map(|index| match row.get(index) {
+                    Some(character) if Some(character.as_str()) != transparent_character => Cell::from(character.as_str()),
+                    _ => Cell::transparent(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl Canvas {
+    /// Stores a stamp under a name, replacing any stamp already stored under it
+    pub fn add_stamp(&mut self, name: &str, pixels: Vec<Vec<Cell>>) -> Result<(), StampError> {
+        if name.is_empty() {
+            return Err(StampError::EmptyName);
+        }
+
+        let width = pixels.first().map_or(0, |row| row.len());
+        if width == 0 {
+            return Err(StampError::Empty);
+        }
+        if width > MAX_STAMP_SIZE || pixels.len() > MAX_STAMP_SIZE {
+            return Err(StampError::TooLarge);
+        }
+
+        self.stamps.insert(String::from(name), pixels);
+        Ok(())
+    }
+
+    /// Lists the stored stamps in name order
+    pub fn stamp_info(&self) -> Vec<StampInfo> {
+        self.stamps
+            .iter()
+            .map(|(name, pixels)| StampInfo {
+                name: name.clone(),
+                dimensions: Dimensions {
+                    width: pixels.first().map_or(0, |row| row.len()) as i32,
+                    height: pixels.len() as i32,
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_pixels_pads_rows_and_marks_transparency() {
+        let pixels = stamp_pixels(&[String::from("a b"), String::from("中")], Some(" "));
+
+        assert_eq!(Cell::from("a"), pixels[0][0]);
+        assert!(pixels[0][1].is_transparent());
+        assert_eq!(Cell::from("中"), pixels[1][0]);
+        assert!(pixels[1][2].is_transparent());
+        assert_eq!(3, pixels[1].len());
+    }
+
+    #[test]
+    fn test_add_stamp() {
+        let mut canvas = Canvas::blank_canvas(2, 2, ".");
+        canvas.add_stamp("dot", stamp_pixels(&[String::from("o")], None)).unwrap();
+        canvas.add_stamp("bar", stamp_pixels(&[String::from("--")], None)).unwrap();
+
+        let info = canvas.stamp_info();
+        assert_eq!("bar", info[0].name);
+        assert_eq!(2, info[0].dimensions.width);
+        assert_eq!("dot", info[1].name);
+
+        assert_eq!(Err(StampError::Empty), canvas.add_stamp("empty", stamp_pixels(&[], None)));
+        assert_eq!(Err(StampError::EmptyName), canvas.add_stamp("", stamp_pixels(&[String::from("o")], None)));
+        assert_eq!(
+            Err(StampError::TooLarge),
+            canvas.add_stamp("wide", stamp_pixels(&["x".repeat(MAX_STAMP_SIZE + 1)], None))
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use fs2::FileExt;
+
+use super::canvas::Canvas;
+
+/// Where a canvas is saved: the canvas file itself, the temp file it is written to first,
+/// and the snapshot of the previous canvas
+pub struct CanvasFiles<'a> {
+    pub canvas: &'a str,
+    pub temp: &'a str,
+    pub snapshot: &'a str,
+}
+
+/// The steps of a save, in order
+/// Fault injection tests stop a save just before one of them, as if the process had crashed there
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaveStep {
+    WriteTemp,
+    SyncTemp,
+    Snapshot,
+    Rename,
+    SyncDirectory,
+}
+
+/// Holds an exclusive advisory lock on a data directory until it is dropped
+#[derive(Debug)]
+pub struct DataDirLock {
+    _file: File,
+}
+
+/// Ways a saved canvas can be damaged, found when it is loaded
+#[derive(Clone, Debug, PartialEq)]
+pub enum CanvasFileError {
+    InvalidDimensions { width: i32, height: i32 },
+    NoLayers,
+    WrongRowCount { layer: String, expected: usize, found: usize },
+    WrongRowWidth { layer: String, row: usize, expected: usize, found: usize },
+    InvalidJson(String), // why a file saved as JSON couldn't be read back
+}
+
+/// A canvas file which couldn't be loaded, and is left where it is until it's repaired, removed or recovered
+#[derive(Clone, Debug, PartialEq)]
+pub struct DamagedCanvasFile {
+    pub path: String,
+    pub error: CanvasFileError,
+}
+
+impl fmt::Display for CanvasFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CanvasFileError::InvalidDimensions { width, height } => {
+                write!(f, "The saved canvas is {} x {}, but both sides must be greater than 0.", width, height)
+            },
+            CanvasFileError::NoLayers => write!(f, "The saved canvas has no layers."),
+            CanvasFileError::WrongRowCount { layer, expected, found } => {
+                write!(f, "Layer '{}' of the saved canvas has {} rows instead of {}.", layer, found, expected)
+            },
+            CanvasFileError::WrongRowWidth { layer, row, expected, found } => {
+                write!(f, "Row {} of layer '{}' of the saved canvas is {} pixels wide instead of {}.", row, layer, found, expected)
+            },
+            CanvasFileError::InvalidJson(reason) => write!(f, "The saved canvas isn't valid JSON: {}.", reason),
+        }
+    }
+}
+
+impl Error for CanvasFileError {}
+
+impl fmt::Display for DamagedCanvasFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} The damaged canvas file is {}.", self.error, self.path)
+    }
+}
+
+impl Error for DamagedCanvasFile {}
+
+/// Canvas file errors are carried inside an io::Error so they can pass through the application's storage layer
+impl From<CanvasFileError> for io::Error {
+    fn from(error: CanvasFileError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl From<DamagedCanvasFile> for io::Error {
+    fn from(error: DamagedCanvasFile) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl Canvas {
+    /// Restores a canvas from its stored representation, checking every layer fits its dimensions
+    /// Files starting with `{` are JSON, and anything else is an older plain text canvas,
+    /// checked against the width and height it is read with
+    pub fn load(contents: &str, width: i32, height: i32, blank_character: &str) -> Result<Canvas, CanvasFileError> {
+        let canvas = if contents.trim_start().starts_with('{') {
+            serde_json::from_str(contents).map_err(|error| CanvasFileError::InvalidJson(error.to_string()))?
+        } else {
+            Canvas::from_text(contents, width, height, blank_character)
+        };
+        canvas.validate()?;
+        Ok(canvas)
+    }
+
+    /// Checks the canvas has at least one layer, and that every layer has one pixel for each point of the canvas
+    pub fn validate(&self) -> Result<(), CanvasFileError> {
+        let dimensions = &self.dimensions;
+        if dimensions.width <= 0 || dimensions.height <= 0 {
+            return Err(CanvasFileError::InvalidDimensions { width: dimensions.width, height: dimensions.height });
+        }
+        if self.layers.is_empty() {
+            return Err(CanvasFileError::NoLayers);
+        }
+
+        let (width, height) = (dimensions.width as usize, dimensions.height as usize);
+        for layer in &self.layers {
+            if layer.pixels.len() != height {
+                return Err(CanvasFileError::WrongRowCount {
+                    layer: layer.name.clone(),
+                    expected: height,
+                    found: layer.pixels.len(),
+                });
+            }
+            if let Some((row, pixels)) = layer.pixels.iter().enumerate().find(|(_, pixels)| pixels.len() != width) {
+                return Err(CanvasFileError::WrongRowWidth {
+                    layer: layer.name.clone(),
+                    row,
+                    expected: width,
+                    found: pixels.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Saves the canvas so that, whenever the process stops, the canvas file holds either the old or the new canvas
+/// The new canvas is written and synced to the temp file, then renamed over the canvas file, and the directory is synced
+/// so the rename itself is durable. With `keep_snapshot` the old canvas is linked to the snapshot first
+pub fn save(files: &CanvasFiles, contents: &str, keep_snapshot: bool) -> io::Result<()> {
+    save_with_faults(files, contents, keep_snapshot, |_| Ok(()))
+}
+
+/// Saves the canvas, calling `before_step` ahead of each step so tests can stop the save part way through
+pub fn save_with_faults<F>(files: &CanvasFiles, contents: &str, keep_snapshot: bool, mut before_step: F) -> io::Result<()>
+where
+    F: FnMut(SaveStep) -> io::Result<()>,
+{
+    before_step(SaveStep::WriteTemp)?;
+    let mut temp_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(files.temp)?;
+    temp_file.write_all(contents.as_bytes())?;
+
+    before_step(SaveStep::SyncTemp)?;
+    temp_file.sync_all()?;
+
+    before_step(SaveStep::Snapshot)?;
+    if keep_snapshot {
+        // a hard link keeps the old canvas without copying it, and without the canvas file ever going missing
+        if Path::new(files.snapshot).exists() {
+            fs::remove_file(files.snapshot)?;
+        }
+        fs::hard_link(files.canvas, files.snapshot).or_else(|_| fs::copy(files.canvas, files.snapshot).map(|_| ()))?;
+    }
+
+    before_step(SaveStep::Rename)?;
+    fs::rename(files.temp, files.canvas)?;
+
+    before_step(SaveStep::SyncDirectory)?;
+    sync_directory(files.canvas)
+}
+
+/// Makes a rename within the canvas file's directory durable
+#[cfg(unix)]
+fn sync_directory(canvas_path: &str) -> io::Result<()> {
+    let directory = match Path::new(canvas_path).parent() {
+        Some(directory) if directory != Path::new("") => directory,
+        _ => Path::new("."),
+    };
+    File::open(directory)?.sync_all()
+}
+
+/// Directories can't be opened to sync them on other platforms, where renames are left to the file system
+#[cfg(not(unix))]
+fn sync_directory(_canvas_path: &str) -> io::Result<()> {
+    Ok(())
+}
+
+/// Takes an exclusive advisory lock on the lock file in a data directory, so only one server uses it at a time
+/// Fails straight away if another process holds the lock
+pub fn lock_data_dir(lock_path: &str) -> io::Result<DataDirLock> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)?;
+    file.try_lock_exclusive().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!("{} is locked, so another server is already using this data directory.", lock_path),
+        )
+    })?;
+    Ok(DataDirLock { _file: file })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    use super::*;
+
+    const OLD_CANVAS: &str = "old\nold\n";
+    const NEW_CANVAS: &str = "new\nnew\n";
+
+    fn temp_directory() -> PathBuf {
+        let directory = env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn test_load_checks_plain_text_against_the_configured_size() {
+        assert!(Canvas::load("abc\ndef\n", 3, 2, ".").is_ok());
+        assert_eq!(
+            CanvasFileError::WrongRowCount { layer: String::from("background"), expected: 3, found: 2 },
+            Canvas::load("abc\ndef\n", 3, 3, ".").unwrap_err()
+        );
+        assert_eq!(
+            CanvasFileError::WrongRowWidth { layer: String::from("background"), row: 1, expected: 3, found: 1 },
+            Canvas::load("abc\nd\n", 3, 2, ".").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_load_checks_every_layer_of_a_saved_canvas() {
+        let mut canvas = Canvas::blank_canvas(3, 2, ".");
+        canvas.add_layer("sketch").unwrap();
+        assert!(Canvas::load(&canvas.serialise(), 30, 30, ".").is_ok());
+
+        canvas.layers[1].pixels[0].pop();
+        assert!(matches!(
+            Canvas::load(&canvas.serialise(), 3, 2, "."),
+            Err(CanvasFileError::WrongRowWidth { row: 0, .. })
+        ));
+
+        canvas.layers.clear();
+        assert_eq!(Err(CanvasFileError::NoLayers), Canvas::load(&canvas.serialise(), 3, 2, ".").map(|_| ()));
+    }
+
+    #[test]
+    fn test_json_which_cant_be_read_is_damage_rather_than_text() {
+        let canvas = Canvas::blank_canvas(3, 2, ".").serialise();
+        let truncated = &canvas[..canvas.len() / 2];
+        assert!(matches!(Canvas::load(truncated, 3, 2, "."), Err(CanvasFileError::InvalidJson(_))));
+        assert!(matches!(Canvas::load("  {\n", 2, 1, "."), Err(CanvasFileError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn test_saves_stopped_at_any_step_leave_a_whole_canvas() {
+        let steps = [SaveStep::WriteTemp, SaveStep::SyncTemp, SaveStep::Snapshot, SaveStep::Rename, SaveStep::SyncDirectory];
+
+        for crash_at in steps.iter() {
+            let directory = temp_directory();
+            let location = |name: &str| directory.join(name).to_string_lossy().into_owned();
+            let (canvas, temp, snapshot) = (location("canvas"), location("temp"), location("snapshot"));
+            let files = CanvasFiles { canvas: &canvas, temp: &temp, snapshot: &snapshot };
+            fs::write(&canvas, OLD_CANVAS).unwrap();
+
+            let crashed = save_with_faults(&files, NEW_CANVAS, true, |step| {
+                if step == *crash_at {
+                    Err(io::Error::other("crashed"))
+                } else {
+                    Ok(())
+                }
+            });
+            assert!(crashed.is_err());
+
+            // the canvas is only replaced once the rename has happened
+            let expected = if *crash_at == SaveStep::SyncDirectory { NEW_CANVAS } else { OLD_CANVAS };
+            let contents = fs::read_to_string(&canvas).unwrap();
+            assert_eq!(expected, contents, "stopped before {:?}", crash_at);
+            assert!(Canvas::load(&contents, 3, 2, ".").is_ok());
+
+            fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_torn_temp_files_never_replace_the_canvas() {
+        let directory = temp_directory();
+        let location = |name: &str| directory.join(name).to_string_lossy().into_owned();
+        let (canvas, temp, snapshot) = (location("canvas"), location("temp"), location("snapshot"));
+        let files = CanvasFiles { canvas: &canvas, temp: &temp, snapshot: &snapshot };
+        fs::write(&canvas, OLD_CANVAS).unwrap();
+
+        // the write only got half way before the crash
+        let crashed = save_with_faults(&files, NEW_CANVAS, true, |step| {
+            if step == SaveStep::SyncTemp {
+                fs::write(&temp, &NEW_CANVAS[..5])?;
+                return Err(io::Error::other("crashed"));
+            }
+            Ok(())
+        });
+        assert!(crashed.is_err());
+        assert_eq!(OLD_CANVAS, fs::read_to_string(&canvas).unwrap());
+        assert!(Canvas::load(&fs::read_to_string(&temp).unwrap(), 3, 2, ".").is_err());
+
+        // a later save goes through, keeping the old canvas as the snapshot
+        save(&files, NEW_CANVAS, true).unwrap();
+        assert_eq!(NEW_CANVAS, fs::read_to_string(&canvas).unwrap());
+        assert_eq!(OLD_CANVAS, fs::read_to_string(&snapshot).unwrap());
+        assert!(!Path::new(&temp).exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_only_one_server_can_lock_a_data_directory() {
+        let directory = temp_directory();
+        let lock_path = directory.join("rusty_canvas.lock").to_string_lossy().into_owned();
+
+        let lock = lock_data_dir(&lock_path).unwrap();
+        let second = lock_data_dir(&lock_path).unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, second.kind());
+
+        drop(lock);
+        assert!(lock_data_dir(&lock_path).is_ok());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,2 @@
+//! The canvas and its draw commands, kept apart from the server so benchmarks can use them
+pub mod drawing_app;
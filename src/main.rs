@@ -1,20 +1,36 @@
+// the chain of warp routes nests deeper than the default limit when built with optimisations
+#![recursion_limit = "256"]
+
 mod server;
-mod drawing_app;
 mod config;
 
+use std::fs;
+use std::path::Path;
 use std::process;
 use std::env;
 use std::sync::Arc;
 use parking_lot::RwLock;
+use rusty_canvas::drawing_app;
 
-use config::Config;
-use drawing_app::{application};
+use config::{Config, ConfigError};
+use drawing_app::{application, storage};
 use server::{server as serverApp};
 
 #[tokio::main]
 async fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|err| {
+    let config = Config::new(env::args(), env::vars()).unwrap_or_else(|err| {
+        if err == ConfigError::Help {
+            println!("{}", config::USAGE);
+            process::exit(0);
+        }
         eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("Run with --help to see the options.");
+        process::exit(1);
+    });
+
+    // held until the server exits, so a second server can't write to the same canvas
+    let _data_dir_lock = prepare_data_dir(&config).unwrap_or_else(|err| {
+        eprintln!("Problem preparing the canvas file: {}", err);
         process::exit(1);
     });
 
@@ -25,8 +41,46 @@ async fn main() {
             blank_character: config.blank_character,
             canvas_path: config.canvas_location,
             canvas_temp_path: config.temp_canvas_location,
+            canvas_snapshot_path: config.snapshot_canvas_location,
+            strict: config.strict,
         }
     )));
 
-    serverApp::run(app).await
+    match app.read().open(&config.configured_canvas, config.on_mismatch) {
+        Ok((_, Some(recovery))) => eprintln!("{}", recovery),
+        Ok(_) => (),
+        Err(err) => {
+            eprintln!("Problem loading the canvas: {}", err);
+            process::exit(1);
+        },
+    }
+
+    if let Err(err) = serverApp::run(app.clone(), config.listen, config.api_keys, config.rate_limits, serverApp::shutdown_signal()).await {
+        eprintln!("Problem running the server: {}", err);
+        process::exit(1);
+    }
+
+    // waits for any draw still holding the canvas before making sure it is on disk
+    let flushed = app.write().flush();
+    if let Err(err) = flushed {
+        eprintln!("Problem saving the canvas: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Creates and locks the directory the canvas is saved in,
+/// then throws away the saved canvas and its snapshot if a fresh one was asked for
+fn prepare_data_dir(config: &Config) -> std::io::Result<storage::DataDirLock> {
+    if let Some(data_dir) = Path::new(&config.canvas_location).parent() {
+        fs::create_dir_all(data_dir)?;
+    }
+    let lock = storage::lock_data_dir(&config.lock_location)?;
+    if config.fresh {
+        for location in &[&config.canvas_location, &config.temp_canvas_location, &config.snapshot_canvas_location] {
+            if Path::new(location).exists() {
+                fs::remove_file(location)?;
+            }
+        }
+    }
+    Ok(lock)
 }
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use warp::{Filter, Rejection};
+
+use super::super::super::config::{ApiKey, Role};
+use super::errors;
+
+/// Header clients send their API key in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Only lets requests through which carry a key with at least the given role
+/// Extracts the name of the key, to attribute changes to, or nothing when no keys are configured
+pub fn require(
+    role: Role,
+    api_keys: Arc<Vec<ApiKey>>,
+) -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>(API_KEY_HEADER)
+        .and_then(move |key: Option<String>| {
+            let api_keys = api_keys.clone();
+            async move { authorise(&api_keys, role, key.as_deref()) }
+        })
+}
+
+/// Like `require`, for routes which don't need to know who sent the request
+pub fn allow(
+    role: Role,
+    api_keys: Arc<Vec<ApiKey>>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    require(role, api_keys)
+        .map(|_| ())
+        .untuple_one()
+}
+
+/// Finds the key sent with a request, and checks its role is enough
+/// Without any configured keys every request is allowed
+pub fn authorise(api_keys: &[ApiKey], required: Role, key: Option<&str>) -> Result<Option<String>, Rejection> {
+    if api_keys.is_empty() {
+        return Ok(None);
+    }
+
+    let api_key = key
+        .and_then(|key| api_keys.iter().find(|api_key| same_key(&api_key.key, key)))
+        .ok_or_else(|| warp::reject::custom(errors::Unauthorized))?;
+
+    if api_key.role < required {
+        return Err(warp::reject::custom(errors::Forbidden {
+            name: api_key.name.clone(),
+            role: api_key.role,
+            required,
+        }));
+    }
+
+    Ok(Some(api_key.name.clone()))
+}
+
+/// Compares keys without stopping at the first difference, so the time taken doesn't give a key away
+fn same_key(expected: &str, given: &str) -> bool {
+    expected.len() == given.len() &&
+        expected.bytes().zip(given.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_keys() -> Vec<ApiKey> {
+        vec!(
+            ApiKey { name: String::from("ada"), key: String::from("ada-key"), role: Role::Admin },
+            ApiKey { name: String::from("bob"), key: String::from("bob-key"), role: Role::Viewer },
+        )
+    }
+
+    #[test]
+    fn test_every_request_is_allowed_without_keys() {
+        assert_eq!(None, authorise(&[], Role::Admin, None).unwrap());
+        assert_eq!(None, authorise(&[], Role::Admin, Some("anything")).unwrap());
+    }
+
+    #[test]
+    fn test_keys_with_enough_of_a_role_are_allowed_and_named() {
+        assert_eq!(Some(String::from("ada")), authorise(&api_keys(), Role::Drawer, Some("ada-key")).unwrap());
+        assert_eq!(Some(String::from("bob")), authorise(&api_keys(), Role::Viewer, Some("bob-key")).unwrap());
+    }
+
+    #[test]
+    fn test_missing_or_unknown_keys_are_unauthorized() {
+        for key in &[None, Some("ada"), Some("ada-key2"), Some("")] {
+            let rejection = authorise(&api_keys(), Role::Viewer, *key).unwrap_err();
+            assert!(rejection.find::<errors::Unauthorized>().is_some());
+        }
+    }
+
+    #[test]
+    fn test_keys_without_enough_of_a_role_are_forbidden() {
+        let rejection = authorise(&api_keys(), Role::Drawer, Some("bob-key")).unwrap_err();
+        match rejection.find::<errors::Forbidden>() {
+            Some(errors::Forbidden { name, role, required }) => {
+                assert_eq!("bob", name);
+                assert_eq!(Role::Viewer, *role);
+                assert_eq!(Role::Drawer, *required);
+            },
+            None => panic!("expected the key to be forbidden"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_the_key_is_read_from_the_header() {
+        let filter = require(Role::Viewer, Arc::new(api_keys()));
+
+        let name = warp::test::request().header("X-API-Key", "bob-key").filter(&filter).await.unwrap();
+        assert_eq!(Some(String::from("bob")), name);
+        assert!(warp::test::request().filter(&filter).await.is_err());
+    }
+}
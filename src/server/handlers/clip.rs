@@ -0,0 +1,167 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, clip};
+use super::utils;
+use super::request;
+use super::validation;
+
+/// Handler for setting the clip
+/// Every following draw command only changes the canvas inside the clip
+/// Returns the new clip
+pub async fn handle_set_clip_request(
+    author: Option<String>,
+    request: request::ClipOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let new_clip = clip::Clip {
+        regions: request.regions,
+        mask_layer: request.mask_layer,
+    };
+
+    match app.write().set_clip(new_clip, author.as_deref()) {
+        Ok(clip) => Ok(warp::reply::json(&clip)),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
+
+/// Handler for clearing the clip
+/// Draw commands can change the whole canvas again
+pub async fn handle_clear_clip_request(
+    author: Option<String>,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Err(e) = app.write().clear_clip(author.as_deref()) {
+        return Err(utils::reject_application_error(e));
+    }
+    Ok(warp::reply::json(&app.read().clip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::{errors, rate_limit, transform};
+    use super::super::super::super::drawing_app::{application, canvas, clip, commands, draw_error};
+
+    fn fill_command(character: &str) -> commands::DrawCommand {
+        commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: canvas::Point { x: 0, y: 0 },
+            dimensions: Some(canvas::Dimensions { width: 4, height: 2 }),
+            character: String::from(character),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_clip_requests() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 4,
+                height: 2,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let request = request::ClipOperation {
+            regions: vec!(canvas::Region {
+                position: canvas::Point { x: 1, y: 0 },
+                dimensions: canvas::Dimensions { width: 2, height: 2 },
+            }),
+            mask_layer: None,
+        };
+        handle_set_clip_request(None, request, app.clone()).await.unwrap();
+
+        let actual = app.write().draw(vec!(fill_command("#"))).unwrap().0;
+        assert_eq!(".##.\n.##.\n", actual.to_string());
+
+        // turning the whole canvas would change its shape, which can't be kept inside the clip
+        let turn = request::TransformOperation {
+            transform: commands::Transform::Rotate90,
+            position: None,
+            dimensions: None,
+            layer: None,
+            strict: None,
+        };
+        let rejection = transform::handle_transform_request(rate_limit::Client::default(), turn, app.clone()).await.err().unwrap();
+        match rejection.find::<errors::InvalidDrawOperation>() {
+            Some(errors::InvalidDrawOperation(e)) => assert_eq!(&draw_error::DrawError::Clipped(clip::ClipError::Reshaped), e),
+            None => panic!("expected the clip to refuse the transform"),
+        }
+        assert_eq!(".##.\n.##.\n", app.read().canvas().unwrap().to_string());
+
+        handle_clear_clip_request(None, app.clone()).await.unwrap();
+        assert!(app.read().clip().is_none());
+
+        let actual = app.write().draw(vec!(fill_command("o"))).unwrap().0;
+        assert_eq!("oooo\noooo\n", actual.to_string());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_clips_are_rejected() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 4,
+                height: 2,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let empty = request::ClipOperation { regions: vec!(), mask_layer: None };
+        let rejection = handle_set_clip_request(None, empty, app.clone()).await.err().unwrap();
+        match rejection.find::<errors::InvalidClipOperation>() {
+            Some(errors::InvalidClipOperation(e)) => assert_eq!(&clip::ClipError::Empty, e),
+            None => panic!("expected an invalid clip rejection"),
+        }
+
+        let unknown_mask = request::ClipOperation { regions: vec!(), mask_layer: Some(String::from("stencil")) };
+        let rejection = handle_set_clip_request(None, unknown_mask, app.clone()).await.err().unwrap();
+        assert!(rejection.find::<errors::InvalidClipOperation>().is_some());
+        assert!(app.read().clip().is_none());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
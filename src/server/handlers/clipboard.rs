@@ -0,0 +1,156 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, commands};
+use super::utils;
+use super::rate_limit;
+use super::request;
+use super::validation;
+
+/// Handler for the copy route
+/// Stores a region of the canvas in a clipboard, leaving the canvas unchanged
+pub async fn handle_copy_request(
+    client: rate_limit::Client,
+    request: request::CopyOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    utils::apply_draw_operation(vec!(copy_command(commands::CommandName::Copy, request, client.author.clone())), app, client)
+}
+
+/// Handler for the cut route
+/// Stores a region of the canvas in a clipboard and clears it
+pub async fn handle_cut_request(
+    client: rate_limit::Client,
+    request: request::CopyOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    utils::apply_draw_operation(vec!(copy_command(commands::CommandName::Cut, request, client.author.clone())), app, client)
+}
+
+/// Handler for the paste route
+/// Draws the contents of a clipboard onto the canvas
+pub async fn handle_paste_request(
+    client: rate_limit::Client,
+    request: request::PasteOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let paste_command = commands::DrawCommand {
+        name: commands::CommandName::Paste,
+        position: request.position,
+        layer: request.layer,
+        clipboard: request.clipboard,
+        blank_is_transparent: request.blank_is_transparent,
+        strict: request.strict,
+        author: client.author.clone(),
+        ..Default::default()
+    };
+
+    utils::apply_draw_operation(vec!(paste_command), app, client)
+}
+
+fn copy_command(name: commands::CommandName, request: request::CopyOperation, author: Option<String>) -> commands::DrawCommand {
+    commands::DrawCommand {
+        name,
+        position: request.position,
+        dimensions: Some(request.dimensions),
+        layer: request.layer,
+        clipboard: request.clipboard,
+        strict: request.strict,
+        author,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::draw_rectangle;
+    use super::super::super::super::drawing_app::{application, canvas};
+
+    #[tokio::test]
+    async fn test_handle_cut_and_paste_requests() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 4,
+                height: 2,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let rectangle = request::DrawRectangleOperation {
+            position: canvas::Point {
+                x: 0,
+                y: 0
+            },
+            dimensions: canvas::Dimensions {
+                width: 2,
+                height: 1,
+            },
+            fill_character: String::from("X"),
+            outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
+        };
+
+        draw_rectangle::handle_draw_rectangle_request(rate_limit::Client::default(), rectangle, app.clone()).await.unwrap();
+
+        let cut = request::CopyOperation {
+            position: canvas::Point { x: 0, y: 0 },
+            dimensions: canvas::Dimensions { width: 2, height: 1 },
+            clipboard: Some(String::from("saved")),
+            layer: None,
+            strict: None,
+        };
+        handle_cut_request(rate_limit::Client::default(), cut, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!("    \n    \n", actual.to_string());
+
+        let paste = request::PasteOperation {
+            position: canvas::Point { x: 2, y: 1 },
+            clipboard: Some(String::from("saved")),
+            layer: None,
+            blank_is_transparent: false,
+            strict: None,
+        };
+        handle_paste_request(rate_limit::Client::default(), paste.clone(), app.clone()).await.unwrap();
+        handle_paste_request(rate_limit::Client::default(), paste, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!("    \n  XX\n", actual.to_string());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
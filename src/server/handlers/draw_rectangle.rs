@@ -3,52 +3,58 @@ use std::sync::Arc;
 
 use super::super::super::drawing_app::{application, commands};
 use super::utils;
+use super::rate_limit;
 use super::request;
+use super::validation;
 
 /// Handler for the draw rectangle route
 /// Takes a valid request and transforms this into valid draw operations
 /// Attempts to draw to canvas and returns the result
 pub async fn handle_draw_rectangle_request(
+    client: rate_limit::Client,
     request: request::DrawRectangleOperation,
     app: Arc<RwLock<application::DrawingApplication>>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
     let mut commands = vec!();
 
     if utils::field_is_not_none(&request.fill_character) {
-        match utils::valid_character(&request.fill_character) {
-            Ok(character) => {
-                let fill_rectangle_command = commands::DrawCommand {
-                    name: commands::CommandName::FillRectangle,
-                    position: request.position.clone(),
-                    dimensions: Some(request.dimensions.clone()),
-                    character,
-                };
-        
-                commands.push(fill_rectangle_command);
-            },
-            _ => return Err(warp::reject::custom(super::errors::StringTooLong)),
-        }
+        let fill_rectangle_command = commands::DrawCommand {
+            name: commands::CommandName::FillRectangle,
+            position: request.position.clone(),
+            dimensions: Some(request.dimensions.clone()),
+            character: utils::canvas_character(&request.fill_character),
+            foreground: request.fill_foreground,
+            background: request.fill_background,
+            layer: request.layer.clone(),
+            pattern: utils::canvas_pattern(request.fill_pattern.clone()),
+            strict: request.strict,
+            author: client.author.clone(),
+            ..Default::default()
+        };
+
+        commands.push(fill_rectangle_command);
     }
 
     if utils::field_is_not_none(&request.outline_character) {
-        match utils::valid_character(&request.outline_character) {
-            Ok(character) => {
-                let outline_rectangle_command = commands::DrawCommand {
-                    name: commands::CommandName::OutlineRectangle,
-                    position: request.position,
-                    dimensions: Some(request.dimensions),
-                    character,
-                };
-        
-                commands.push(outline_rectangle_command);
-            },
-            _ => {
-                return Err(warp::reject::custom(super::errors::StringTooLong))
-            },
-        }
+        let outline_rectangle_command = commands::DrawCommand {
+            name: commands::CommandName::OutlineRectangle,
+            position: request.position,
+            dimensions: Some(request.dimensions),
+            character: utils::canvas_character(&request.outline_character),
+            foreground: request.outline_foreground,
+            background: request.outline_background,
+            layer: request.layer,
+            brush: request.outline_brush,
+            strict: request.strict,
+            author: client.author.clone(),
+            ..Default::default()
+        };
+
+        commands.push(outline_rectangle_command);
     }
 
-    utils::apply_draw_operation(commands, app)
+    utils::apply_draw_operation(commands, app, client)
 }
 
 #[cfg(test)]
@@ -61,20 +67,23 @@ mod tests {
     
     use super::*;
     use super::request;
-    use super::super::super::super::drawing_app::{application, canvas};
+    use super::super::super::super::drawing_app::{application, canvas, commands, draw_error};
 
     #[tokio::test]
     async fn test_handle_draw_rectangle_request() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -85,6 +94,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request_one = request::DrawRectangleOperation {
             position: canvas::Point {
@@ -97,6 +109,14 @@ mod tests {
             },
             fill_character: String::from("."),
             outline_character: String::from("."),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_two = request::DrawRectangleOperation {
@@ -110,6 +130,14 @@ mod tests {
             },
             fill_character: String::from(" "),
             outline_character: String::from("O"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_three = request::DrawRectangleOperation {
@@ -123,17 +151,25 @@ mod tests {
             },
             fill_character: String::from("X"),
             outline_character: String::from("X"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let expected = "              .......\n              .......\n              .......\nOOOOOOOO      .......\nO      O      .......\nO    XXXXX    .......\nOOOOOXXXXX           \n     XXXXX           \n";
 
-        handle_draw_rectangle_request(request_one, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_two, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_three, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_three, app.clone()).await.unwrap();
 
         let actual = app
-            .write()
-            .draw(vec!());
+            .read()
+            .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -144,20 +180,26 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
 
     #[tokio::test]
     async fn test_none_keyword() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -168,6 +210,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request_one = request::DrawRectangleOperation {
             position: canvas::Point {
@@ -180,6 +225,14 @@ mod tests {
             },
             fill_character: String::from('.'),
             outline_character: String::from('.'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_two = request::DrawRectangleOperation {
@@ -193,6 +246,14 @@ mod tests {
             },
             fill_character: String::from("none"),
             outline_character: String::from('O'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_three = request::DrawRectangleOperation {
@@ -205,18 +266,26 @@ mod tests {
                 height: 3,
             },
             fill_character: String::from('X'),
-            outline_character: String::from('X')
+            outline_character: String::from('X'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let expected = "              .......\n              .......\n              .......\nOOOOOOOO      .......\nO      O      .......\nO    XXXXX    .......\nOOOOOXXXXX           \n     XXXXX           \n";
 
-        handle_draw_rectangle_request(request_one, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_two, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_three, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_three, app.clone()).await.unwrap();
 
         let actual = app
-            .write()
-            .draw(vec!());
+            .read()
+            .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -228,20 +297,26 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
 
     #[tokio::test]
     async fn test_none_written_in_any_case_keyword() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -252,6 +327,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request_one = request::DrawRectangleOperation {
             position: canvas::Point {
@@ -264,6 +342,14 @@ mod tests {
             },
             fill_character: String::from('.'),
             outline_character: String::from('.'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_two = request::DrawRectangleOperation {
@@ -277,6 +363,14 @@ mod tests {
             },
             fill_character: String::from("NoNe"),
             outline_character: String::from('O'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_three = request::DrawRectangleOperation {
@@ -289,18 +383,26 @@ mod tests {
                 height: 3,
             },
             fill_character: String::from('X'),
-            outline_character: String::from('X')
+            outline_character: String::from('X'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let expected = "              .......\n              .......\n              .......\nOOOOOOOO      .......\nO      O      .......\nO    XXXXX    .......\nOOOOOXXXXX           \n     XXXXX           \n";
 
-        handle_draw_rectangle_request(request_one, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_two, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_three, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_three, app.clone()).await.unwrap();
 
         let actual = app
-            .write()
-            .draw(vec!());
+            .read()
+            .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -311,20 +413,26 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
 
     #[tokio::test]
     async fn test_should_only_outline() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -335,6 +443,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request_one = request::DrawRectangleOperation {
             position: canvas::Point {
@@ -347,6 +458,14 @@ mod tests {
             },
             fill_character: String::from('9'),
             outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_two = request::DrawRectangleOperation {
@@ -360,16 +479,24 @@ mod tests {
             },
             fill_character: String::from("NoNe"),
             outline_character: String::from('O'),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let expected = "99999999999999       \n99999999999999       \n99999999999999       \n999OOOO9999999       \n999O99O9999999       \n999O99O9999999       \n999OOOO9999999       \n99999999999999       \n";
 
-        handle_draw_rectangle_request(request_one, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_two, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
         
         let actual = app
-        .write()
-        .draw(vec!());
+        .read()
+        .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -380,20 +507,26 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
 
     #[tokio::test]
     async fn test_should_only_fill() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -404,6 +537,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request_one = request::DrawRectangleOperation {
             position: canvas::Point {
@@ -416,6 +552,14 @@ mod tests {
             },
             fill_character: String::from('9'),
             outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let request_two = request::DrawRectangleOperation {
@@ -429,16 +573,24 @@ mod tests {
             },
             fill_character: String::from('0'),
             outline_character: String::from("NoNe"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
         };
 
         let expected = "99999999999999       \n99999999999999       \n99999999999999       \n99900009999999       \n99900009999999       \n99900009999999       \n99900009999999       \n99999999999999       \n";
 
-        handle_draw_rectangle_request(request_one, app.clone()).await.unwrap();
-        handle_draw_rectangle_request(request_two, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        handle_draw_rectangle_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
         
         let actual = app
-        .write()
-        .draw(vec!());
+        .read()
+        .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -449,5 +601,95 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_patterned_fill_request() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 4,
+                height: 2,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let mut request = request::DrawRectangleOperation {
+            position: canvas::Point {
+                x: 0,
+                y: 0
+            },
+            dimensions: canvas::Dimensions {
+                width: 4,
+                height: 2,
+            },
+            fill_character: String::from("#"),
+            outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: Some(commands::Pattern::Dither { other_character: String::from("ab"), level: 8 }),
+            outline_brush: commands::Brush::default(),
+            strict: None,
+        };
+
+        // pattern characters are validated like any other character
+        let rejection = handle_draw_rectangle_request(rate_limit::Client::default(), request.clone(), app.clone()).await.err().unwrap();
+        match rejection.find::<super::super::errors::InvalidFields>() {
+            Some(super::super::errors::InvalidFields(fields)) => assert_eq!("fill_pattern.other_character", fields[0].field),
+            None => panic!("expected an invalid fill_pattern.other_character"),
+        }
+
+        request.fill_pattern = Some(commands::Pattern::Dither { other_character: String::from("."), level: 8 });
+        handle_draw_rectangle_request(rate_limit::Client::default(), request.clone(), app.clone()).await.unwrap();
+
+        let actual = app
+            .read()
+            .canvas();
+
+        assert_eq!(".#.#\n#.#.\n", actual.unwrap().to_string());
+
+        // drawing onto a layer which doesn't exist is refused rather than ignored
+        request.layer = Some(String::from("sketch"));
+        let rejection = handle_draw_rectangle_request(rate_limit::Client::default(), request.clone(), app.clone()).await.err().unwrap();
+        match rejection.find::<super::super::errors::InvalidDrawOperation>() {
+            Some(super::super::errors::InvalidDrawOperation(e)) => {
+                assert_eq!(&draw_error::DrawError::LayerNotFound(String::from("sketch")), e)
+            },
+            None => panic!("expected an unknown layer rejection"),
+        }
+
+        // a damaged canvas file is reported with its path, and isn't drawn over
+        fs::write(&canvas_location, "....\n").unwrap();
+        request.layer = None;
+        let rejection = handle_draw_rectangle_request(rate_limit::Client::default(), request, app.clone()).await.err().unwrap();
+        match rejection.find::<super::super::errors::DamagedCanvas>() {
+            Some(super::super::errors::DamagedCanvas(damaged)) => assert_eq!(canvas_location, damaged.path),
+            None => panic!("expected a damaged canvas rejection"),
+        }
+        assert_eq!("....\n", fs::read_to_string(&canvas_location).unwrap());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
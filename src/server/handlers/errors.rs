@@ -1,12 +1,77 @@
 use warp::{reject::Reject};
 
+use super::super::super::config::Role;
+use super::super::super::drawing_app::{clip, draw_error, layers, locks, resize, stamps, storage};
+use super::validation;
+
+#[derive(Debug)]
+/// Error returned when keys are configured, and a request has no key or one which isn't known
+pub struct Unauthorized;
+
+#[derive(Debug)]
+/// Error returned when a request's key doesn't have the role a route needs
+pub struct Forbidden {
+    pub name: String,
+    pub role: Role,
+    pub required: Role,
+}
+
+#[derive(Debug, PartialEq)]
+/// Error returned when a client has made too many draw requests, or changed too many cells, in the last minute
+pub enum RateLimited {
+    Requests { requests_per_minute: u32, retry_after: u64 },
+    CellQuota { cells_per_minute: u32, retry_after: u64 },
+}
+
+impl RateLimited {
+    /// Seconds until the client can draw again
+    pub fn retry_after(&self) -> u64 {
+        match self {
+            RateLimited::Requests { retry_after, .. } | RateLimited::CellQuota { retry_after, .. } => *retry_after,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Error returned when fields of a request are missing, can't be read or aren't valid
+pub struct InvalidFields(pub Vec<validation::FieldError>);
+
+#[derive(Debug)]
+/// Error returned when a layer can't be created, changed or removed
+pub struct InvalidLayerOperation(pub layers::LayerError);
+
+#[derive(Debug)]
+/// Error returned when the canvas can't be resized or cropped as requested
+pub struct InvalidResizeOperation(pub resize::ResizeError);
+
+#[derive(Debug)]
+/// Error returned when a stamp can't be stored
+pub struct InvalidStampOperation(pub stamps::StampError);
+
+#[derive(Debug)]
+/// Error returned when a clip can't be set
+pub struct InvalidClipOperation(pub clip::ClipError);
+
+#[derive(Debug)]
+/// Error returned when a lock can't be changed, or a draw operation touches a locked region
+pub struct InvalidLockOperation(pub locks::LockError);
+
 #[derive(Debug)]
-/// Error returned when a draw operation is not succesful
-pub struct ApplyOperationError;
+/// Error returned when a draw command can't be applied, or the canvas can't be loaded or saved
+pub struct InvalidDrawOperation(pub draw_error::DrawError);
 
 #[derive(Debug)]
-/// Error returned when a draw operation is not succesful
-pub struct StringTooLong;
+/// Error returned when the saved canvas is damaged, which is refused until an operator repairs or recovers it
+pub struct DamagedCanvas(pub storage::DamagedCanvasFile);
 
-impl Reject for ApplyOperationError {}
-impl Reject for StringTooLong {}
\ No newline at end of file
+impl Reject for Unauthorized {}
+impl Reject for Forbidden {}
+impl Reject for RateLimited {}
+impl Reject for InvalidFields {}
+impl Reject for InvalidLayerOperation {}
+impl Reject for InvalidResizeOperation {}
+impl Reject for InvalidStampOperation {}
+impl Reject for InvalidClipOperation {}
+impl Reject for InvalidLockOperation {}
+impl Reject for DamagedCanvas {}
+impl Reject for InvalidDrawOperation {}
@@ -3,34 +3,46 @@ use std::sync::Arc;
 
 use super::super::super::drawing_app::{application, commands};
 use super::utils;
+use super::rate_limit;
 use super::request;
+use super::validation;
 
 /// Handler for the flood fill route
 /// Takes a valid request and transforms this into a draw operation
 /// It then attempts to draw to canvas and returns the result
 pub async fn handle_flood_fill_request(
+    client: rate_limit::Client,
     request: request::FloodFillOperation,
     app: Arc<RwLock<application::DrawingApplication>>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
     let mut commands = vec!();
 
     if utils::field_is_not_none(&request.fill_character) {
-        match utils::valid_character(&request.fill_character) {
-            Ok(character) => {
-                let flood_fill_command = commands::DrawCommand {
-                    name: commands::CommandName::FloodFill,
-                    position: request.position.clone(),
-                    dimensions: None,
-                    character,
-                };
-                
-                commands.push(flood_fill_command)
+        let flood_fill_command = commands::DrawCommand {
+            name: commands::CommandName::FloodFill,
+            position: request.position.clone(),
+            character: utils::canvas_character(&request.fill_character),
+            foreground: request.fill_foreground,
+            background: request.fill_background,
+            layer: request.layer,
+            flood_fill: commands::FloodFillOptions {
+                connectivity: request.connectivity,
+                mode: request.mode,
+                boundary_character: request.boundary_character.as_deref().map(utils::canvas_character),
+                clip: request.clip,
+                mask_layer: request.mask_layer,
             },
-            _ => return Err(warp::reject::custom(super::errors::StringTooLong)),
-        }
+            pattern: utils::canvas_pattern(request.fill_pattern),
+            strict: request.strict,
+            author: client.author.clone(),
+            ..Default::default()
+        };
+
+        commands.push(flood_fill_command)
     }
 
-    utils::apply_draw_operation(commands, app)
+    utils::apply_draw_operation(commands, app, client)
 }
 
 #[cfg(test)]
@@ -42,20 +54,23 @@ mod tests {
     use std::sync::Arc;
     use uuid::Uuid;
     use super::request;
-    use super::super::super::super::drawing_app::{application, canvas};
+    use super::super::super::super::drawing_app::{application, canvas, commands};
 
     #[tokio::test]
     async fn test_flood_fill_request() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -66,6 +81,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request = request::FloodFillOperation {
             position: canvas::Point {
@@ -73,15 +91,25 @@ mod tests {
                 y: 0
             },
             fill_character: String::from("Y"),
+            fill_foreground: None,
+            fill_background: None,
+            layer: None,
+            connectivity: commands::Connectivity::Four,
+            mode: commands::FillMode::Flood,
+            boundary_character: None,
+            clip: None,
+            mask_layer: None,
+            fill_pattern: None,
+            strict: None,
         };
 
         let expected = "YYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\n";
 
-        handle_flood_fill_request(request, app.clone()).await.unwrap();
+        handle_flood_fill_request(rate_limit::Client::default(), request, app.clone()).await.unwrap();
 
         let actual = app
-            .write()
-            .draw(vec!());
+            .read()
+            .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -93,20 +121,26 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
 
     #[tokio::test]
     async fn test_none_has_no_effect() {
         let canvas_location = Uuid::new_v4().to_string();
         let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
 
         let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
             application::ApplicationOptions {
                 width: 21,
                 height: 8,
-                blank_character: ' ',
+                blank_character: String::from(" "),
                 canvas_path: canvas_location.clone(),
                 canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
             }
         )));
         
@@ -117,6 +151,9 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
 
         let request_one = request::FloodFillOperation {
             position: canvas::Point {
@@ -124,6 +161,16 @@ mod tests {
                 y: 0
             },
             fill_character: String::from("Y"),
+            fill_foreground: None,
+            fill_background: None,
+            layer: None,
+            connectivity: commands::Connectivity::Four,
+            mode: commands::FillMode::Flood,
+            boundary_character: None,
+            clip: None,
+            mask_layer: None,
+            fill_pattern: None,
+            strict: None,
         };
 
         let request_two = request::FloodFillOperation {
@@ -132,16 +179,26 @@ mod tests {
                 y: 0
             },
             fill_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            layer: None,
+            connectivity: commands::Connectivity::Four,
+            mode: commands::FillMode::Flood,
+            boundary_character: None,
+            clip: None,
+            mask_layer: None,
+            fill_pattern: None,
+            strict: None,
         };
 
         let expected = "YYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\nYYYYYYYYYYYYYYYYYYYYY\n";
 
-        handle_flood_fill_request(request_one, app.clone()).await.unwrap();
-        handle_flood_fill_request(request_two, app.clone()).await.unwrap();
+        handle_flood_fill_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        handle_flood_fill_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
 
         let actual = app
-            .write()
-            .draw(vec!());
+            .read()
+            .canvas();
         
         assert_eq!(expected, actual.unwrap().to_string());
 
@@ -153,5 +210,76 @@ mod tests {
         if Path::new(&temp_canvas_location).exists() {
             fs::remove_file(&temp_canvas_location).unwrap();
         }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_boundary_fill_request() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 3,
+                height: 1,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let mut request = request::FloodFillOperation {
+            position: canvas::Point {
+                x: 0,
+                y: 0
+            },
+            fill_character: String::from("Y"),
+            fill_foreground: None,
+            fill_background: None,
+            layer: None,
+            connectivity: commands::Connectivity::Eight,
+            mode: commands::FillMode::Boundary,
+            boundary_character: None,
+            clip: None,
+            mask_layer: None,
+            fill_pattern: None,
+            strict: None,
+        };
+
+        // boundary fills need something to stop at
+        let rejection = handle_flood_fill_request(rate_limit::Client::default(), request.clone(), app.clone()).await.err().unwrap();
+        match rejection.find::<super::super::errors::InvalidFields>() {
+            Some(super::super::errors::InvalidFields(fields)) => assert_eq!("boundary_character", fields[0].field),
+            None => panic!("expected an invalid boundary_character"),
+        }
+
+        request.boundary_character = Some(String::from("Y"));
+        request.clip = Some(canvas::Region {
+            position: canvas::Point { x: 0, y: 0 },
+            dimensions: canvas::Dimensions { width: 2, height: 1 },
+        });
+        handle_flood_fill_request(rate_limit::Client::default(), request, app.clone()).await.unwrap();
+
+        let actual = app
+            .read()
+            .canvas();
+
+        assert_eq!("YY \n", actual.unwrap().to_string());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
     }
-}
\ No newline at end of file
+}
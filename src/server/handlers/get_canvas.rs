@@ -3,19 +3,30 @@ use std::sync::Arc;
 
 use super::super::super::drawing_app::{application};
 use super::utils;
-use super::errors;
 
 /// Handler for the main canvas route
 /// Returns the canvas
 pub async fn handle_get_canvas(
     app: Arc<RwLock<application::DrawingApplication>>
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let canvas = app.write().draw(vec!());
+    let canvas = app.read().canvas();
     match canvas {
         Ok(canvas) => {
             let html_string = utils::construct_html_with_canvas(&canvas);
             Ok(warp::reply::html(html_string))
         },
-        Err(_) => Err(warp::reject::custom(errors::ApplyOperationError))
+        Err(e) => Err(utils::reject_application_error(e))
+    }
+}
+
+/// Handler for the terminal canvas route
+/// Returns the canvas with ANSI colour codes, i.e. for `curl localhost:8080/ansi`
+pub async fn handle_get_canvas_ansi(
+    app: Arc<RwLock<application::DrawingApplication>>
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let canvas = app.read().canvas();
+    match canvas {
+        Ok(canvas) => Ok(canvas.to_ansi()),
+        Err(e) => Err(utils::reject_application_error(e))
     }
 }
@@ -0,0 +1,106 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, canvas, commands};
+use super::utils;
+use super::rate_limit;
+use super::request;
+use super::validation;
+
+/// Handler for the gradient route
+/// Turns the request into a gradient fill draw command, using the default ramp if none is given
+/// Attempts to draw to canvas and returns the result
+pub async fn handle_gradient_request(
+    client: rate_limit::Client,
+    request: request::GradientOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let ramp = canvas::graphemes(request.ramp.as_deref().unwrap_or(commands::DEFAULT_RAMP));
+
+    let gradient_command = commands::DrawCommand {
+        name: commands::CommandName::GradientFill,
+        position: request.position,
+        dimensions: Some(request.dimensions),
+        foreground: request.foreground,
+        background: request.background,
+        layer: request.layer,
+        gradient: Some(commands::Gradient {
+            direction: request.direction,
+            ramp,
+            dither: request.dither,
+        }),
+        strict: request.strict,
+        author: client.author.clone(),
+        ..Default::default()
+    };
+
+    utils::apply_draw_operation(vec!(gradient_command), app, client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::super::super::drawing_app::{application, canvas, commands};
+
+    #[tokio::test]
+    async fn test_handle_gradient_request() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 10,
+                height: 2,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let mut request = request::GradientOperation {
+            position: canvas::Point { x: 0, y: 0 },
+            dimensions: canvas::Dimensions { width: 10, height: 1 },
+            direction: commands::GradientDirection::Horizontal,
+            ramp: Some(String::new()),
+            dither: false,
+            foreground: None,
+            background: None,
+            layer: None,
+            strict: None,
+        };
+
+        let rejection = handle_gradient_request(rate_limit::Client::default(), request.clone(), app.clone()).await.err().unwrap();
+        match rejection.find::<super::super::errors::InvalidFields>() {
+            Some(super::super::errors::InvalidFields(fields)) => assert_eq!("ramp", fields[0].field),
+            None => panic!("expected an invalid ramp"),
+        }
+
+        // without a ramp the default one is used
+        request.ramp = None;
+        handle_gradient_request(rate_limit::Client::default(), request, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!(" .:-=+*#%@\n          \n", actual.to_string());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
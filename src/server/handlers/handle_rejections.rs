@@ -1,45 +1,145 @@
 
 use std::convert::Infallible;
-use std::error::Error;
 
 use serde::{Serialize};
+use warp::http::header::{HeaderValue, RETRY_AFTER};
 use warp::http::StatusCode;
 use warp::{Rejection, Reply};
 
+use super::super::super::drawing_app::clip::ClipError;
+use super::super::super::drawing_app::draw_error::DrawError;
+use super::super::super::drawing_app::layers::LayerError;
+use super::super::super::drawing_app::locks::{LockError, LockInfo};
+use super::validation::FieldError;
+
 /// An API error serializable to JSON.
+/// Draw operations refused by a lock also say which lock, and who owns it
+/// Invalid requests list each field which was refused, with the value sent and the reason
 #[derive(Serialize)]
 struct ErrorMessage {
     code: u16,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lock: Option<LockInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError>,
 }
 
 /// Takes a warp rejection, and processes our custom errors
+/// Rate limited requests are also told when to retry, in the Retry-After header
 pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     let code;
     let message;
-    let mut deserialise_error = String::from("Error deserializing JSON: ");
+    let forbidden_error;
+    let rate_limit_error;
+    let fields_error;
+    let layer_error;
+    let resize_error;
+    let stamp_error;
+    let clip_error;
+    let lock_error;
+    let file_error;
+    let draw_error;
+    let mut lock = None;
+    let mut fields = vec!();
+    let mut retry_after = None;
 
     if err.is_not_found() {
         code = StatusCode::NOT_FOUND;
         message = "NOT_FOUND";
-    } else if let Some(super::errors::StringTooLong) = err.find() {
+    } else if err.find::<super::errors::Unauthorized>().is_some() {
+        code = StatusCode::UNAUTHORIZED;
+        message = "This needs an API key, sent in the X-API-Key header.";
+    } else if let Some(super::errors::Forbidden { name, role, required }) = err.find() {
+        code = StatusCode::FORBIDDEN;
+        forbidden_error = format!("The key '{}' has the {} role, and this needs the {} role or above.", name, role, required);
+        message = &forbidden_error;
+    } else if let Some(e) = err.find::<super::errors::RateLimited>() {
+        code = StatusCode::TOO_MANY_REQUESTS;
+        rate_limit_error = match e {
+            super::errors::RateLimited::Requests { requests_per_minute, retry_after } => format!(
+                "Only {} draw requests a minute are allowed, try again in {}.", requests_per_minute, seconds(*retry_after)
+            ),
+            super::errors::RateLimited::CellQuota { cells_per_minute, retry_after } => format!(
+                "Only {} cells can be changed a minute, try again in {}.", cells_per_minute, seconds(*retry_after)
+            ),
+        };
+        message = &rate_limit_error;
+        retry_after = Some(e.retry_after());
+    } else if let Some(super::errors::InvalidFields(errors)) = err.find() {
         code = StatusCode::BAD_REQUEST;
-        message = "Fill and outline characters should be either 'none' or of length 1.";
-    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
-        let error = match e.source() {
-            Some(cause) => cause.to_string(),
-            None => String::from("BAD_REQUEST"),
+        let reasons: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        fields_error = format!("Invalid request: {}.", reasons.join(", "));
+        message = &fields_error;
+        fields = errors.clone();
+    } else if let Some(super::errors::InvalidLayerOperation(e)) = err.find() {
+        code = match e {
+            LayerError::NotFound(_) => StatusCode::NOT_FOUND,
+            LayerError::AlreadyExists(_) => StatusCode::CONFLICT,
+            LayerError::LastLayer => StatusCode::BAD_REQUEST,
         };
-        deserialise_error.push_str(&error);
-        message = &deserialise_error;
+        layer_error = e.to_string();
+        message = &layer_error;
+    } else if let Some(super::errors::InvalidResizeOperation(e)) = err.find() {
+        code = StatusCode::BAD_REQUEST;
+        resize_error = e.to_string();
+        message = &resize_error;
+    } else if let Some(super::errors::InvalidStampOperation(e)) = err.find() {
         code = StatusCode::BAD_REQUEST;
-    } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
+        stamp_error = e.to_string();
+        message = &stamp_error;
+    } else if let Some(super::errors::InvalidClipOperation(e)) = err.find() {
+        code = match e {
+            ClipError::Empty => StatusCode::BAD_REQUEST,
+            ClipError::MaskLayerNotFound(_) => StatusCode::NOT_FOUND,
+            ClipError::Reshaped => StatusCode::CONFLICT,
+        };
+        clip_error = e.to_string();
+        message = &clip_error;
+    } else if let Some(super::errors::InvalidLockOperation(e)) = err.find() {
+        code = match e {
+            LockError::EmptyName | LockError::EmptyOwner => StatusCode::BAD_REQUEST,
+            LockError::AlreadyExists(_) => StatusCode::CONFLICT,
+            LockError::NotFound(_) => StatusCode::NOT_FOUND,
+            LockError::Locked(info) => {
+                lock = Some(info.clone());
+                StatusCode::LOCKED
+            },
+        };
+        lock_error = e.to_string();
+        message = &lock_error;
+    } else if let Some(super::errors::InvalidDrawOperation(e)) = err.find() {
+        code = match e {
+            DrawError::MissingField(_) |
+            DrawError::InvalidDimensions(_) |
+            DrawError::InvalidCharacter(_) |
+            DrawError::InvalidBrushWidth(_) |
+            DrawError::EmptyGradientRamp => StatusCode::BAD_REQUEST,
+            DrawError::OutOfBounds(_) |
+            DrawError::OffCanvas { .. } |
+            DrawError::ZeroSized(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DrawError::LayerNotFound(_) |
+            DrawError::MaskLayerNotFound(_) |
+            DrawError::StampNotFound(_) |
+            DrawError::ClipboardNotFound(_) => StatusCode::NOT_FOUND,
+            DrawError::Clipped(_) => StatusCode::CONFLICT,
+            DrawError::Locked(_) => StatusCode::LOCKED,
+            DrawError::DamagedCanvas(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DrawError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        draw_error = e.to_string();
+        message = &draw_error;
+    } else if let Some(super::errors::DamagedCanvas(e)) = err.find() {
+        // the damaged file is left in place, so every request is refused until an operator deals with it
+        code = StatusCode::SERVICE_UNAVAILABLE;
+        file_error = format!(
+            "{} Repair or remove it, or restart the server to recover the canvas from the temp file or last snapshot.",
+            e,
+        );
+        message = &file_error;
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         code = StatusCode::NOT_FOUND;
         message = "NOT_FOUND";
-    } else if let Some(super::errors::ApplyOperationError) = err.find() {
-        // We somehow passed an invalid draw operation to the application
-        code = StatusCode::INTERNAL_SERVER_ERROR;
-        message = "Oops, you must have passed something that wasn't a valid draw command. Validation should have caught that... it's a bug!";
     } else {
         eprintln!("Unhandled rejection: {:?}", err);
         code = StatusCode::INTERNAL_SERVER_ERROR;
@@ -49,7 +149,21 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
     let json = warp::reply::json(&ErrorMessage {
         code: code.as_u16(),
         message: message.into(),
+        lock,
+        fields,
     });
 
-    Ok(warp::reply::with_status(json, code))
-}
\ No newline at end of file
+    let mut response = warp::reply::with_status(json, code).into_response();
+    if let Some(seconds) = retry_after {
+        response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(seconds));
+    }
+    Ok(response)
+}
+
+/// Words a wait in seconds for a message
+fn seconds(count: u64) -> String {
+    match count {
+        1 => String::from("1 second"),
+        count => format!("{} seconds", count),
+    }
+}
@@ -0,0 +1,16 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application};
+use super::utils;
+
+/// Handler for listing the latest changes applied to the canvas
+/// Returns the action, author and number of cells changed of each, oldest first
+pub async fn handle_list_history(
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match app.read().canvas() {
+        Ok(canvas) => Ok(warp::reply::json(&canvas.history)),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
@@ -0,0 +1,236 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, canvas};
+use super::utils;
+use super::request;
+use super::validation;
+
+/// Handler for listing the layers
+/// Returns the name, visibility and position of each layer, from bottom to top
+pub async fn handle_list_layers(
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let canvas = app.read().canvas();
+    reply_with_layers(canvas)
+}
+
+/// Handler for creating a new layer
+/// New layers are transparent and are added on top of the existing ones
+pub async fn handle_create_layer_request(
+    author: Option<String>,
+    request: request::CreateLayerOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let canvas = app.write().add_layer(&request.name, author.as_deref());
+    reply_with_layers(canvas)
+}
+
+/// Handler for moving a layer or changing whether it is shown
+pub async fn handle_update_layer_request(
+    name: String,
+    author: Option<String>,
+    request: request::UpdateLayerOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let canvas = app.write().update_layer(&name, request.index, request.visible, author.as_deref());
+    reply_with_layers(canvas)
+}
+
+/// Handler for deleting a layer
+pub async fn handle_delete_layer_request(
+    name: String,
+    author: Option<String>,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let canvas = app.write().remove_layer(&name, author.as_deref());
+    reply_with_layers(canvas)
+}
+
+fn reply_with_layers(
+    canvas: std::io::Result<canvas::Canvas>,
+) -> Result<warp::reply::Json, warp::Rejection> {
+    match canvas {
+        Ok(canvas) => Ok(warp::reply::json(&canvas.layer_info())),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::{draw_rectangle, errors, rate_limit};
+    use super::super::super::super::drawing_app::{application, canvas, commands, layers, locks};
+
+    #[tokio::test]
+    async fn test_draw_onto_layers() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 6,
+                height: 3,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        handle_create_layer_request(
+            None,
+            request::CreateLayerOperation { name: String::from("overlay") },
+            app.clone(),
+        ).await.unwrap();
+
+        let request_one = request::DrawRectangleOperation {
+            position: canvas::Point {
+                x: 0,
+                y: 0
+            },
+            dimensions: canvas::Dimensions {
+                width: 6,
+                height: 3,
+            },
+            fill_character: String::from("."),
+            outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
+        };
+
+        let request_two = request::DrawRectangleOperation {
+            position: canvas::Point {
+                x: 1,
+                y: 0
+            },
+            dimensions: canvas::Dimensions {
+                width: 3,
+                height: 3,
+            },
+            fill_character: String::from("transparent"),
+            outline_character: String::from("O"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: Some(String::from("overlay")),
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
+        };
+
+        draw_rectangle::handle_draw_rectangle_request(rate_limit::Client::default(), request_one, app.clone()).await.unwrap();
+        draw_rectangle::handle_draw_rectangle_request(rate_limit::Client::default(), request_two, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!(".OOO..\n.O.O..\n.OOO..\n", actual.to_string());
+
+        // a lock over the overlay's outline stops it being hidden, moved under the fill or deleted
+        let lock = locks::Lock {
+            owner: String::from("ada"),
+            region: canvas::Region {
+                position: canvas::Point { x: 0, y: 0 },
+                dimensions: canvas::Dimensions { width: 6, height: 1 },
+            },
+            mode: locks::LockMode::Reject,
+        };
+        app.write().add_lock("header", lock, None).unwrap();
+        let changes = vec!(
+            request::UpdateLayerOperation { index: None, visible: Some(false) },
+            request::UpdateLayerOperation { index: Some(0), visible: None },
+        );
+        for change in changes {
+            let rejection = handle_update_layer_request(String::from("overlay"), None, change, app.clone()).await.err().unwrap();
+            assert!(rejection.find::<errors::InvalidLockOperation>().is_some());
+        }
+        let rejection = handle_delete_layer_request(String::from("overlay"), None, app.clone()).await.err().unwrap();
+        assert!(rejection.find::<errors::InvalidLockOperation>().is_some());
+        assert_eq!(".OOO..\n.O.O..\n.OOO..\n", app.read().canvas().unwrap().to_string());
+        app.write().remove_lock("header", None).unwrap();
+
+        handle_update_layer_request(
+            String::from("overlay"),
+            None,
+            request::UpdateLayerOperation { index: None, visible: Some(false) },
+            app.clone(),
+        ).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!("......\n......\n......\n", actual.to_string());
+
+        handle_delete_layer_request(String::from("overlay"), None, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!(1, actual.layers.len());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_missing_layer() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 6,
+                height: 3,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let rejection = handle_delete_layer_request(String::from("missing"), None, app.clone())
+            .await
+            .err()
+            .unwrap();
+
+        match rejection.find::<errors::InvalidLayerOperation>() {
+            Some(errors::InvalidLayerOperation(e)) => {
+                assert_eq!(&layers::LayerError::NotFound(String::from("missing")), e)
+            },
+            None => panic!("expected a layer error"),
+        }
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
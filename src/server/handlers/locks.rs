@@ -0,0 +1,173 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, canvas, locks};
+use super::utils;
+use super::request;
+use super::validation;
+
+/// Handler for listing the locks
+/// Returns the name, owner, region and mode of each lock
+pub async fn handle_list_locks(
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let canvas = app.read().canvas();
+    reply_with_locks(canvas)
+}
+
+/// Handler for locking a region of the canvas
+/// Lock names are unique, so an existing lock has to be deleted before its name is reused
+pub async fn handle_create_lock_request(
+    author: Option<String>,
+    request: request::CreateLockOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let lock = locks::Lock {
+        owner: request.owner,
+        region: canvas::Region {
+            position: request.position,
+            dimensions: request.dimensions,
+        },
+        mode: request.mode,
+    };
+
+    let canvas = app.write().add_lock(&request.name, lock, author.as_deref());
+    reply_with_locks(canvas)
+}
+
+/// Handler for deleting a lock
+pub async fn handle_delete_lock_request(
+    name: String,
+    author: Option<String>,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let canvas = app.write().remove_lock(&name, author.as_deref());
+    reply_with_locks(canvas)
+}
+
+fn reply_with_locks(
+    canvas: std::io::Result<canvas::Canvas>,
+) -> Result<warp::reply::Json, warp::Rejection> {
+    match canvas {
+        Ok(canvas) => Ok(warp::reply::json(&canvas.lock_info())),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::{draw_rectangle, errors, rate_limit};
+    use super::super::super::super::drawing_app::{application, canvas, commands, history, locks};
+
+    fn fill_request(character: &str) -> request::DrawRectangleOperation {
+        request::DrawRectangleOperation {
+            position: canvas::Point { x: 0, y: 0 },
+            dimensions: canvas::Dimensions { width: 4, height: 3 },
+            fill_character: String::from(character),
+            outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
+        }
+    }
+
+    fn root() -> Option<String> {
+        Some(String::from("root"))
+    }
+
+    fn lock_request(name: &str, y: i32, mode: locks::LockMode) -> request::CreateLockOperation {
+        request::CreateLockOperation {
+            name: String::from(name),
+            owner: String::from("ada"),
+            position: canvas::Point { x: 0, y },
+            dimensions: canvas::Dimensions { width: 4, height: 1 },
+            mode,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_locked_regions() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 4,
+                height: 3,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        handle_create_lock_request(root(), lock_request("header", 0, locks::LockMode::Reject), app.clone()).await.unwrap();
+        handle_create_lock_request(root(), lock_request("footer", 2, locks::LockMode::Clip), app.clone()).await.unwrap();
+
+        // a rejecting lock refuses the whole draw request
+        let rejection = draw_rectangle::handle_draw_rectangle_request(rate_limit::Client::default(), fill_request("#"), app.clone()).await.err().unwrap();
+        match rejection.find::<errors::InvalidLockOperation>() {
+            Some(errors::InvalidLockOperation(locks::LockError::Locked(info))) => {
+                assert_eq!("header", info.name);
+                assert_eq!("ada", info.owner);
+            },
+            _ => panic!("expected the header lock to refuse the draw"),
+        }
+        assert_eq!("....\n....\n....\n", app.read().canvas().unwrap().to_string());
+
+        // a clipping lock keeps its region unchanged but lets the rest through
+        handle_delete_lock_request(String::from("header"), root(), app.clone()).await.unwrap();
+        draw_rectangle::handle_draw_rectangle_request(rate_limit::Client::default(), fill_request("#"), app.clone()).await.unwrap();
+        assert_eq!("####\n####\n....\n", app.read().canvas().unwrap().to_string());
+
+        let rejection = handle_delete_lock_request(String::from("header"), root(), app.clone()).await.err().unwrap();
+        assert!(rejection.find::<errors::InvalidLockOperation>().is_some());
+
+        // locks apply to their owner too, and each applied change is attributed to its author
+        let mut owner = rate_limit::Client::default();
+        owner.author = Some(String::from("ada"));
+        draw_rectangle::handle_draw_rectangle_request(owner, fill_request("o"), app.clone()).await.unwrap();
+        let canvas = app.read().canvas().unwrap();
+        assert_eq!("oooo\noooo\n....\n", canvas.to_string());
+        let changes: Vec<(history::Action, Option<&str>, usize)> = canvas.history
+            .iter()
+            .map(|change| (change.action.clone(), change.author.as_deref(), change.cells_changed))
+            .collect();
+        assert_eq!(
+            vec!(
+                (history::Action::Edit(history::Edit::CreateLock), Some("root"), 0),
+                (history::Action::Edit(history::Edit::CreateLock), Some("root"), 0),
+                (history::Action::Edit(history::Edit::DeleteLock), Some("root"), 0),
+                (history::Action::Draw(commands::CommandName::FillRectangle), None, 8),
+                (history::Action::Draw(commands::CommandName::FillRectangle), Some("ada"), 8),
+            ),
+            changes
+        );
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
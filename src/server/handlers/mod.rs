@@ -1,8 +1,21 @@
+pub mod auth;
+pub mod clip;
+pub mod clipboard;
 pub mod draw_rectangle;
 pub mod flood_fill;
 pub mod get_canvas;
+pub mod gradient;
 pub mod handle_rejections;
+pub mod history;
+pub mod layers;
+pub mod locks;
+pub mod rate_limit;
+pub mod resize;
+pub mod shapes;
+pub mod stamps;
+pub mod transform;
 pub mod ws;
 pub mod utils;
 mod request;
-mod errors;
\ No newline at end of file
+mod errors;
+mod validation;
\ No newline at end of file
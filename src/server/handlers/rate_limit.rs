@@ -0,0 +1,287 @@
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use warp::{Filter, Rejection};
+
+use super::super::super::config::RateLimits;
+use super::errors;
+use super::validation;
+
+/// How far back the cells a client changed count towards its quota
+const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+/// Clients tracked before the ones which have used none of their allowance are forgotten
+const TRACKED_CLIENTS: usize = 1024;
+
+/// Who a draw request is counted against
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ClientId {
+    Key(String), // name of the API key sent
+    Address(Option<IpAddr>), // none over a Unix socket, where every client shares one allowance
+}
+
+/// How much of its allowance a client has used
+struct Usage {
+    tokens: f64, // draw requests which can be made straight away
+    refilled: Instant,
+    cells: VecDeque<(Instant, usize)>, // cells changed by each draw within the quota window, oldest first
+}
+
+/// Keeps a token bucket for each client, and the cells each has changed recently
+pub struct RateLimiter {
+    limits: RateLimits,
+    clients: Mutex<HashMap<ClientId, Usage>>,
+}
+
+/// The client a draw request came from, which the cells it changes are counted against
+#[derive(Clone, Default)]
+pub struct Client {
+    pub author: Option<String>, // name of the client's API key, which its commands are attributed to
+    counted: Option<(Arc<RateLimiter>, ClientId)>,
+}
+
+impl Client {
+    /// Counts the cells a draw changed towards the client's quota
+    pub fn record(&self, cells: usize) {
+        if let Some((limiter, id)) = &self.counted {
+            limiter.record(id, cells, Instant::now());
+        }
+    }
+}
+
+/// Reads and checks a draw request's body before taking anything from the client's allowance,
+/// so requests refused for a body which can't be read or isn't valid don't use it up
+/// The client is found from the API key name and address the `client` filter extracts
+pub fn admit_valid<C, B, T>(
+    limiter: Arc<RateLimiter>,
+    client: C,
+    body: B,
+) -> impl Filter<Extract = (Client, T), Error = Rejection> + Clone
+where
+    C: Filter<Extract = (Option<String>, Option<SocketAddr>), Error = Rejection> + Clone + Send,
+    B: Filter<Extract = (T,), Error = Rejection> + Clone + Send,
+    T: validation::Validate + Send,
+{
+    client
+        .and(body)
+        .and_then(move |author, address, request: T| {
+            let limiter = limiter.clone();
+            async move {
+                validation::validate(&request)?;
+                let client = admit(limiter, author, address)?;
+                Ok::<_, Rejection>((client, request))
+            }
+        })
+        .untuple_one()
+}
+
+/// Finds the client a draw request came from, and lets the request through if the client has allowance left
+/// Clients with an API key are counted by its name, and others by their address
+fn admit(
+    limiter: Arc<RateLimiter>,
+    author: Option<String>,
+    address: Option<SocketAddr>,
+) -> Result<Client, Rejection> {
+    let id = match &author {
+        Some(name) => ClientId::Key(name.clone()),
+        None => ClientId::Address(address.map(|address| address.ip())),
+    };
+    limiter.admit(&id, Instant::now()).map_err(warp::reject::custom)?;
+
+    Ok(Client {
+        author,
+        counted: Some((limiter, id)),
+    })
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> RateLimiter {
+        RateLimiter {
+            limits,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the client's bucket, unless the bucket is empty or the client's quota is used up
+    /// A client over its quota keeps its token, so it isn't charged for a request which was refused
+    fn admit(&self, id: &ClientId, now: Instant) -> Result<(), errors::RateLimited> {
+        if self.limits.requests_per_minute.is_none() && self.limits.cells_per_minute.is_none() {
+            return Ok(());
+        }
+
+        let mut clients = self.clients.lock();
+        if clients.len() >= TRACKED_CLIENTS && !clients.contains_key(id) {
+            clients.retain(|_, usage| !self.is_unused(usage, now));
+        }
+        let burst = self.limits.burst as f64;
+        let usage = clients.entry(id.clone()).or_insert_with(|| Usage {
+            tokens: burst,
+            refilled: now,
+            cells: VecDeque::new(),
+        });
+
+        if let Some(quota) = self.limits.cells_per_minute {
+            while usage.cells.front().is_some_and(|(at, _)| *at + QUOTA_WINDOW <= now) {
+                usage.cells.pop_front();
+            }
+            let used: usize = usage.cells.iter().map(|(_, cells)| cells).sum();
+            if used >= quota as usize {
+                // the quota frees up once enough of the oldest draws have left the window
+                let mut remaining = used;
+                let freed = usage.cells
+                    .iter()
+                    .find(|(_, cells)| {
+                        remaining -= cells;
+                        remaining < quota as usize
+                    })
+                    .map_or(now, |(at, _)| *at + QUOTA_WINDOW);
+                return Err(errors::RateLimited::CellQuota {
+                    cells_per_minute: quota,
+                    retry_after: whole_seconds(freed - now),
+                });
+            }
+        }
+
+        if let Some(rate) = self.limits.requests_per_minute {
+            let per_second = rate as f64 / 60.0;
+            let elapsed = now.duration_since(usage.refilled).as_secs_f64();
+            usage.tokens = (usage.tokens + elapsed * per_second).min(burst);
+            usage.refilled = now;
+
+            if usage.tokens < 1.0 {
+                return Err(errors::RateLimited::Requests {
+                    requests_per_minute: rate,
+                    retry_after: whole_seconds(Duration::from_secs_f64((1.0 - usage.tokens) / per_second)),
+                });
+            }
+            usage.tokens -= 1.0;
+        }
+
+        Ok(())
+    }
+
+    fn record(&self, id: &ClientId, cells: usize, now: Instant) {
+        if self.limits.cells_per_minute.is_none() || cells == 0 {
+            return;
+        }
+        if let Some(usage) = self.clients.lock().get_mut(id) {
+            usage.cells.push_back((now, cells));
+        }
+    }
+
+    /// Whether a client's bucket has refilled and its draws have left the quota window,
+    /// so forgetting it makes no difference
+    fn is_unused(&self, usage: &Usage, now: Instant) -> bool {
+        let refilled = self.limits.requests_per_minute.is_none_or(|rate| {
+            let elapsed = now.duration_since(usage.refilled).as_secs_f64();
+            usage.tokens + elapsed * rate as f64 / 60.0 >= self.limits.burst as f64
+        });
+        let quiet = usage.cells.back().is_none_or(|(at, _)| *at + QUOTA_WINDOW <= now);
+        refilled && quiet
+    }
+}
+
+/// Rounds a wait up to whole seconds, as used by the Retry-After header
+fn whole_seconds(wait: Duration) -> u64 {
+    let seconds = wait.as_secs() + if wait.subsec_nanos() > 0 { 1 } else { 0 };
+    seconds.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::super::config::{ApiKey, Role};
+    use super::super::{auth, request};
+
+    fn limiter(requests_per_minute: Option<u32>, burst: u32, cells_per_minute: Option<u32>) -> RateLimiter {
+        RateLimiter::new(RateLimits { requests_per_minute, burst, cells_per_minute })
+    }
+
+    fn key(name: &str) -> ClientId {
+        ClientId::Key(String::from(name))
+    }
+
+    #[test]
+    fn test_without_limits_every_request_is_admitted() {
+        let limiter = limiter(None, 10, None);
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            limiter.record(&key("ada"), 1000, now);
+            assert!(limiter.admit(&key("ada"), now).is_ok());
+        }
+        assert!(limiter.clients.lock().is_empty());
+    }
+
+    #[test]
+    fn test_a_burst_is_admitted_then_the_bucket_refills_at_the_rate() {
+        let limiter = limiter(Some(30), 3, None);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.admit(&key("ada"), now).is_ok());
+        }
+        assert_eq!(
+            Err(errors::RateLimited::Requests { requests_per_minute: 30, retry_after: 2 }),
+            limiter.admit(&key("ada"), now)
+        );
+        // other clients have their own bucket
+        assert!(limiter.admit(&key("bob"), now).is_ok());
+        assert!(limiter.admit(&ClientId::Address(None), now).is_ok());
+
+        // one token comes back every 2 seconds
+        assert!(limiter.admit(&key("ada"), now + Duration::from_secs(2)).is_ok());
+        assert!(limiter.admit(&key("ada"), now + Duration::from_secs(3)).is_err());
+    }
+
+    #[test]
+    fn test_draws_are_refused_once_the_cell_quota_is_used_up() {
+        let limiter = limiter(None, 10, Some(100));
+        let now = Instant::now();
+
+        assert!(limiter.admit(&key("ada"), now).is_ok());
+        limiter.record(&key("ada"), 60, now);
+        assert!(limiter.admit(&key("ada"), now + Duration::from_secs(10)).is_ok());
+        limiter.record(&key("ada"), 60, now + Duration::from_secs(10));
+
+        // 120 cells in the last minute, until the first draw leaves the window
+        assert_eq!(
+            Err(errors::RateLimited::CellQuota { cells_per_minute: 100, retry_after: 30 }),
+            limiter.admit(&key("ada"), now + Duration::from_secs(30))
+        );
+        assert!(limiter.admit(&key("bob"), now + Duration::from_secs(30)).is_ok());
+        assert!(limiter.admit(&key("ada"), now + Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn test_clients_which_used_nothing_are_forgotten() {
+        let limiter = limiter(Some(60), 1, None);
+        let now = Instant::now();
+
+        for index in 0..TRACKED_CLIENTS {
+            limiter.admit(&key(&index.to_string()), now).unwrap();
+        }
+        limiter.admit(&key("ada"), now + Duration::from_secs(2)).unwrap();
+        assert_eq!(1, limiter.clients.lock().len());
+    }
+
+    #[tokio::test]
+    async fn test_requests_with_bad_bodies_use_none_of_the_allowance() {
+        let api_keys = vec!(ApiKey { name: String::from("ada"), key: String::from("ada-key"), role: Role::Drawer });
+        let client = auth::require(Role::Drawer, Arc::new(api_keys)).and(warp::addr::remote());
+        let filter = admit_valid(Arc::new(limiter(Some(1), 1, None)), client, validation::json_body::<request::LineOperation>());
+        let line = |body: &str| warp::test::request().method("POST").header(auth::API_KEY_HEADER, "ada-key").body(body);
+
+        let unreadable = line("{").filter(&filter).await.err().unwrap();
+        assert!(unreadable.find::<errors::InvalidFields>().is_some());
+        let invalid = line(r#"{"start":{"x":0,"y":0},"end":{"x":1,"y":0},"character":"x","brush":{"width":0}}"#).filter(&filter).await.err().unwrap();
+        assert!(invalid.find::<errors::InvalidFields>().is_some());
+
+        // the one request of the burst is still there for a valid body, and only then used up
+        let valid = r#"{"start":{"x":0,"y":0},"end":{"x":1,"y":0},"character":"x"}"#;
+        let (admitted, _) = line(valid).filter(&filter).await.unwrap();
+        assert_eq!(Some(String::from("ada")), admitted.author);
+        assert!(line(valid).filter(&filter).await.err().unwrap().find::<errors::RateLimited>().is_some());
+    }
+}
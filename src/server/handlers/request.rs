@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
-use super::super::super::drawing_app::{canvas};
+use super::super::super::drawing_app::{canvas, commands, locks, resize};
+
+// Every draw request takes an optional `strict` field, which refuses rather than clips a shape that doesn't fit
+// the canvas. Leaving it out uses the server's setting
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// Request data structure expected on the draw rectangle route
@@ -8,13 +11,228 @@ pub struct DrawRectangleOperation {
     pub dimensions: canvas::Dimensions,
     pub fill_character: String,
     pub outline_character: String,
+    #[serde(default)]
+    pub fill_foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub fill_background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub outline_foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub outline_background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub fill_pattern: Option<commands::Pattern>,
+    #[serde(default)]
+    pub outline_brush: commands::Brush,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
 }
 
-pub const NONE_CHARACTER: &'static str = "none";
+pub const NONE_CHARACTER: &str = "none";
+
+pub const TRANSPARENT_CHARACTER: &str = "transparent";
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// Request data structure expected on the floor fill route
 pub struct FloodFillOperation {
     pub position: canvas::Point,
     pub fill_character: String,
+    #[serde(default)]
+    pub fill_foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub fill_background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub fill_pattern: Option<commands::Pattern>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub connectivity: commands::Connectivity,
+    #[serde(default)]
+    pub mode: commands::FillMode,
+    #[serde(default)]
+    pub boundary_character: Option<String>,
+    #[serde(default)]
+    pub clip: Option<canvas::Region>,
+    #[serde(default)]
+    pub mask_layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Request data structure expected when creating a layer
+pub struct CreateLayerOperation {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Request data structure expected when updating a layer
+/// Only the fields which are present are changed
+pub struct UpdateLayerOperation {
+    #[serde(default)]
+    pub index: Option<usize>,
+    #[serde(default)]
+    pub visible: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Request data structure expected on the resize route
+pub struct ResizeOperation {
+    pub dimensions: canvas::Dimensions,
+    #[serde(default)]
+    pub anchor: resize::Anchor,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Request data structure expected on the crop route
+pub struct CropOperation {
+    pub position: canvas::Point,
+    pub dimensions: canvas::Dimensions,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Request data structure expected on the transform route
+/// Without dimensions the whole canvas is transformed
+pub struct TransformOperation {
+    pub transform: commands::Transform,
+    #[serde(default)]
+    pub position: Option<canvas::Point>,
+    #[serde(default)]
+    pub dimensions: Option<canvas::Dimensions>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+/// Represents a copy or cut request, storing a region in a named clipboard
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CopyOperation {
+    pub position: canvas::Point,
+    pub dimensions: canvas::Dimensions,
+    #[serde(default)]
+    pub clipboard: Option<String>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+/// Represents a paste request, drawing a named clipboard onto the canvas
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PasteOperation {
+    pub position: canvas::Point,
+    #[serde(default)]
+    pub clipboard: Option<String>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub blank_is_transparent: bool,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+/// Represents a request to store a stamp, given as rows of text
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreateStampOperation {
+    pub name: String,
+    pub rows: Vec<String>,
+    #[serde(default)]
+    pub transparent_character: Option<String>,
+}
+
+/// Represents a request to draw a stored stamp onto the canvas
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StampOperation {
+    pub stamp: String,
+    pub position: canvas::Point,
+    #[serde(default)]
+    pub transform: Option<commands::Transform>,
+    #[serde(default)]
+    pub blank_is_transparent: bool,
+    #[serde(default)]
+    pub foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+/// Represents a gradient fill request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GradientOperation {
+    pub position: canvas::Point,
+    pub dimensions: canvas::Dimensions,
+    pub direction: commands::GradientDirection,
+    #[serde(default)]
+    pub ramp: Option<String>, // characters from the start of the gradient to the end
+    #[serde(default)]
+    pub dither: bool,
+    #[serde(default)]
+    pub foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+/// Represents a request to restrict drawing to part of the canvas
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ClipOperation {
+    #[serde(default)]
+    pub regions: Vec<canvas::Region>,
+    #[serde(default)]
+    pub mask_layer: Option<String>,
+}
+
+/// Represents a request to lock a region of the canvas
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreateLockOperation {
+    pub name: String,
+    pub owner: String,
+    pub position: canvas::Point,
+    pub dimensions: canvas::Dimensions,
+    #[serde(default)]
+    pub mode: locks::LockMode,
+}
+
+/// Represents a request to draw a straight line
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LineOperation {
+    pub start: canvas::Point,
+    pub end: canvas::Point,
+    pub character: String,
+    #[serde(default)]
+    pub brush: commands::Brush,
+    #[serde(default)]
+    pub foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+/// Represents a request to outline the ellipse fitting a rectangle
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EllipseOperation {
+    pub position: canvas::Point,
+    pub dimensions: canvas::Dimensions,
+    pub character: String,
+    #[serde(default)]
+    pub brush: commands::Brush,
+    #[serde(default)]
+    pub foreground: Option<canvas::Colour>,
+    #[serde(default)]
+    pub background: Option<canvas::Colour>,
+    #[serde(default)]
+    pub layer: Option<String>,
+    #[serde(default)]
+    pub strict: Option<bool>,
 }
@@ -0,0 +1,170 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+use warp::http::StatusCode;
+
+use super::super::super::drawing_app::{application};
+use super::utils;
+use super::request;
+use super::validation;
+
+/// Handler for the resize route
+/// Grows or shrinks the canvas around the requested anchor and returns the result
+pub async fn handle_resize_request(
+    author: Option<String>,
+    request: request::ResizeOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let canvas = app
+        .write()
+        .resize(&request.dimensions, request.anchor, author.as_deref());
+
+    match canvas {
+        Ok(canvas) => Ok(warp::reply::with_status(canvas.to_string(), StatusCode::OK)),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
+
+/// Handler for the crop route
+/// Cuts the canvas down to the requested rectangle and returns the result
+pub async fn handle_crop_request(
+    author: Option<String>,
+    request: request::CropOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let canvas = app
+        .write()
+        .crop(&request.position, &request.dimensions, author.as_deref());
+
+    match canvas {
+        Ok(canvas) => Ok(warp::reply::with_status(canvas.to_string(), StatusCode::OK)),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::errors;
+    use super::super::super::super::drawing_app::{application, canvas, resize};
+
+    #[tokio::test]
+    async fn test_resize_and_crop_persist() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let options = application::ApplicationOptions {
+            width: 4,
+            height: 2,
+            blank_character: String::from("."),
+            canvas_path: canvas_location.clone(),
+            canvas_temp_path: temp_canvas_location.clone(),
+            canvas_snapshot_path: snapshot_canvas_location.clone(),
+            strict: false,
+        };
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(options.clone())));
+
+        handle_resize_request(
+            None,
+            request::ResizeOperation {
+                dimensions: canvas::Dimensions { width: 6, height: 3 },
+                anchor: resize::Anchor::Center,
+            },
+            app.clone(),
+        ).await.unwrap();
+
+        handle_crop_request(
+            None,
+            request::CropOperation {
+                position: canvas::Point { x: 1, y: 1 },
+                dimensions: canvas::Dimensions { width: 3, height: 2 },
+            },
+            app.clone(),
+        ).await.unwrap();
+
+        // a fresh application, as after a restart, picks up the stored dimensions
+        let restarted = application::DrawingApplication::initialize(options);
+        let actual = restarted.canvas().unwrap();
+
+        assert_eq!(3, actual.dimensions.width);
+        assert_eq!(2, actual.dimensions.height);
+        assert_eq!("...\n...\n", actual.to_string());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_resize() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 4,
+                height: 2,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let rejection = handle_resize_request(
+            None,
+            request::ResizeOperation {
+                dimensions: canvas::Dimensions { width: -1, height: 3 },
+                anchor: resize::Anchor::TopLeft,
+            },
+            app.clone(),
+        ).await.err().unwrap();
+
+        match rejection.find::<errors::InvalidFields>() {
+            Some(errors::InvalidFields(fields)) => assert_eq!("dimensions.width", fields[0].field),
+            None => panic!("expected an invalid dimensions.width"),
+        }
+
+        let rejection = handle_resize_request(
+            None,
+            request::ResizeOperation {
+                dimensions: canvas::Dimensions { width: 2, height: canvas::MAX_CANVAS_DIMENSION + 1 },
+                anchor: resize::Anchor::TopLeft,
+            },
+            app.clone(),
+        ).await.err().unwrap();
+
+        match rejection.find::<errors::InvalidFields>() {
+            Some(errors::InvalidFields(fields)) => assert_eq!("dimensions.height", fields[0].field),
+            None => panic!("expected an invalid dimensions.height"),
+        }
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, commands};
+use super::utils;
+use super::rate_limit;
+use super::request;
+use super::validation;
+
+/// Handler for the line route
+/// Turns the request into a line draw command, running from the start to the end point
+/// Attempts to draw to canvas and returns the result
+pub async fn handle_line_request(
+    client: rate_limit::Client,
+    request: request::LineOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let line_command = commands::DrawCommand {
+        name: commands::CommandName::Line,
+        position: request.start,
+        character: utils::canvas_character(&request.character),
+        foreground: request.foreground,
+        background: request.background,
+        layer: request.layer,
+        end: Some(request.end),
+        brush: request.brush,
+        strict: request.strict,
+        author: client.author.clone(),
+        ..Default::default()
+    };
+
+    utils::apply_draw_operation(vec!(line_command), app, client)
+}
+
+/// Handler for the ellipse route
+/// Turns the request into an ellipse outline draw command, fitting the given rectangle
+/// Attempts to draw to canvas and returns the result
+pub async fn handle_ellipse_request(
+    client: rate_limit::Client,
+    request: request::EllipseOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let ellipse_command = commands::DrawCommand {
+        name: commands::CommandName::OutlineEllipse,
+        position: request.position,
+        dimensions: Some(request.dimensions),
+        character: utils::canvas_character(&request.character),
+        foreground: request.foreground,
+        background: request.background,
+        layer: request.layer,
+        brush: request.brush,
+        strict: request.strict,
+        author: client.author.clone(),
+        ..Default::default()
+    };
+
+    utils::apply_draw_operation(vec!(ellipse_command), app, client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::errors;
+    use super::super::super::super::drawing_app::{application, canvas, commands};
+
+    #[tokio::test]
+    async fn test_handle_shape_requests() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 7,
+                height: 5,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let line = request::LineOperation {
+            start: canvas::Point { x: 0, y: 2 },
+            end: canvas::Point { x: 6, y: 2 },
+            character: String::from("-"),
+            brush: commands::Brush::default(),
+            foreground: None,
+            background: None,
+            layer: None,
+            strict: None,
+        };
+        handle_line_request(rate_limit::Client::default(), line, app.clone()).await.unwrap();
+
+        let ellipse = request::EllipseOperation {
+            position: canvas::Point { x: 1, y: 0 },
+            dimensions: canvas::Dimensions { width: 5, height: 5 },
+            character: String::from("o"),
+            brush: commands::Brush::default(),
+            foreground: None,
+            background: None,
+            layer: None,
+            strict: None,
+        };
+        handle_ellipse_request(rate_limit::Client::default(), ellipse.clone(), app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!("..ooo..\n.o...o.\n-o---o-\n.o...o.\n..ooo..\n", actual.to_string());
+
+        let too_thin = request::EllipseOperation {
+            brush: commands::Brush { width: 0, alignment: commands::BrushAlignment::Inward },
+            ..ellipse
+        };
+        let rejection = handle_ellipse_request(rate_limit::Client::default(), too_thin, app.clone()).await.err().unwrap();
+        match rejection.find::<errors::InvalidFields>() {
+            Some(errors::InvalidFields(fields)) => assert_eq!("brush.width", fields[0].field),
+            None => panic!("expected an invalid brush.width"),
+        }
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
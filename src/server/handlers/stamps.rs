@@ -0,0 +1,148 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, canvas, commands, stamps};
+use super::utils;
+use super::rate_limit;
+use super::request;
+use super::validation;
+
+/// Handler for listing the stamps
+/// Returns the name and size of each stored stamp
+pub async fn handle_list_stamps(
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let canvas = app.read().canvas();
+    reply_with_stamps(canvas)
+}
+
+/// Handler for storing a stamp
+/// Each row of text is split into grapheme clusters, any matching the transparent character become transparent
+pub async fn handle_create_stamp_request(
+    author: Option<String>,
+    request: request::CreateStampOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let transparent_character = request.transparent_character.as_deref().map(utils::canvas_character);
+    let pixels = stamps::stamp_pixels(&request.rows, transparent_character.as_deref());
+
+    let canvas = app.write().add_stamp(&request.name, pixels, author.as_deref());
+    reply_with_stamps(canvas)
+}
+
+/// Handler for the stamp route
+/// Turns the request into a stamp draw command
+/// Attempts to draw to canvas and returns the result
+pub async fn handle_stamp_request(
+    client: rate_limit::Client,
+    request: request::StampOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let stamp_command = commands::DrawCommand {
+        name: commands::CommandName::Stamp,
+        position: request.position,
+        foreground: request.foreground,
+        background: request.background,
+        layer: request.layer,
+        blank_is_transparent: request.blank_is_transparent,
+        stamp: Some(request.stamp),
+        transform: request.transform,
+        strict: request.strict,
+        author: client.author.clone(),
+        ..Default::default()
+    };
+
+    utils::apply_draw_operation(vec!(stamp_command), app, client)
+}
+
+fn reply_with_stamps(
+    canvas: std::io::Result<canvas::Canvas>,
+) -> Result<warp::reply::Json, warp::Rejection> {
+    match canvas {
+        Ok(canvas) => Ok(warp::reply::json(&canvas.stamp_info())),
+        Err(e) => Err(utils::reject_application_error(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::errors;
+    use super::super::super::super::drawing_app::{application, canvas, commands, stamps};
+
+    #[tokio::test]
+    async fn test_create_and_draw_stamps() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 5,
+                height: 3,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let create = request::CreateStampOperation {
+            name: String::from("corner"),
+            rows: vec![String::from("+-"), String::from("| ")],
+            transparent_character: Some(String::from(" ")),
+        };
+        handle_create_stamp_request(None, create, app.clone()).await.unwrap();
+
+        let stamp = request::StampOperation {
+            stamp: String::from("corner"),
+            position: canvas::Point { x: 3, y: 1 },
+            transform: Some(commands::Transform::FlipHorizontal),
+            blank_is_transparent: false,
+            foreground: None,
+            background: None,
+            layer: None,
+            strict: None,
+        };
+        handle_stamp_request(rate_limit::Client::default(), stamp, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!(".....\n...-+\n....|\n", actual.to_string());
+
+        let info = app.read().canvas().unwrap().stamp_info();
+        assert_eq!(1, info.len());
+        assert_eq!(2, info[0].dimensions.height);
+
+        // stamps which are too large are refused
+        let too_large = request::CreateStampOperation {
+            name: String::from("banner"),
+            rows: vec!["=".repeat(stamps::MAX_STAMP_SIZE + 1)],
+            transparent_character: None,
+        };
+        let rejection = handle_create_stamp_request(None, too_large, app.clone()).await.err().unwrap();
+        match rejection.find::<errors::InvalidFields>() {
+            Some(errors::InvalidFields(fields)) => assert_eq!("rows[0]", fields[0].field),
+            None => panic!("expected the stamp rows to be refused"),
+        }
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+use super::super::super::drawing_app::{application, canvas, commands};
+use super::utils;
+use super::rate_limit;
+use super::request;
+use super::validation;
+
+/// Handler for the transform route
+/// Turns the request into a flip, rotate or transpose draw command
+/// Attempts to draw to canvas and returns the result
+pub async fn handle_transform_request(
+    client: rate_limit::Client,
+    request: request::TransformOperation,
+    app: Arc<RwLock<application::DrawingApplication>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validation::validate(&request)?;
+    let transform_command = commands::DrawCommand {
+        name: request.transform.command_name(),
+        position: request.position.unwrap_or(canvas::Point { x: 0, y: 0 }),
+        dimensions: request.dimensions,
+        layer: request.layer,
+        strict: request.strict,
+        author: client.author.clone(),
+        ..Default::default()
+    };
+
+    utils::apply_draw_operation(vec!(transform_command), app, client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::draw_rectangle;
+    use super::super::super::super::drawing_app::{application, canvas};
+
+    #[tokio::test]
+    async fn test_handle_transform_request() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 5,
+                height: 3,
+                blank_character: String::from(" "),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let rectangle = request::DrawRectangleOperation {
+            position: canvas::Point {
+                x: 0,
+                y: 0
+            },
+            dimensions: canvas::Dimensions {
+                width: 2,
+                height: 1,
+            },
+            fill_character: String::from("X"),
+            outline_character: String::from("none"),
+            fill_foreground: None,
+            fill_background: None,
+            outline_foreground: None,
+            outline_background: None,
+            layer: None,
+            fill_pattern: None,
+            outline_brush: commands::Brush::default(),
+            strict: None,
+        };
+
+        draw_rectangle::handle_draw_rectangle_request(rate_limit::Client::default(), rectangle, app.clone()).await.unwrap();
+
+        let flip_region = request::TransformOperation {
+            transform: commands::Transform::FlipHorizontal,
+            position: Some(canvas::Point { x: 0, y: 0 }),
+            dimensions: Some(canvas::Dimensions { width: 3, height: 1 }),
+            layer: None,
+            strict: None,
+        };
+        handle_transform_request(rate_limit::Client::default(), flip_region, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!(" XX  \n     \n     \n", actual.to_string());
+
+        let rotate_canvas = request::TransformOperation {
+            transform: commands::Transform::Rotate90,
+            position: None,
+            dimensions: None,
+            layer: None,
+            strict: None,
+        };
+        handle_transform_request(rate_limit::Client::default(), rotate_canvas, app.clone()).await.unwrap();
+
+        let actual = app.read().canvas().unwrap();
+        assert_eq!("   \n  X\n  X\n   \n   \n", actual.to_string());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
+}
@@ -1,43 +1,87 @@
+use std::io;
 use std::sync::Arc;
 use warp::{Filter};
 use parking_lot::RwLock;
 use warp::http::StatusCode;
 
-use super::super::super::drawing_app::{application, canvas, commands};
+use super::super::super::drawing_app::{application, canvas, clip, commands, draw_error, layers, locks, resize, stamps, storage};
 use super::errors;
+use super::rate_limit;
 use super::request;
+use super::validation;
 
 /// Attempts to apply draw operation to the canvas
+/// The cells it changes are counted towards the client's quota
 /// Returns a Result type
 pub fn apply_draw_operation(
     command: Vec<commands::DrawCommand>,
     app: Arc<RwLock<application::DrawingApplication>>,
+    client: rate_limit::Client,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let canvas = app
+    let drawn = app
         .write()
         .draw(command);
 
-    match canvas {
-        Ok(canvas) => {
+    match drawn {
+        Ok((canvas, cells_changed)) => {
+            client.record(cells_changed);
             Ok(warp::reply::with_status(
                 canvas.to_string(),
                 StatusCode::OK,
             ))
         },
-        Err(_) => Err(warp::reject::custom(errors::ApplyOperationError))
+        Err(e) => Err(reject_draw_error(e))
     }
 }
 
+/// Converts a draw command which couldn't be applied into a rejection
+/// Commands refused by a lock are rejected like any other lock error, so the lock is reported
+pub fn reject_draw_error(error: draw_error::DrawError) -> warp::Rejection {
+    match error {
+        draw_error::DrawError::Locked(lock_error) => warp::reject::custom(errors::InvalidLockOperation(lock_error)),
+        draw_error::DrawError::DamagedCanvas(damaged) => warp::reject::custom(errors::DamagedCanvas(damaged)),
+        error => warp::reject::custom(errors::InvalidDrawOperation(error)),
+    }
+}
+
+/// Converts a failure from the drawing application into a rejection
+/// Layer, resize, stamp, clip, lock and draw errors are caused by the request, a damaged canvas file is reported as such
+/// and anything else is a storage failure
+pub fn reject_application_error(error: io::Error) -> warp::Rejection {
+    if let Some(inner) = error.get_ref() {
+        if let Some(layer_error) = inner.downcast_ref::<layers::LayerError>() {
+            return warp::reject::custom(errors::InvalidLayerOperation(layer_error.clone()));
+        }
+        if let Some(resize_error) = inner.downcast_ref::<resize::ResizeError>() {
+            return warp::reject::custom(errors::InvalidResizeOperation(resize_error.clone()));
+        }
+        if let Some(stamp_error) = inner.downcast_ref::<stamps::StampError>() {
+            return warp::reject::custom(errors::InvalidStampOperation(stamp_error.clone()));
+        }
+        if let Some(clip_error) = inner.downcast_ref::<clip::ClipError>() {
+            return warp::reject::custom(errors::InvalidClipOperation(clip_error.clone()));
+        }
+        if let Some(lock_error) = inner.downcast_ref::<locks::LockError>() {
+            return warp::reject::custom(errors::InvalidLockOperation(lock_error.clone()));
+        }
+        if let Some(damaged) = inner.downcast_ref::<storage::DamagedCanvasFile>() {
+            return warp::reject::custom(errors::DamagedCanvas(damaged.clone()));
+        }
+    }
+
+    reject_draw_error(draw_error::DrawError::from(error))
+}
+
 /// Check that the body is JSON and marshalls into correct draw rectangle request format
-/// Rejects big payloads
+/// Rejects big payloads, and requests with fields which can't be read
 pub fn parse_draw_rectangle_request() -> impl Filter<Extract = (request::DrawRectangleOperation,), Error = warp::Rejection> + Clone {
-    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+    validation::json_body()
 }
 
 /// Check that the body is JSON and marshalls into correct flood fill request format
-/// Rejects big payloads
+/// Rejects big payloads, and requests with fields which can't be read
 pub fn parse_flood_fill_request() -> impl Filter<Extract = (request::FloodFillOperation,), Error = warp::Rejection> + Clone {
-    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+    validation::json_body()
 }
 
 /// Parse the operation's outline or fill character
@@ -46,32 +90,129 @@ pub fn field_is_not_none(field: &str) -> bool {
     field.to_ascii_lowercase() != request::NONE_CHARACTER
 }
 
-/// Parse the operation's outline or fill character
-/// Checks that it is a single valid unicode character
-pub fn valid_character(field: &str) -> Result<char, warp::Rejection> {
-    match field.chars().take(1).next() {
-        Some(character) => {
-            if field.chars().count() == 1 {
-                return Ok(character);
-            } 
-            
-            return Err(warp::reject::custom(super::errors::StringTooLong));
-        },
-        _ => Err(warp::reject::custom(super::errors::StringTooLong)),
+/// Check that the body is JSON and marshalls into correct create layer request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_create_layer_request() -> impl Filter<Extract = (request::CreateLayerOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct update layer request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_update_layer_request() -> impl Filter<Extract = (request::UpdateLayerOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct resize request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_resize_request() -> impl Filter<Extract = (request::ResizeOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct crop request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_crop_request() -> impl Filter<Extract = (request::CropOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct transform request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_transform_request() -> impl Filter<Extract = (request::TransformOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct copy or cut request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_copy_request() -> impl Filter<Extract = (request::CopyOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct paste request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_paste_request() -> impl Filter<Extract = (request::PasteOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct create stamp request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_create_stamp_request() -> impl Filter<Extract = (request::CreateStampOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct stamp request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_stamp_request() -> impl Filter<Extract = (request::StampOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct gradient request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_gradient_request() -> impl Filter<Extract = (request::GradientOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct clip request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_clip_request() -> impl Filter<Extract = (request::ClipOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct create lock request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_create_lock_request() -> impl Filter<Extract = (request::CreateLockOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct line request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_line_request() -> impl Filter<Extract = (request::LineOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Check that the body is JSON and marshalls into correct ellipse request format
+/// Rejects big payloads, and requests with fields which can't be read
+pub fn parse_ellipse_request() -> impl Filter<Extract = (request::EllipseOperation,), Error = warp::Rejection> + Clone {
+    validation::json_body()
+}
+
+/// Converts a validated outline or fill character into the character drawn on the canvas
+/// The transparent keyword becomes the transparent character
+pub fn canvas_character(field: &str) -> String {
+    if field.to_ascii_lowercase() == request::TRANSPARENT_CHARACTER {
+        return String::from(canvas::TRANSPARENT_CHARACTER);
     }
+    String::from(field)
+}
+
+/// Converts a validated fill pattern's characters into the characters drawn on the canvas
+pub fn canvas_pattern(pattern: Option<commands::Pattern>) -> Option<commands::Pattern> {
+    pattern.map(|pattern| match pattern {
+        commands::Pattern::Checkerboard { other_character } => commands::Pattern::Checkerboard {
+            other_character: canvas_character(&other_character),
+        },
+        commands::Pattern::Dither { other_character, level } => commands::Pattern::Dither {
+            other_character: canvas_character(&other_character),
+            level,
+        },
+        pattern => pattern,
+    })
 }
 
-/// Constructs a valid HTML element out of the canvas
+/// Constructs a valid HTML element out of the visible layers of the canvas
+/// Coloured pixels are wrapped in a span with inline styles
+/// Narrow glyphs are padded like the plain text canvas, so rows line up alongside wide ones
 pub fn convert_canvas_to_html(canvas: &canvas::Canvas) -> String { 
-    let canvas_element = canvas.pixels
+    let pixels = canvas.composite();
+    let column_width = canvas::column_width(&pixels);
+    let canvas_element = pixels
         .iter()
         .map(|row| {
             let mut html_string = String::from("<div>");
-            let pixels: String = String::from(row
+            let pixels: String = row
                 .iter()
-                .collect::<String>());
+                .map(|cell| convert_cell_to_html(cell, column_width))
+                .collect::<String>();
             html_string.push_str(&pixels);
-            html_string.push_str(&String::from("</div>"));
+            html_string.push_str("</div>");
             html_string
         })
         .fold(String::new(), |a, b| a + &b + "\n");
@@ -79,6 +220,23 @@ pub fn convert_canvas_to_html(canvas: &canvas::Canvas) -> String {
     format!("{}{}{}","<div class=\"canvas\">", canvas_element, "</div>")
 }
 
+/// Renders a single pixel, adding colour styles where the cell has them
+fn convert_cell_to_html(cell: &canvas::Cell, column_width: usize) -> String {
+    if !cell.is_coloured() {
+        return cell.padded(column_width);
+    }
+
+    let mut style = String::new();
+    if let Some(foreground) = cell.foreground {
+        style.push_str(&format!("color:{};", foreground.to_hex()));
+    }
+    if let Some(background) = cell.background {
+        style.push_str(&format!("background-color:{};", background.to_hex()));
+    }
+
+    format!("<span style=\"{}\">{}</span>", style, cell.padded(column_width))
+}
+
 /// Constructs a basic HTML document containing the canvas
 /// Contains basic web socket initialisation scripts 
 pub fn construct_html_with_canvas(canvas: &canvas::Canvas) -> String {
@@ -87,6 +245,7 @@ pub fn construct_html_with_canvas(canvas: &canvas::Canvas) -> String {
         <html lang=\"en\">
             <head>
                 <title>Rusty Canvas!</title>
+                <style>.canvas { font-family: monospace; white-space: pre; }</style>
             </head>
         <body>"
     );
@@ -94,4 +253,50 @@ pub fn construct_html_with_canvas(canvas: &canvas::Canvas) -> String {
     let end_tags = String::from("</body></html>");
 
     format!("{}{}{}", start_tags, canvas_element, end_tags)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_canvas_to_html_with_colours() {
+        let mut canvas = canvas::Canvas::blank_canvas(3, 1, ".");
+        canvas.layers[0].pixels[0][1] = canvas::Cell {
+            character: String::from("#"),
+            foreground: Some(canvas::Colour::Rgb(255, 0, 0)),
+            background: Some(canvas::Colour::Palette(16)),
+        };
+
+        let expected = "<div class=\"canvas\"><div>.<span style=\"color:#ff0000;background-color:#000000;\">#</span>.</div>\n</div>";
+
+        assert_eq!(expected, convert_canvas_to_html(&canvas));
+    }
+
+    #[test]
+    fn test_convert_canvas_to_html_composites_layers() {
+        let mut canvas = canvas::Canvas::blank_canvas(3, 1, ".");
+        canvas.add_layer("top").unwrap();
+        canvas.layers[1].pixels[0][2] = canvas::Cell::from("T");
+
+        let expected = "<div class=\"canvas\"><div>..T</div>\n</div>";
+
+        assert_eq!(expected, convert_canvas_to_html(&canvas));
+    }
+
+    #[test]
+    fn test_convert_canvas_to_html_pads_narrow_glyphs() {
+        let mut canvas = canvas::Canvas::blank_canvas(3, 1, ".");
+        canvas.layers[0].pixels[0][1] = canvas::Cell::from("中");
+
+        let expected = "<div class=\"canvas\"><div>. 中. </div>\n</div>";
+
+        assert_eq!(expected, convert_canvas_to_html(&canvas));
+    }
+
+    #[test]
+    fn test_transparent_keyword() {
+        assert_eq!(canvas::TRANSPARENT_CHARACTER, canvas_character("Transparent"));
+        assert_eq!("#", canvas_character("#"));
+    }
+}
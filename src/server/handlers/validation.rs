@@ -0,0 +1,536 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use warp::Filter;
+
+use super::super::super::drawing_app::{canvas, commands, stamps};
+use super::errors;
+use super::request;
+
+/// A field of a request which couldn't be accepted, with the value that was sent and why it was refused
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String, // path to the field, such as dimensions.width or fill_pattern.rows[2]
+    pub value: Value,
+    pub reason: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.reason)
+    }
+}
+
+/// Collects every invalid field of a request, so they can all be reported together
+#[derive(Debug, Default)]
+pub struct FieldErrors {
+    errors: Vec<FieldError>,
+}
+
+impl FieldErrors {
+    /// Records that a field was refused
+    pub fn add<V: Serialize>(&mut self, field: &str, value: V, reason: &str) {
+        self.errors.push(FieldError {
+            field: String::from(field),
+            value: serde_json::to_value(value).unwrap_or(Value::Null),
+            reason: String::from(reason),
+        });
+    }
+
+    /// Checks a character is a single grapheme cluster or the transparent keyword
+    /// With `none_allowed` the none keyword, which skips that part of the shape, is allowed too
+    pub fn character(&mut self, field: &str, value: &str, none_allowed: bool) {
+        if none_allowed && value.to_ascii_lowercase() == request::NONE_CHARACTER {
+            return;
+        }
+        if value.to_ascii_lowercase() == request::TRANSPARENT_CHARACTER || canvas::graphemes(value).len() == 1 {
+            return;
+        }
+
+        let reason = if none_allowed {
+            "must be one grapheme, \"transparent\" or \"none\""
+        } else {
+            "must be one grapheme or \"transparent\""
+        };
+        self.add(field, value, reason);
+    }
+
+    /// Checks neither side of a shape is negative, shapes of size 0 draw nothing
+    pub fn dimensions(&mut self, field: &str, dimensions: &canvas::Dimensions) {
+        if dimensions.width < 0 {
+            self.add(&format!("{}.width", field), dimensions.width, "must be >= 0");
+        }
+        if dimensions.height < 0 {
+            self.add(&format!("{}.height", field), dimensions.height, "must be >= 0");
+        }
+    }
+
+    /// Checks both sides of a canvas size are above 0 and at most `MAX_CANVAS_DIMENSION`
+    pub fn canvas_dimensions(&mut self, field: &str, dimensions: &canvas::Dimensions) {
+        let reason = format!("must be between 1 and {}", canvas::MAX_CANVAS_DIMENSION);
+        if dimensions.width <= 0 || dimensions.width > canvas::MAX_CANVAS_DIMENSION {
+            self.add(&format!("{}.width", field), dimensions.width, &reason);
+        }
+        if dimensions.height <= 0 || dimensions.height > canvas::MAX_CANVAS_DIMENSION {
+            self.add(&format!("{}.height", field), dimensions.height, &reason);
+        }
+    }
+
+    /// Checks a brush is at least one and at most `MAX_BRUSH_WIDTH` cells wide
+    pub fn brush(&mut self, field: &str, brush: &commands::Brush) {
+        if brush.width < 1 || brush.width > commands::MAX_BRUSH_WIDTH {
+            let reason = format!("must be between 1 and {}", commands::MAX_BRUSH_WIDTH);
+            self.add(&format!("{}.width", field), brush.width, &reason);
+        }
+    }
+
+    /// Checks a fill pattern's characters, size and level
+    pub fn pattern(&mut self, field: &str, pattern: &Option<commands::Pattern>) {
+        match pattern {
+            Some(commands::Pattern::Tile { rows }) => {
+                if rows.is_empty() || rows.len() > commands::MAX_TILE_SIZE {
+                    let reason = format!("must have between 1 and {} rows", commands::MAX_TILE_SIZE);
+                    self.add(&format!("{}.rows", field), rows, &reason);
+                }
+                for (index, row) in rows.iter().enumerate() {
+                    let width = canvas::graphemes(row).len();
+                    if width == 0 || width > commands::MAX_TILE_SIZE {
+                        let reason = format!("must be between 1 and {} characters", commands::MAX_TILE_SIZE);
+                        self.add(&format!("{}.rows[{}]", field, index), row, &reason);
+                    }
+                }
+            },
+            Some(commands::Pattern::Checkerboard { other_character }) => {
+                self.character(&format!("{}.other_character", field), other_character, false);
+            },
+            Some(commands::Pattern::Dither { other_character, level }) => {
+                self.character(&format!("{}.other_character", field), other_character, false);
+                if *level > commands::DITHER_LEVELS {
+                    let reason = format!("must be between 0 and {}", commands::DITHER_LEVELS);
+                    self.add(&format!("{}.level", field), level, &reason);
+                }
+            },
+            None => {},
+        }
+    }
+
+    /// Checks a name isn't empty
+    pub fn name(&mut self, field: &str, value: &str) {
+        if value.is_empty() {
+            self.add(field, value, "must not be empty");
+        }
+    }
+
+    /// Rejects the request if any field was refused
+    pub fn finish(self) -> Result<(), warp::Rejection> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(warp::reject::custom(errors::InvalidFields(self.errors)))
+    }
+}
+
+/// Requests which can check their own fields, beyond what deserialising them already checks
+pub trait Validate {
+    fn validate(&self, errors: &mut FieldErrors);
+}
+
+/// Checks every field of a request, rejecting it with all of the invalid fields at once
+pub fn validate<T: Validate>(request: &T) -> Result<(), warp::Rejection> {
+    let mut errors = FieldErrors::default();
+    request.validate(&mut errors);
+    errors.finish()
+}
+
+impl Validate for request::DrawRectangleOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.dimensions("dimensions", &self.dimensions);
+        errors.character("fill_character", &self.fill_character, true);
+        errors.character("outline_character", &self.outline_character, true);
+        errors.pattern("fill_pattern", &self.fill_pattern);
+        errors.brush("outline_brush", &self.outline_brush);
+    }
+}
+
+impl Validate for request::FloodFillOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.character("fill_character", &self.fill_character, true);
+        errors.pattern("fill_pattern", &self.fill_pattern);
+        match &self.boundary_character {
+            Some(character) => errors.character("boundary_character", character, false),
+            None if self.mode == commands::FillMode::Boundary => {
+                errors.add("boundary_character", Value::Null, "is required when mode is \"boundary\"");
+            },
+            None => {},
+        }
+        if let Some(clip) = &self.clip {
+            errors.dimensions("clip.dimensions", &clip.dimensions);
+        }
+    }
+}
+
+impl Validate for request::TransformOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if let Some(dimensions) = &self.dimensions {
+            errors.dimensions("dimensions", dimensions);
+        }
+    }
+}
+
+impl Validate for request::ResizeOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.canvas_dimensions("dimensions", &self.dimensions);
+    }
+}
+
+impl Validate for request::CropOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.canvas_dimensions("dimensions", &self.dimensions);
+    }
+}
+
+impl Validate for request::CopyOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.dimensions("dimensions", &self.dimensions);
+    }
+}
+
+impl Validate for request::CreateLayerOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.name("name", &self.name);
+    }
+}
+
+impl Validate for request::UpdateLayerOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if self.index.is_none() && self.visible.is_none() {
+            errors.add("body", self, "must set index, visible or both");
+        }
+    }
+}
+
+impl Validate for request::PasteOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        if let Some(clipboard) = &self.clipboard {
+            errors.name("clipboard", clipboard);
+        }
+        if let Some(layer) = &self.layer {
+            errors.name("layer", layer);
+        }
+    }
+}
+
+impl Validate for request::StampOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.name("stamp", &self.stamp);
+        if let Some(layer) = &self.layer {
+            errors.name("layer", layer);
+        }
+    }
+}
+
+impl Validate for request::CreateStampOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.name("name", &self.name);
+        if self.rows.first().is_none_or(|row| row.is_empty()) {
+            errors.add("rows", &self.rows, "must start with a row of at least one character");
+        }
+        if self.rows.len() > stamps::MAX_STAMP_SIZE {
+            errors.add("rows", &self.rows, &format!("must have at most {} rows", stamps::MAX_STAMP_SIZE));
+        }
+        for (index, row) in self.rows.iter().enumerate() {
+            if canvas::graphemes(row).len() > stamps::MAX_STAMP_SIZE {
+                let reason = format!("must be at most {} characters", stamps::MAX_STAMP_SIZE);
+                errors.add(&format!("rows[{}]", index), row, &reason);
+            }
+        }
+        if let Some(character) = &self.transparent_character {
+            errors.character("transparent_character", character, false);
+        }
+    }
+}
+
+impl Validate for request::GradientOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.dimensions("dimensions", &self.dimensions);
+        if let Some(ramp) = &self.ramp {
+            if ramp.is_empty() {
+                errors.add("ramp", ramp, "must have at least one character");
+            }
+        }
+    }
+}
+
+impl Validate for request::ClipOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        for (index, region) in self.regions.iter().enumerate() {
+            errors.dimensions(&format!("regions[{}].dimensions", index), &region.dimensions);
+        }
+    }
+}
+
+impl Validate for request::CreateLockOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.name("name", &self.name);
+        errors.name("owner", &self.owner);
+        errors.dimensions("dimensions", &self.dimensions);
+    }
+}
+
+impl Validate for request::LineOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.character("character", &self.character, false);
+        errors.brush("brush", &self.brush);
+    }
+}
+
+impl Validate for request::EllipseOperation {
+    fn validate(&self, errors: &mut FieldErrors) {
+        errors.dimensions("dimensions", &self.dimensions);
+        errors.character("character", &self.character, false);
+        errors.brush("brush", &self.brush);
+    }
+}
+
+/// Check that the body is JSON and marshalls into the request format
+/// Rejects big payloads, and reports which field couldn't be read
+pub fn json_body<T>() -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+where
+    T: DeserializeOwned + Send,
+{
+    warp::body::content_length_limit(1024 * 16)
+        .and(warp::body::bytes())
+        .and_then(|body: warp::hyper::body::Bytes| async move { deserialise(&body) })
+}
+
+/// Reads a request from JSON, turning a serde error into the field it happened at
+pub fn deserialise<T: DeserializeOwned>(body: &[u8]) -> Result<T, warp::Rejection> {
+    let reject = |field_error| warp::reject::custom(errors::InvalidFields(vec!(field_error)));
+
+    let mut deserialiser = serde_json::Deserializer::from_slice(body);
+    let request = serde_path_to_error::deserialize(&mut deserialiser)
+        .map_err(|error| reject(field_error(body, &error)))?;
+    deserialiser.end().map_err(|error| reject(syntax_error(&error)))?;
+    Ok(request)
+}
+
+/// Describes where a request couldn't be read, and the value found there
+fn field_error(body: &[u8], error: &serde_path_to_error::Error<serde_json::Error>) -> FieldError {
+    let inner = error.inner();
+    if inner.is_syntax() || inner.is_eof() {
+        return syntax_error(inner);
+    }
+
+    let mut path: Vec<String> = error.path().iter().map(|segment| match segment {
+        Segment::Seq { index } => format!("[{}]", index),
+        Segment::Map { key } => key.clone(),
+        Segment::Enum { variant } => variant.clone(),
+        Segment::Unknown => String::from("?"),
+    }).collect();
+
+    // serde reports a missing field against the structure it was missing from
+    let message = serde_message(inner);
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.strip_suffix('`'));
+
+    match missing {
+        Some(field) => {
+            path.push(String::from(field));
+            FieldError { field: join_path(&path), value: Value::Null, reason: String::from("is required") }
+        },
+        None => FieldError { field: join_path(&path), value: value_at(body, error.path()), reason: serde_reason(&message) },
+    }
+}
+
+/// Rewords serde's "invalid type: string "top", expected i32" as "must be i32", as the value is reported alongside
+fn serde_reason(message: &str) -> String {
+    match message.rsplit_once(", expected ") {
+        Some((_, expected)) => format!("must be {}", expected),
+        None => String::from(message),
+    }
+}
+
+/// Describes a body which isn't JSON at all
+fn syntax_error(error: &serde_json::Error) -> FieldError {
+    FieldError {
+        field: String::from("body"),
+        value: Value::Null,
+        reason: format!("must be valid JSON ({})", serde_message(error)),
+    }
+}
+
+/// A serde error's message, without the line and column it happened at
+fn serde_message(error: &serde_json::Error) -> String {
+    let location = format!(" at line {} column {}", error.line(), error.column());
+    let message = error.to_string();
+    match message.strip_suffix(&location) {
+        Some(message) => String::from(message),
+        None => message,
+    }
+}
+
+/// Finds the value sent at a path of the request body, if the body is valid JSON
+fn value_at(body: &[u8], path: &serde_path_to_error::Path) -> Value {
+    let mut value = match serde_json::from_slice::<Value>(body) {
+        Ok(value) => value,
+        Err(_) => return Value::Null,
+    };
+    for segment in path.iter() {
+        let next = match segment {
+            Segment::Seq { index } => value.get_mut(*index).map(Value::take),
+            Segment::Map { key } => value.get_mut(key).map(Value::take),
+            _ => None,
+        };
+        value = match next {
+            Some(next) => next,
+            None => return Value::Null,
+        };
+    }
+    value
+}
+
+/// Joins the parts of a field's path with dots, keeping list indexes next to their list
+fn join_path(path: &[String]) -> String {
+    let mut joined = String::new();
+    for part in path {
+        if !joined.is_empty() && !part.starts_with('[') {
+            joined.push('.');
+        }
+        joined.push_str(part);
+    }
+    if joined.is_empty() {
+        joined.push_str("body");
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_errors<T: Validate + DeserializeOwned>(body: &str) -> Vec<FieldError> {
+        let rejection = match deserialise::<T>(body.as_bytes()) {
+            Ok(request) => match validate(&request) {
+                Ok(_) => return vec!(),
+                Err(rejection) => rejection,
+            },
+            Err(rejection) => rejection,
+        };
+        match rejection.find::<errors::InvalidFields>() {
+            Some(errors::InvalidFields(fields)) => fields.clone(),
+            None => panic!("expected invalid fields"),
+        }
+    }
+
+    fn field_error(field: &str, value: Value, reason: &str) -> FieldError {
+        FieldError { field: String::from(field), value, reason: String::from(reason) }
+    }
+
+    #[test]
+    fn test_characters_are_single_grapheme_clusters() {
+        let mut errors = FieldErrors::default();
+        for character in ["👩‍💻", "🇬🇧", "👍🏽", "e\u{301}", "Transparent"].iter() {
+            errors.character("character", character, false);
+        }
+        errors.character("character", "none", true);
+        assert!(errors.errors.is_empty());
+
+        for character in ["ab", "🇬🇧🇫🇷", "", "none"].iter() {
+            errors.character("character", character, false);
+        }
+        assert_eq!(4, errors.errors.len());
+    }
+
+    #[test]
+    fn test_every_invalid_field_is_reported() {
+        let errors = field_errors::<request::DrawRectangleOperation>(r##"{
+            "position": { "x": 0, "y": 0 },
+            "dimensions": { "width": -2, "height": 3 },
+            "fill_character": "ab",
+            "outline_character": "none",
+            "fill_pattern": { "type": "dither", "other_character": "#", "level": 20 },
+            "outline_brush": { "width": 0 }
+        }"##);
+
+        assert_eq!(vec!(
+            field_error("dimensions.width", Value::from(-2), "must be >= 0"),
+            field_error("fill_character", Value::from("ab"), "must be one grapheme, \"transparent\" or \"none\""),
+            field_error("fill_pattern.level", Value::from(20), "must be between 0 and 16"),
+            field_error("outline_brush.width", Value::from(0), "must be between 1 and 32"),
+        ), errors);
+    }
+
+    #[test]
+    fn test_serde_errors_name_the_field() {
+        let wrong_type = field_errors::<request::EllipseOperation>(r#"{
+            "position": { "x": 0, "y": 0 },
+            "dimensions": { "width": "wide", "height": 3 },
+            "character": "o"
+        }"#);
+        assert_eq!(vec!(field_error(
+            "dimensions.width",
+            Value::from("wide"),
+            "must be i32",
+        )), wrong_type);
+
+        let unknown = field_errors::<request::TransformOperation>(r#"{ "transform": "rotate_45" }"#);
+        assert_eq!("transform", unknown[0].field);
+        assert_eq!(Value::from("rotate_45"), unknown[0].value);
+        assert!(unknown[0].reason.starts_with("must be one of `flip_horizontal`"));
+
+        let missing = field_errors::<request::EllipseOperation>(r#"{ "position": { "x": 0, "y": 0 }, "character": "o" }"#);
+        assert_eq!(vec!(field_error("dimensions", Value::Null, "is required")), missing);
+
+        let nested = field_errors::<request::ClipOperation>(r#"{ "regions": [{ "position": { "x": 0, "y": 0 }, "dimensions": { "width": 1, "height": -1 } }] }"#);
+        assert_eq!(vec!(field_error("regions[0].dimensions.height", Value::from(-1), "must be >= 0")), nested);
+
+        let malformed = field_errors::<request::ClipOperation>(r#"{ "regions": "#);
+        assert_eq!("body", malformed[0].field);
+    }
+
+    #[test]
+    fn test_layer_stamp_and_paste_requests_are_checked() {
+        let unnamed = field_errors::<request::CreateLayerOperation>(r#"{ "name": "" }"#);
+        assert_eq!(vec!(field_error("name", Value::from(""), "must not be empty")), unnamed);
+
+        let unchanged = field_errors::<request::UpdateLayerOperation>(r#"{}"#);
+        assert_eq!(vec!(field_error(
+            "body",
+            serde_json::json!({ "index": null, "visible": null }),
+            "must set index, visible or both",
+        )), unchanged);
+        assert!(field_errors::<request::UpdateLayerOperation>(r#"{ "visible": false }"#).is_empty());
+
+        let stamp = field_errors::<request::StampOperation>(r#"{ "stamp": "", "position": { "x": 0, "y": 0 }, "layer": "" }"#);
+        assert_eq!(vec!(
+            field_error("stamp", Value::from(""), "must not be empty"),
+            field_error("layer", Value::from(""), "must not be empty"),
+        ), stamp);
+
+        let paste = field_errors::<request::PasteOperation>(r#"{ "position": { "x": 0, "y": 0 }, "clipboard": "" }"#);
+        assert_eq!(vec!(field_error("clipboard", Value::from(""), "must not be empty")), paste);
+        assert!(field_errors::<request::PasteOperation>(r#"{ "position": { "x": 0, "y": 0 } }"#).is_empty());
+    }
+
+    #[test]
+    fn test_valid_requests_are_accepted() {
+        let errors = field_errors::<request::FloodFillOperation>(r#"{
+            "position": { "x": 0, "y": 0 },
+            "fill_character": "👩‍💻",
+            "mode": "boundary",
+            "boundary_character": "Transparent"
+        }"#);
+        assert!(errors.is_empty());
+
+        let missing_boundary = field_errors::<request::FloodFillOperation>(r#"{
+            "position": { "x": 0, "y": 0 },
+            "fill_character": "é",
+            "mode": "boundary"
+        }"#);
+        assert_eq!(vec!(field_error("boundary_character", Value::Null, "is required when mode is \"boundary\"")), missing_boundary);
+    }
+}
@@ -3,7 +3,6 @@ use std::sync::Arc;
 use warp::http::StatusCode;
 
 use super::super::super::drawing_app::{application};
-use super::errors;
 use super::utils;
 
 /// Skeleton for web socker handler
@@ -12,7 +11,7 @@ pub async fn ws_handler(
     _ws: warp::ws::Ws, 
     app: Arc<RwLock<application::DrawingApplication>>
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let canvas = app.write().draw(vec!());
+    let canvas = app.read().canvas();
     match canvas {
         Ok(canvas) => {
             let html_string = utils::construct_html_with_canvas(&canvas);
@@ -21,6 +20,6 @@ pub async fn ws_handler(
                 StatusCode::SWITCHING_PROTOCOLS,
             ))
         },
-        Err(_) => Err(warp::reject::custom(errors::ApplyOperationError))
+        Err(e) => Err(utils::reject_application_error(e))
     }
 }
\ No newline at end of file
@@ -1,44 +1,378 @@
 use parking_lot::RwLock;
+use std::error::Error;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
 use std::sync::Arc;
+use tokio::net::UnixListener;
+use tokio::signal::unix::{signal, SignalKind};
 use warp::{Filter};
 
+use super::super::config::{ApiKey, Listen, RateLimits, Role};
 use super::super::drawing_app::{application};
 use super::handlers;
 
-pub async fn run(app: Arc<RwLock<application::DrawingApplication>>) {
+/// Serves the application until the shutdown future completes
+/// With API keys, every route needs a key with a role allowed to use it
+/// Draw routes are also rate limited for each client, by API key or else by address, once their body has been checked
+/// Once shutdown starts no new connections are accepted, but requests in progress are finished
+pub async fn run<S>(
+    app: Arc<RwLock<application::DrawingApplication>>,
+    listen: Listen,
+    api_keys: Vec<ApiKey>,
+    rate_limits: RateLimits,
+    shutdown: S,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    S: Future<Output = ()> + Send + 'static,
+{
     let app_filter = warp::any().map(move || app.clone());
 
+    // each route checks the request's API key before reading its body
+    let api_keys = Arc::new(api_keys);
+    let view = handlers::auth::allow(Role::Viewer, api_keys.clone());
+    let limiter = Arc::new(handlers::rate_limit::RateLimiter::new(rate_limits));
+    let drawer = handlers::auth::require(Role::Drawer, api_keys.clone()).and(warp::addr::remote());
+    let change = handlers::auth::require(Role::Drawer, api_keys.clone());
+    let admin = handlers::auth::require(Role::Admin, api_keys);
+
     let get_canvas = warp::get()
         .and(warp::path::end())
+        .and(view.clone())
         .and(app_filter.clone())
         .and_then(handlers::get_canvas::handle_get_canvas);
 
+    let get_canvas_ansi = warp::get()
+        .and(warp::path("ansi"))
+        .and(warp::path::end())
+        .and(view.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::get_canvas::handle_get_canvas_ansi);
+
     let fill_rectangle = warp::post()
         .and(warp::path("drawrectangle"))
         .and(warp::path::end())
-        .and(handlers::utils::parse_draw_rectangle_request())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_draw_rectangle_request()))
         .and(app_filter.clone())
         .and_then(handlers::draw_rectangle::handle_draw_rectangle_request);
 
     let flood_fill = warp::post()
         .and(warp::path("floodfill"))
         .and(warp::path::end())
-        .and(handlers::utils::parse_flood_fill_request())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_flood_fill_request()))
         .and(app_filter.clone())
         .and_then(handlers::flood_fill::handle_flood_fill_request);
 
+    let line = warp::post()
+        .and(warp::path("line"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_line_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::shapes::handle_line_request);
+
+    let ellipse = warp::post()
+        .and(warp::path("ellipse"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_ellipse_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::shapes::handle_ellipse_request);
+
+    let list_layers = warp::get()
+        .and(warp::path("layers"))
+        .and(warp::path::end())
+        .and(view.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::layers::handle_list_layers);
+
+    let create_layer = warp::post()
+        .and(warp::path("layers"))
+        .and(warp::path::end())
+        .and(change.clone())
+        .and(handlers::utils::parse_create_layer_request())
+        .and(app_filter.clone())
+        .and_then(handlers::layers::handle_create_layer_request);
+
+    let update_layer = warp::put()
+        .and(warp::path!("layers" / String))
+        .and(change.clone())
+        .and(handlers::utils::parse_update_layer_request())
+        .and(app_filter.clone())
+        .and_then(handlers::layers::handle_update_layer_request);
+
+    let delete_layer = warp::delete()
+        .and(warp::path!("layers" / String))
+        .and(change.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::layers::handle_delete_layer_request);
+
+    let transform = warp::post()
+        .and(warp::path("transform"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_transform_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::transform::handle_transform_request);
+
+    let gradient = warp::post()
+        .and(warp::path("gradient"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_gradient_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::gradient::handle_gradient_request);
+
+    let copy = warp::post()
+        .and(warp::path("copy"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_copy_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::clipboard::handle_copy_request);
+
+    let cut = warp::post()
+        .and(warp::path("cut"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_copy_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::clipboard::handle_cut_request);
+
+    let paste = warp::post()
+        .and(warp::path("paste"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_paste_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::clipboard::handle_paste_request);
+
+    let list_stamps = warp::get()
+        .and(warp::path("stamps"))
+        .and(warp::path::end())
+        .and(view.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::stamps::handle_list_stamps);
+
+    let create_stamp = warp::post()
+        .and(warp::path("stamps"))
+        .and(warp::path::end())
+        .and(change.clone())
+        .and(handlers::utils::parse_create_stamp_request())
+        .and(app_filter.clone())
+        .and_then(handlers::stamps::handle_create_stamp_request);
+
+    let stamp = warp::post()
+        .and(warp::path("stamp"))
+        .and(warp::path::end())
+        .and(handlers::rate_limit::admit_valid(limiter.clone(), drawer.clone(), handlers::utils::parse_stamp_request()))
+        .and(app_filter.clone())
+        .and_then(handlers::stamps::handle_stamp_request);
+
+    let set_clip = warp::post()
+        .and(warp::path("clip"))
+        .and(warp::path::end())
+        .and(change.clone())
+        .and(handlers::utils::parse_clip_request())
+        .and(app_filter.clone())
+        .and_then(handlers::clip::handle_set_clip_request);
+
+    let clear_clip = warp::delete()
+        .and(warp::path("clip"))
+        .and(warp::path::end())
+        .and(change.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::clip::handle_clear_clip_request);
+
+    let list_locks = warp::get()
+        .and(warp::path("locks"))
+        .and(warp::path::end())
+        .and(view.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::locks::handle_list_locks);
+
+    let list_history = warp::get()
+        .and(warp::path("history"))
+        .and(warp::path::end())
+        .and(view.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::history::handle_list_history);
+
+    let create_lock = warp::post()
+        .and(warp::path("locks"))
+        .and(warp::path::end())
+        .and(admin.clone())
+        .and(handlers::utils::parse_create_lock_request())
+        .and(app_filter.clone())
+        .and_then(handlers::locks::handle_create_lock_request);
+
+    let delete_lock = warp::delete()
+        .and(warp::path!("locks" / String))
+        .and(admin.clone())
+        .and(app_filter.clone())
+        .and_then(handlers::locks::handle_delete_lock_request);
+
+    let resize = warp::post()
+        .and(warp::path("resize"))
+        .and(warp::path::end())
+        .and(admin.clone())
+        .and(handlers::utils::parse_resize_request())
+        .and(app_filter.clone())
+        .and_then(handlers::resize::handle_resize_request);
+
+    let crop = warp::post()
+        .and(warp::path("crop"))
+        .and(warp::path::end())
+        .and(admin.clone())
+        .and(handlers::utils::parse_crop_request())
+        .and(app_filter.clone())
+        .and_then(handlers::resize::handle_crop_request);
+
     let ws_route = warp::path("canvas")
+        .and(view.clone())
         .and(warp::ws())
         .and(app_filter.clone())
         .and_then(handlers::ws::ws_handler);
 
     let routes = fill_rectangle
         .or(flood_fill)
+        .or(line)
+        .or(ellipse)
         .or(get_canvas)
+        .or(get_canvas_ansi)
+        .or(list_layers)
+        .or(create_layer)
+        .or(update_layer)
+        .or(delete_layer)
+        .or(transform)
+        .or(gradient)
+        .or(copy)
+        .or(cut)
+        .or(paste)
+        .or(list_stamps)
+        .or(create_stamp)
+        .or(stamp)
+        .or(set_clip)
+        .or(clear_clip)
+        .or(list_locks)
+        .or(create_lock)
+        .or(delete_lock)
+        .or(list_history)
+        .or(resize)
+        .or(crop)
         .or(ws_route)
         .recover(handlers::handle_rejections::handle_rejection);
 
-    warp::serve(routes)
-        .run(([127, 0, 0, 1], 8080))
-        .await;
+    let server = warp::serve(routes);
+
+    match listen {
+        Listen::Tcp(address) => {
+            let (address, server) = server.try_bind_with_graceful_shutdown(address, shutdown)?;
+            println!("Listening on http://{}", address);
+            server.await;
+        },
+        Listen::Unix(path) => {
+            remove_stale_socket(&path)?;
+            let mut listener = UnixListener::bind(&path)?;
+            println!("Listening on {}", path.display());
+            server.serve_incoming_with_graceful_shutdown(listener.incoming(), shutdown).await;
+            fs::remove_file(&path)?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Completes when the process is asked to stop, with Ctrl-C (SIGINT) or SIGTERM
+pub async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Problem listening for SIGTERM");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+
+    println!("Shutting down, finishing requests in progress");
+}
+
+/// Removes a socket left behind by a server which didn't shut down cleanly, so it can be bound again
+/// Anything at the path other than a socket is left alone, and binding fails
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::prelude::*;
+    use std::os::unix::net::UnixStream;
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+    use uuid::Uuid;
+
+    use super::*;
+    use super::super::super::drawing_app::application;
+    #[tokio::test]
+    async fn test_serves_over_a_unix_socket_until_shut_down() {
+        let canvas_location = Uuid::new_v4().to_string();
+        let temp_canvas_location = Uuid::new_v4().to_string();
+        let snapshot_canvas_location = Uuid::new_v4().to_string();
+        let socket = env::temp_dir().join(format!("{}.sock", Uuid::new_v4()));
+
+        let app = Arc::new(RwLock::new(application::DrawingApplication::initialize(
+            application::ApplicationOptions {
+                width: 3,
+                height: 1,
+                blank_character: String::from("."),
+                canvas_path: canvas_location.clone(),
+                canvas_temp_path: temp_canvas_location.clone(),
+                canvas_snapshot_path: snapshot_canvas_location.clone(),
+                strict: false,
+            }
+        )));
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let rate_limits = RateLimits { requests_per_minute: None, burst: 10, cells_per_minute: None };
+        let server = tokio::spawn(run(app, Listen::Unix(socket.clone()), vec!(), rate_limits, async {
+            stopped.await.ok();
+        }));
+
+        // the client blocks, so it runs on its own thread while the runtime drives the server
+        let (reply, replied) = oneshot::channel();
+        let path = socket.clone();
+        thread::spawn(move || {
+            let mut stream = loop {
+                match UnixStream::connect(&path) {
+                    Ok(stream) => break stream,
+                    Err(_) => thread::sleep(Duration::from_millis(10)),
+                }
+            };
+            stream.write_all(b"GET /ansi HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            reply.send(response).unwrap();
+        });
+
+        let response = replied.await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("...\n"));
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        assert!(!socket.exists());
+
+        // clean up
+        if Path::new(&canvas_location).exists() {
+            fs::remove_file(&canvas_location).unwrap();
+        }
+        if Path::new(&temp_canvas_location).exists() {
+            fs::remove_file(&temp_canvas_location).unwrap();
+        }
+        if Path::new(&snapshot_canvas_location).exists() {
+            fs::remove_file(&snapshot_canvas_location).unwrap();
+        }
+    }
 }
\ No newline at end of file